@@ -10,11 +10,13 @@ fn main() {
     let git_hash = get_git_hash();
     let short_hash = git_hash.trim().chars().take(7).collect::<String>();
 
+    // The environment variables have to be emitted on every run of the build script,
+    // otherwise a rerun with unchanged git info would leave them undefined.
+    println!("cargo:rustc-env=GIT_HASH={}", short_hash);
+    println!("cargo:rustc-env=GIT_BRANCH={}", git_branch.trim());
+
     // Only update if git info has changed
     if needs_update(&git_info_path, &git_branch, &short_hash) {
-        println!("cargo:rustc-env=GIT_HASH={}", short_hash);
-        println!("cargo:rustc-env=GIT_BRANCH={}", git_branch.trim());
-
         // Write current git info to file for future comparison
         fs::write(git_info_path, format!("{}\n{}", git_branch, short_hash)).unwrap();
     }
@@ -27,7 +29,7 @@ fn main() {
 fn get_git_branch() -> String {
     env::var("GIT_BRANCH").unwrap_or_else(|_| {
         Command::new("git")
-            .args(&["rev-parse", "--abbrev-ref", "HEAD"])
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
             .output()
             .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
             .unwrap_or_else(|_| "unknown".to_string())
@@ -37,7 +39,7 @@ fn get_git_branch() -> String {
 fn get_git_hash() -> String {
     env::var("GIT_HASH").unwrap_or_else(|_| {
         Command::new("git")
-            .args(&["rev-parse", "--short", "HEAD"])
+            .args(["rev-parse", "--short", "HEAD"])
             .output()
             .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
             .unwrap_or_else(|_| "unknown".to_string())
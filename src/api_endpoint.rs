@@ -1,11 +1,17 @@
-use anyhow::anyhow;
-use reqwasm::http::Request;
+use std::fmt;
+
+use reqwasm::http::{Request, Response};
 use serde_json::{Value, json};
 use sycamore::prelude::*;
+use web_sys::js_sys::{Array, Date, Uint8Array};
+use web_sys::{Blob, BlobPropertyBag, FormData};
 
+use crate::mock_provider::{self, MOCK_PROVIDER_URL};
 use crate::models::{
-    chatlog::{Message, StackedMessage, parse_think_block},
-    config::ApiEndpointConfig,
+    chatlog::{Message, StackedMessage, inline_file_attachments, parse_think_block},
+    config::{ApiEndpointConfig, ApiProvider, ModelPrice},
+    documents::{Citation, build_context_message},
+    request_inspector::{REDACTED_API_KEY, RecordedExchange, RequestInspector},
     system_message::SystemMessage,
 };
 
@@ -14,7 +20,10 @@ const RESPONSE_RESERVATION: u32 = 2000;
 const MAX_REQUEST_TOKENS: u32 = TOTAL_API_LIMIT - RESPONSE_RESERVATION;
 const CHARS_PER_TOKEN_ESTIMATE: f32 = 4.0;
 
-fn estimate_tokens(text: &str) -> u32 {
+/// How many inputs are embedded with a single request to the embeddings endpoint.
+pub const EMBEDDING_BATCH_SIZE: usize = 64;
+
+pub(crate) fn estimate_tokens(text: &str) -> u32 {
     let count = text.chars().count();
     if count == 0 {
         0
@@ -32,10 +41,107 @@ pub struct CompletionResponse {
     pub prompt_ms: Option<f64>,
 }
 
-/// Sends a chat request to the API with the given messages and handles the response.
-pub fn send_chat_completion_request<F>(msgs: Vec<Message>, is_regenerating: bool, on_response: F)
-where
-    F: FnOnce(anyhow::Result<CompletionResponse>) + 'static,
+/// The different ways a chat completion request can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// The request never got a response, e.g. a bad endpoint URL, CORS or no connection.
+    Network(String),
+    /// The endpoint answered with a non-success HTTP status and the provider's message.
+    HttpStatus { status: u16, message: String },
+    /// The provider refused to generate or finish the reply due to content moderation.
+    ContentFiltered(String),
+    /// The response couldn't be understood as an OpenAI compatible completion.
+    MalformedResponse(String),
+    /// The response was understood but contained no text content for the reply.
+    EmptyContent(String),
+    /// The prompt was too large for the model's context window.
+    ContextLengthExceeded(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Network(msg) => write!(f, "Network error: {}", msg),
+            ApiError::HttpStatus { status, message } => {
+                write!(f, "API request failed ({}): {}", status, message)
+            }
+            ApiError::ContentFiltered(msg) => write!(f, "Content filtered: {}", msg),
+            ApiError::MalformedResponse(msg) => write!(f, "Malformed response: {}", msg),
+            ApiError::EmptyContent(msg) => write!(f, "Empty response: {}", msg),
+            ApiError::ContextLengthExceeded(msg) => write!(f, "Context length exceeded: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+// Takes the HTTP status and body of a failed request and classifies it into an `ApiError`,
+// pulling the provider's message out of the usual `{"error": {"message": ...}}` shape
+// when it's present.
+fn classify_error_response(status: u16, body: &str) -> ApiError {
+    let error_json = serde_json::from_str::<Value>(body).ok();
+    let error_obj = error_json.as_ref().map(|json| &json["error"]);
+    let message = error_obj
+        .and_then(|e| e["message"].as_str().or_else(|| e.as_str()))
+        .unwrap_or(body)
+        .trim()
+        .to_string();
+    let code = error_obj
+        .map(|e| {
+            format!(
+                "{} {}",
+                e["code"].as_str().unwrap_or_default(),
+                e["type"].as_str().unwrap_or_default()
+            )
+        })
+        .unwrap_or_default();
+
+    let haystack = format!("{} {}", code, message).to_lowercase();
+    if status == 413
+        || haystack.contains("context_length_exceeded")
+        || haystack.contains("context length")
+        || haystack.contains("context window")
+        || haystack.contains("maximum context")
+    {
+        ApiError::ContextLengthExceeded(message)
+    } else if haystack.contains("content_filter")
+        || haystack.contains("content_policy")
+        || haystack.contains("moderation")
+    {
+        ApiError::ContentFiltered(message)
+    } else {
+        ApiError::HttpStatus { status, message }
+    }
+}
+
+// returns the number of tokens a request to the endpoint may use in total.
+fn request_token_budget(api_config: &ApiEndpointConfig) -> u32 {
+    if api_config.target_context_size.is_none() {
+        MAX_REQUEST_TOKENS
+    } else {
+        api_config.get_target_context_size()
+    }
+}
+
+/// Returns how many tokens of a request the chunks of the attached documents may use,
+/// which leaves the other half of the budget for the conversation itself.
+pub fn document_context_budget(api_config: &ApiEndpointConfig) -> u32 {
+    request_token_budget(api_config) / 2
+}
+
+/// Sends a chat request to the API with the given messages and handles the response. The
+/// response is a reply to the last of the messages.
+///
+/// Prior thinking content is stripped from the messages unless `keep_reasoning` is set.
+/// The chunks of the attached documents in `document_context` are sent in a system
+/// message after the main one and count against the token budget.
+pub fn send_chat_completion_request<F>(
+    msgs: Vec<Message>,
+    keep_reasoning: bool,
+    document_context: &[Citation],
+    on_response: F,
+) where
+    F: FnOnce(Result<CompletionResponse, ApiError>) + 'static,
 {
     // pull the api endpoint configuration from the context
     let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
@@ -45,11 +151,7 @@ where
         api_config
     );
 
-    let mut working_token_budget = if api_config.target_context_size.is_none() {
-        MAX_REQUEST_TOKENS
-    } else {
-        api_config.get_target_context_size()
-    };
+    let mut working_token_budget = request_token_budget(&api_config);
     let max_working_token_budget = working_token_budget;
     console_log!("Working token budget: {}", max_working_token_budget);
     let mut messages: Vec<_> = Vec::new();
@@ -58,21 +160,22 @@ where
     let system_message_context = use_context::<SystemMessage>();
     let system_message = system_message_context.signal().get_clone();
     let system_message_trimmed = system_message.trim();
-    working_token_budget -= estimate_tokens(system_message_trimmed);
+    working_token_budget =
+        working_token_budget.saturating_sub(estimate_tokens(system_message_trimmed));
+
+    // the relevant chunks of the attached documents
+    let context_message = if document_context.is_empty() {
+        String::new()
+    } else {
+        build_context_message(document_context)
+    };
+    working_token_budget = working_token_budget.saturating_sub(estimate_tokens(&context_message));
 
     // construct the message history list
-    let mut regen_skip = is_regenerating;
     let mut first_message = true;
     for m in msgs.iter().rev() {
-        // before the `first_message` skip, we take out the most recent message
-        // if we're regenerating it.
-        if regen_skip {
-            regen_skip = false;
-            continue;
-        }
-
-        // we need to remove the thinking content when sending in messages as this
-        // is currently considered best practice.
+        // by default we remove the thinking content when sending in messages as this
+        // is currently considered best practice, but the chatlog can opt to keep it.
         let current_message = m.get_selected_message().unwrap_or_else(|| {
             debug_assert!(
                 false,
@@ -82,32 +185,36 @@ where
         });
 
         let content = match parse_think_block(current_message.message.clone()) {
-            Some((main_content, _)) => main_content,
-            None => current_message.message.clone(),
+            Some((main_content, _)) if !keep_reasoning => main_content,
+            _ => current_message.message.trim().to_string(),
         };
+        let content = inline_file_attachments(&content, &current_message.files);
         let msg_token_est = estimate_tokens(&content);
         if msg_token_est <= working_token_budget {
             let mut added_msg_already = false;
 
             // only when processing the first message do we look for image data.
             // if the image data is present, then we have to encode our JSON
-            // request object differently to pair the message with the image.
-            if first_message {
-                if let Some(image_base64) = &current_message.image_base64 {
+            // request object differently to pair the message with the images,
+            // each of which is sent as its own part.
+            // generated images in the AI's own messages are never sent back.
+            if first_message && !m.ai_generated {
+                if !current_message.images.is_empty() {
+                    let mut parts = vec![json!({
+                        "type": "text",
+                        "text": content,
+                    })];
+                    parts.extend(current_message.images.iter().map(|image| {
+                        json!({
+                            "type": "image_url",
+                            "image_url": {
+                                "url": image,
+                            },
+                        })
+                    }));
                     messages.push(json!({
                         "role": "user",
-                        "content": [
-                            {
-                                "type": "text",
-                                "text": content,
-                            },
-                            {
-                                "type": "image_url",
-                                "image_url": {
-                                    "url": image_base64,
-                                },
-                            }
-                        ]
+                        "content": parts,
                     }));
                     added_msg_already = true;
                 }
@@ -128,6 +235,17 @@ where
     }
     messages.reverse();
 
+    // the document context goes first so that the system message ends up before it
+    if !context_message.is_empty() {
+        messages.insert(
+            0,
+            json!({
+                "role": "system",
+                "content": context_message,
+            }),
+        );
+    }
+
     // if the system message is present, we insert it at the beginning of the message
     // stack and label the role appropriately.
     if !system_message_trimmed.is_empty() {
@@ -147,7 +265,7 @@ where
     console_log!(
         "A total of {} messages sent; The system message is approx. {} tokens; Toal estimated: {}.",
         messages.len(),
-        estimate_tokens(&system_message_trimmed),
+        estimate_tokens(system_message_trimmed),
         max_working_token_budget - working_token_budget
     );
 
@@ -175,102 +293,680 @@ where
     if api_config.repetition_penalty.is_some() {
         request_body["repetition_penalty"] = json!(api_config.get_repetition_penalty());
     }
-    //console_log!("DEBUG: request body: {}", request_body);
+    if let Some(effort) = api_config.get_reasoning_effort() {
+        request_body["reasoning_effort"] = json!(effort);
+    }
+    if api_config.thinking_budget.is_some() {
+        // this is the Anthropic style of requesting extended thinking with a token budget.
+        request_body["thinking"] = json!({
+            "type": "enabled",
+            "budget_tokens": api_config.get_thinking_budget(),
+        });
+    }
+
+    let url = chat_completions_url(&api_config);
+    let inspector = use_context::<RequestInspector>();
+    dispatch_chat_completion_request(api_config, url, request_body, inspector, on_response);
+}
+
+/// The URL chat completion requests are sent to with this configuration.
+pub fn chat_completions_url(api_config: &ApiEndpointConfig) -> String {
+    // the mock provider answers offline, without ever touching the network
+    if api_config.provider == ApiProvider::Mock {
+        MOCK_PROVIDER_URL.to_string()
+    } else {
+        format!("{}/chat/completions", api_config.endpoint)
+    }
+}
+
+// The configuration a recorded request is replayed with. The API key only belongs to the
+// current endpoint, so a request recorded for any other URL is sent without it.
+fn replay_config(api_config: ApiEndpointConfig, url: &str) -> ApiEndpointConfig {
+    if chat_completions_url(&api_config) == url {
+        api_config
+    } else {
+        ApiEndpointConfig {
+            api_key: String::new(),
+            ..api_config
+        }
+    }
+}
+
+/// Sends a previously recorded request again, exactly as it was, to the same URL. The API
+/// key from the current configuration is only sent along if that URL is the one of the
+/// current endpoint. The new exchange is recorded as well.
+pub fn replay_chat_completion_request<F>(
+    api_config: ApiEndpointConfig,
+    exchange: &RecordedExchange,
+    inspector: RequestInspector,
+    on_response: F,
+) where
+    F: FnOnce(Result<CompletionResponse, ApiError>) + 'static,
+{
+    match serde_json::from_str::<Value>(&exchange.request_body) {
+        Ok(request_body) => dispatch_chat_completion_request(
+            replay_config(api_config, &exchange.url),
+            exchange.url.clone(),
+            request_body,
+            inspector,
+            on_response,
+        ),
+        Err(e) => on_response(Err(ApiError::MalformedResponse(format!(
+            "the recorded request body isn't valid JSON: {}",
+            e
+        )))),
+    }
+}
+
+// The raw response to a request, before any of it is parsed.
+pub(crate) struct RawResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+// POSTs the request body to the URL, or hands it to the mock provider, then records the raw
+// exchange in the request inspector and parses the response.
+fn dispatch_chat_completion_request<F>(
+    api_config: ApiEndpointConfig,
+    url: String,
+    request_body: Value,
+    inspector: RequestInspector,
+    on_response: F,
+) where
+    F: FnOnce(Result<CompletionResponse, ApiError>) + 'static,
+{
+    let request_headers = vec![
+        ("Content-Type".to_string(), "application/json".to_string()),
+        (
+            "Authorization".to_string(),
+            format!("Bearer {}", api_config.api_key),
+        ),
+        (
+            "HTTP-Referer".to_string(),
+            "https://github.com/tbogdala/switchboard".to_string(),
+        ),
+        ("X-Title".to_string(), "Switchboard!".to_string()),
+    ];
 
-    // make a POST request to the API
     wasm_bindgen_futures::spawn_local(async move {
-        let result = Request::post(&format!("{}/chat/completions", api_config.endpoint))
-            .header("Content-Type", "application/json")
-            .header("Authorization", &format!("Bearer {}", api_config.api_key))
-            .header("HTTP-Referer", "https://github.com/tbogdala/switchboard")
-            .header("X-Title", "Switchboard!")
-            .body(request_body.to_string())
-            .send()
-            .await;
-
-        match result {
-            Ok(response) => {
-                if response.ok() {
-                    match response.text().await {
-                        Ok(text) => {
-                            // console_log!("DEBUG: Response text: {}", &text);
-                            if let Ok(response) = extract_chat_completion_response(&text) {
-                                on_response(Ok(response));
-                            }
-                        }
-                        Err(e) => {
-                            console_log!("Error reading response text: {}", e.to_string());
-                            on_response(Err(anyhow!(
-                                "Error reading response text: {}",
-                                e.to_string()
-                            )));
-                        }
-                    }
+        let started = Date::now();
+        let body = request_body.to_string();
+        let result = if url == MOCK_PROVIDER_URL {
+            mock_provider::mock_chat_completion(api_config.mock, &request_body).await
+        } else {
+            // make a POST request to the API
+            let request = request_headers
+                .iter()
+                .fold(Request::post(&url), |request, (name, value)| {
+                    request.header(name, value)
+                });
+            match request.body(body.clone()).send().await {
+                Ok(response) => match response.text().await {
+                    Ok(text) => Ok(RawResponse {
+                        status: response.status(),
+                        headers: response.headers().entries().collect(),
+                        body: text,
+                    }),
+                    Err(e) => Err(format!("unable to read the response body: {}", e)),
+                },
+                Err(e) => Err(e.to_string()),
+            }
+        };
+
+        // keep the exchange for the inspector, without the API key
+        let redacted_headers = request_headers
+            .into_iter()
+            .map(|(name, value)| {
+                if api_config.api_key.is_empty() {
+                    (name, value)
                 } else {
-                    match response.text().await {
-                        Ok(error_text) => {
-                            // Try to parse the error response JSON
-                            if let Ok(error_json) =
-                                serde_json::from_str::<serde_json::Value>(&error_text)
-                            {
-                                if let Some(message) = error_json["error"]["message"].as_str() {
-                                    console_log!("API request failed: {}", message);
-                                    on_response(Err(anyhow!("API request failed: {}", message)));
-                                } else {
-                                    console_log!(
-                                        "API request failed with unexpected error format: {}",
-                                        error_text
-                                    );
-                                    on_response(Err(anyhow!("API request failed: {}", error_text)));
-                                }
-                            } else {
-                                console_log!(
-                                    "API request failed with non-JSON response: {}",
-                                    error_text
-                                );
-                                on_response(Err(anyhow!("API request failed: {}", error_text)));
-                            }
-                        }
-                        Err(e) => {
-                            console_log!(
-                                "API request failed and couldn't read error response: {}",
-                                e.to_string()
-                            );
-                            on_response(Err(anyhow!(
-                                "API request failed and couldn't read error response"
-                            )));
-                        }
-                    }
+                    (name, value.replace(&api_config.api_key, REDACTED_API_KEY))
+                }
+            })
+            .collect();
+        inspector.record(RecordedExchange {
+            id: 0,
+            sent_at: String::from(Date::new(&started.into()).to_locale_time_string("default")),
+            url,
+            request_headers: redacted_headers,
+            request_body: body,
+            status: result.as_ref().ok().map(|r| r.status),
+            response_headers: result
+                .as_ref()
+                .map(|r| r.headers.clone())
+                .unwrap_or_default(),
+            response_body: result.as_ref().map(|r| r.body.clone()).unwrap_or_default(),
+            error: result.as_ref().err().cloned(),
+            elapsed_ms: (Date::now() - started) as u32,
+        });
+
+        let outcome = match result {
+            Ok(response) => parse_chat_completion_response(response.status, &response.body),
+            Err(e) => Err(ApiError::Network(e)),
+        };
+
+        if let Err(e) = &outcome {
+            console_log!("Completion request failed: {:?}", e);
+        }
+        on_response(outcome);
+    });
+}
+
+// Parses the raw response to a completion request, whether the reply came back
+// whole or as a stream of server-sent events.
+pub(crate) fn parse_chat_completion_response(
+    status: u16,
+    body: &str,
+) -> Result<CompletionResponse, ApiError> {
+    if !(200..300).contains(&status) {
+        Err(classify_error_response(status, body))
+    } else if body.trim_start().starts_with("data:") {
+        extract_streamed_chat_completion_response(body)
+    } else {
+        extract_chat_completion_response(body)
+    }
+}
+
+// Sends a request that isn't a chat completion and waits for a successful response. No
+// response at all or a failed status is turned into an `ApiError`.
+async fn send_request(request: Request) -> Result<Response, ApiError> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ApiError::Network(e.to_string()))?;
+    if response.ok() {
+        Ok(response)
+    } else {
+        let error_text = response.text().await.unwrap_or_default();
+        Err(classify_error_response(response.status(), &error_text))
+    }
+}
+
+// Sends the request and parses the body of the successful response with `extract`.
+async fn fetch_and_extract<T>(
+    request: Request,
+    extract: impl FnOnce(&str) -> Result<T, ApiError>,
+) -> Result<T, ApiError> {
+    let response = send_request(request).await?;
+    let text = response
+        .text()
+        .await
+        .map_err(|e| ApiError::Network(format!("unable to read the response body: {}", e)))?;
+    extract(&text)
+}
+
+// Runs the request in the background and hands its outcome to `on_response`. Failures are
+// logged under the `name` of the request.
+fn spawn_request<T, F>(
+    name: &'static str,
+    request: impl Future<Output = Result<T, ApiError>> + 'static,
+    on_response: F,
+) where
+    F: FnOnce(Result<T, ApiError>) + 'static,
+{
+    wasm_bindgen_futures::spawn_local(async move {
+        let outcome = request.await;
+        if let Err(e) = &outcome {
+            console_log!("{} request failed: {:?}", name, e);
+        }
+        on_response(outcome);
+    });
+}
+
+/// Requests embedding vectors for the inputs from the `/embeddings` endpoint configured
+/// for searching chatlogs. The vectors come back in the same order as the inputs.
+pub async fn fetch_embeddings(
+    api_config: &ApiEndpointConfig,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, ApiError> {
+    let embeddings = &api_config.embeddings;
+    let url = format!(
+        "{}/embeddings",
+        api_config.service_endpoint(&embeddings.endpoint)
+    );
+    let request_body = json!({
+        "model": embeddings.model_id,
+        "input": inputs,
+    });
+
+    let request = Request::post(&url)
+        .header("Content-Type", "application/json")
+        .header(
+            "Authorization",
+            &format!("Bearer {}", api_config.service_api_key(&embeddings.api_key)),
+        )
+        .body(request_body.to_string());
+    fetch_and_extract(request, |text| extract_embeddings(text, inputs.len())).await
+}
+
+// Pulls the vectors out of an embeddings response, ordered by their `index`.
+fn extract_embeddings(json_response: &str, expected: usize) -> Result<Vec<Vec<f32>>, ApiError> {
+    let parsed_value: Value = serde_json::from_str(json_response)
+        .map_err(|e| ApiError::MalformedResponse(format!("Failed to parse JSON: {}", e)))?;
+    let data = parsed_value
+        .get("data")
+        .and_then(|data| data.as_array())
+        .ok_or_else(|| {
+            ApiError::MalformedResponse("Could not find 'data' in the JSON".to_string())
+        })?;
+
+    let mut vectors: Vec<(u64, Vec<f32>)> = Vec::with_capacity(data.len());
+    for (position, item) in data.iter().enumerate() {
+        let vector = item
+            .get("embedding")
+            .and_then(|e| e.as_array())
+            .ok_or_else(|| {
+                ApiError::MalformedResponse("Could not find 'embedding' in the JSON".to_string())
+            })?
+            .iter()
+            .map(|v| v.as_f64().unwrap_or_default() as f32)
+            .collect();
+        let index = item
+            .get("index")
+            .and_then(|i| i.as_u64())
+            .unwrap_or(position as u64);
+        vectors.push((index, vector));
+    }
+    vectors.sort_by_key(|(index, _)| *index);
+
+    if vectors.len() != expected {
+        return Err(ApiError::MalformedResponse(format!(
+            "Expected {} embeddings but got {}",
+            expected,
+            vectors.len()
+        )));
+    }
+    Ok(vectors.into_iter().map(|(_, vector)| vector).collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedImage {
+    pub image_url: String, // a data URL with the base64 encoded image, or a remote URL
+    pub revised_prompt: Option<String>,
+    pub prompt_tokens: Option<i64>, // reported by the models that are billed per token
+    pub completion_tokens: Option<i64>,
+}
+
+/// Sends a prompt to the `/images/generations` endpoint configured for image generation
+/// and hands back the generated image.
+pub fn send_image_generation_request<F>(
+    api_config: ApiEndpointConfig,
+    prompt: String,
+    on_response: F,
+) where
+    F: FnOnce(Result<GeneratedImage, ApiError>) + 'static,
+{
+    let image_generation = &api_config.image_generation;
+    let url = format!(
+        "{}/images/generations",
+        api_config.service_endpoint(&image_generation.endpoint)
+    );
+    let api_key = api_config
+        .service_api_key(&image_generation.api_key)
+        .to_string();
+    let mut request_body = json!({
+        "prompt": prompt,
+        "n": 1,
+        "response_format": "b64_json",
+    });
+    if !image_generation.model_id.trim().is_empty() {
+        request_body["model"] = json!(image_generation.model_id.trim());
+    }
+    if !image_generation.size.trim().is_empty() {
+        request_body["size"] = json!(image_generation.size.trim());
+    }
+
+    let request = Request::post(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {}", api_key))
+        .body(request_body.to_string());
+    spawn_request(
+        "Image generation",
+        fetch_and_extract(request, extract_generated_image),
+        on_response,
+    );
+}
+
+// Pulls the first image out of an image generation response. The image is turned into
+// a data URL so it can be stored in the chatlog; servers that only return a URL to the
+// image have that used instead.
+fn extract_generated_image(json_response: &str) -> Result<GeneratedImage, ApiError> {
+    let parsed_value: Value = serde_json::from_str(json_response)
+        .map_err(|e| ApiError::MalformedResponse(format!("Failed to parse JSON: {}", e)))?;
+    let image = parsed_value
+        .get("data")
+        .and_then(|data| data.get(0))
+        .ok_or_else(|| {
+            ApiError::MalformedResponse("Could not find 'data[0]' in the JSON".to_string())
+        })?;
+    let revised_prompt = image
+        .get("revised_prompt")
+        .and_then(|p| p.as_str())
+        .map(|p| p.to_string());
+
+    let image_url = if let Some(b64) = image.get("b64_json").and_then(|b| b.as_str()) {
+        // PNG is the default output format of the OpenAI API and stable diffusion servers
+        format!("data:image/png;base64,{}", b64)
+    } else if let Some(url) = image.get("url").and_then(|u| u.as_str()) {
+        url.to_string()
+    } else {
+        return Err(ApiError::EmptyContent(
+            "The response didn't include any image data".to_string(),
+        ));
+    };
+    let usage = parsed_value.get("usage");
+    Ok(GeneratedImage {
+        image_url,
+        revised_prompt,
+        prompt_tokens: usage
+            .and_then(|usage| usage.get("input_tokens"))
+            .and_then(|tokens| tokens.as_i64()),
+        completion_tokens: usage
+            .and_then(|usage| usage.get("output_tokens"))
+            .and_then(|tokens| tokens.as_i64()),
+    })
+}
+
+/// Sends recorded audio to the `/audio/transcriptions` endpoint configured for voice input
+/// and hands back the transcript.
+pub fn send_transcription_request<F>(
+    api_config: ApiEndpointConfig,
+    audio: Blob,
+    file_name: &str,
+    on_response: F,
+) where
+    F: FnOnce(Result<String, ApiError>) + 'static,
+{
+    let transcription = &api_config.transcription;
+    let url = format!(
+        "{}/audio/transcriptions",
+        api_config.service_endpoint(&transcription.endpoint)
+    );
+    let api_key = api_config
+        .service_api_key(&transcription.api_key)
+        .to_string();
+
+    // the audio is sent as multipart form data, the same as a file upload
+    let form_data = match FormData::new() {
+        Ok(form_data) => form_data,
+        Err(e) => {
+            on_response(Err(ApiError::Network(format!(
+                "unable to create the form data: {:?}",
+                e
+            ))));
+            return;
+        }
+    };
+    let mut fields = vec![
+        ("model", transcription.model_id.clone()),
+        ("response_format", "json".to_string()),
+    ];
+    if let Some(language) = transcription.language.as_ref() {
+        fields.push(("language", language.clone()));
+    }
+    let appended = form_data
+        .append_with_blob_and_filename("file", &audio, file_name)
+        .and_then(|_| {
+            fields
+                .iter()
+                .try_for_each(|(name, value)| form_data.append_with_str(name, value))
+        });
+    if let Err(e) = appended {
+        on_response(Err(ApiError::Network(format!(
+            "unable to build the form data: {:?}",
+            e
+        ))));
+        return;
+    }
+
+    let request = Request::post(&url)
+        .header("Authorization", &format!("Bearer {}", api_key))
+        .body(form_data);
+    spawn_request(
+        "Transcription",
+        fetch_and_extract(request, extract_transcript),
+        on_response,
+    );
+}
+
+/// Sends text to the `/audio/speech` endpoint configured for reading messages aloud and
+/// hands back the synthesized audio.
+pub fn send_speech_request<F>(api_config: ApiEndpointConfig, text: String, on_response: F)
+where
+    F: FnOnce(Result<Blob, ApiError>) + 'static,
+{
+    let speech = &api_config.speech;
+    let url = format!(
+        "{}/audio/speech",
+        api_config.service_endpoint(&speech.endpoint)
+    );
+    let api_key = api_config.service_api_key(&speech.api_key).to_string();
+    let request_body = json!({
+        "model": speech.model_id,
+        "voice": speech.voice,
+        "input": text,
+        "response_format": "mp3",
+    });
+
+    let request = Request::post(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {}", api_key))
+        .body(request_body.to_string());
+    let fetch_audio = async move {
+        let response = send_request(request).await?;
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .unwrap_or_else(|| "audio/mpeg".to_string());
+        let bytes = response
+            .binary()
+            .await
+            .map_err(|e| ApiError::Network(format!("unable to read the response body: {}", e)))?;
+        if bytes.is_empty() {
+            return Err(ApiError::EmptyContent(
+                "The speech endpoint returned no audio".to_string(),
+            ));
+        }
+        let parts = Array::of1(&Uint8Array::from(bytes.as_slice()));
+        let options = BlobPropertyBag::new();
+        options.set_type(&content_type);
+        Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+            .map_err(|e| ApiError::MalformedResponse(format!("unusable audio: {:?}", e)))
+    };
+    spawn_request("Speech", fetch_audio, on_response);
+}
+
+// Pulls the text out of a transcription response, which is a JSON object with a `text`
+// field. Some servers answer with the plain text instead, which is used as it is.
+fn extract_transcript(response: &str) -> Result<String, ApiError> {
+    let text = match serde_json::from_str::<Value>(response) {
+        Ok(json) => json
+            .get("text")
+            .and_then(|text| text.as_str())
+            .map(|text| text.to_string())
+            .ok_or_else(|| {
+                ApiError::MalformedResponse("Could not find 'text' in the JSON".to_string())
+            })?,
+        Err(_) => response.to_string(),
+    };
+    if text.trim().is_empty() {
+        Err(ApiError::EmptyContent(
+            "No speech was recognized in the recording".to_string(),
+        ))
+    } else {
+        Ok(text.trim().to_string())
+    }
+}
+
+/// Requests the `/models` listing from the API and parses out the prices of all the models
+/// that have them. Prices are reported per token and converted to USD per million tokens.
+pub fn fetch_model_prices<F>(api_config: ApiEndpointConfig, on_response: F)
+where
+    F: FnOnce(Result<Vec<ModelPrice>, ApiError>) + 'static,
+{
+    let request = Request::get(&format!("{}/models", api_config.endpoint))
+        .header("Authorization", &format!("Bearer {}", api_config.api_key));
+    spawn_request(
+        "Model listing",
+        fetch_and_extract(request, extract_model_prices),
+        on_response,
+    );
+}
+
+// Parses the OpenRouter style `{"data": [{"id": ..., "pricing": {"prompt": ..., "completion": ...}}]}`
+// listing of models. The prices may be given as strings or numbers.
+fn extract_model_prices(json_response: &str) -> Result<Vec<ModelPrice>, ApiError> {
+    let parsed_value: Value = serde_json::from_str(json_response)
+        .map_err(|e| ApiError::MalformedResponse(format!("Failed to parse JSON: {}", e)))?;
+    let models = parsed_value
+        .get("data")
+        .and_then(|data| data.as_array())
+        .ok_or_else(|| {
+            ApiError::MalformedResponse("Could not find 'data' in the JSON".to_string())
+        })?;
+
+    let per_token = |value: Option<&Value>| -> Option<f64> {
+        let value = value?;
+        value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+    };
+
+    let prices = models
+        .iter()
+        .filter_map(|model| {
+            let model_id = model.get("id")?.as_str()?;
+            let pricing = model.get("pricing")?;
+            let prompt = per_token(pricing.get("prompt"))?;
+            let completion = per_token(pricing.get("completion"))?;
+            Some(ModelPrice {
+                model_id: model_id.to_string(),
+                prompt: prompt * 1_000_000.0,
+                completion: completion * 1_000_000.0,
+            })
+        })
+        .collect();
+    Ok(prices)
+}
+
+// Pulls the text out of a message's `content`, which is normally a plain string
+// but can also be an array of typed content blocks. Any Anthropic style thinking
+// blocks found in the array are appended to `thinking` instead.
+fn extract_content_text(content: &Value, thinking: &mut Vec<String>) -> Option<String> {
+    if let Some(text) = content.as_str() {
+        return Some(text.to_string());
+    }
+
+    let blocks = content.as_array()?;
+    let mut texts = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                    texts.push(text.to_string());
                 }
             }
-            Err(e) => {
-                console_log!("Error sending request: {}", e.to_string());
-                on_response(Err(anyhow!("Error sending request: {}", e.to_string())));
+            Some("thinking") => {
+                if let Some(text) = block.get("thinking").and_then(|t| t.as_str()) {
+                    thinking.push(text.to_string());
+                }
             }
+            _ => {}
         }
-    });
+    }
+
+    if texts.is_empty() {
+        None
+    } else {
+        Some(texts.join("\n"))
+    }
+}
+
+// Gathers the reasoning text from all of the known places providers put it:
+// `reasoning` (OpenRouter, llama.cpp), `reasoning_content` (DeepSeek, vLLM),
+// `reasoning_details` (OpenAI via OpenRouter) and `thinking_blocks` (Anthropic via
+// LiteLLM style proxies). Encrypted or redacted reasoning is skipped.
+fn extract_reasoning(message: &Value, thinking: &mut Vec<String>) {
+    for key in ["reasoning", "reasoning_content"] {
+        if let Some(text) = message.get(key).and_then(|r| r.as_str()) {
+            thinking.push(text.to_string());
+        }
+    }
+
+    // if a plain text field was present, the details are just a structured
+    // duplicate of it so we don't want to include them twice.
+    if !thinking.is_empty() {
+        return;
+    }
+
+    if let Some(details) = message.get("reasoning_details").and_then(|d| d.as_array()) {
+        for detail in details {
+            let text = match detail.get("type").and_then(|t| t.as_str()) {
+                Some("reasoning.text") => detail.get("text"),
+                Some("reasoning.summary") => detail.get("summary"),
+                _ => None,
+            };
+            if let Some(text) = text.and_then(|t| t.as_str()) {
+                thinking.push(text.to_string());
+            }
+        }
+    }
+
+    if let Some(blocks) = message.get("thinking_blocks").and_then(|b| b.as_array()) {
+        for block in blocks {
+            if let Some(text) = block.get("thinking").and_then(|t| t.as_str()) {
+                thinking.push(text.to_string());
+            }
+        }
+    }
 }
 
 // Takes the raw JSON returned by OpenAI compatible endpoints and parses out the information we want.
-fn extract_chat_completion_response(json_response: &str) -> Result<CompletionResponse, String> {
+fn extract_chat_completion_response(json_response: &str) -> Result<CompletionResponse, ApiError> {
     // Parse the JSON string into a serde_json::Value
-    let parsed_value: Value =
-        serde_json::from_str(json_response).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    let parsed_value: Value = serde_json::from_str(json_response)
+        .map_err(|e| ApiError::MalformedResponse(format!("Failed to parse JSON: {}", e)))?;
+
+    // some providers report errors with a successful HTTP status and an `error` object.
+    if parsed_value.get("error").is_some_and(|e| !e.is_null()) {
+        let status = parsed_value["error"]["code"]
+            .as_u64()
+            .and_then(|c| u16::try_from(c).ok())
+            .unwrap_or(200);
+        return Err(classify_error_response(status, json_response));
+    }
 
-    // Navigate the JSON structure to find the content
-    // Access `choices` array -> first element -> `message` object -> `content` string
-    let content = parsed_value
+    // Navigate the JSON structure to find the message: `choices` array -> first element
+    // -> `message` object. The content and all the different reasoning formats are
+    // pulled from there.
+    let choice = parsed_value
         .get("choices")
         .and_then(|choices| choices.get(0))
-        .and_then(|choice| choice.get("message"))
+        .ok_or_else(|| {
+            ApiError::MalformedResponse("Could not find 'choices[0]' in the JSON".to_string())
+        })?;
+    let finish_reason = choice
+        .get("finish_reason")
+        .and_then(|reason| reason.as_str())
+        .unwrap_or_default();
+    let message = choice.get("message");
+    let mut thinking = Vec::new();
+    let content = message
         .and_then(|message| message.get("content"))
-        .and_then(|content| content.as_str());
-    let reasoning = parsed_value
-        .get("choices")
-        .and_then(|choices| choices.get(0))
-        .and_then(|choice| choice.get("message"))
-        .and_then(|message| message.get("reasoning"))
-        .and_then(|reasoning| reasoning.as_str());
+        .and_then(|content| extract_content_text(content, &mut thinking));
+    if let Some(message) = message {
+        extract_reasoning(message, &mut thinking);
+    }
+    // some providers duplicate the same reasoning in more than one field, so
+    // only the unique chunks are kept.
+    let mut reasoning_chunks: Vec<&str> = Vec::new();
+    for chunk in thinking.iter().map(|t| t.trim()) {
+        if !chunk.is_empty() && !reasoning_chunks.contains(&chunk) {
+            reasoning_chunks.push(chunk);
+        }
+    }
+    let reasoning = reasoning_chunks.join("\n\n");
 
     // Pull the timings out from the response if they're present.
     let completion_tokens = parsed_value
@@ -290,26 +986,369 @@ fn extract_chat_completion_response(json_response: &str) -> Result<CompletionRes
         .and_then(|usage| usage.get("prompt_ms"))
         .and_then(|tokens| tokens.as_f64());
 
-    // if reasoning tokens is given, include those in the output.
-    if let Some(text) = content {
-        if let Some(reason_header) = reasoning {
-            Ok(CompletionResponse {
-                text: format!("<think>{}</think>\n{}", reason_header.trim(), text.trim()),
-                completion_tokens,
-                predicted_ms,
-                prompt_tokens,
-                prompt_ms,
-            })
-        } else {
-            Ok(CompletionResponse {
-                text: text.trim().to_string(),
-                completion_tokens,
-                predicted_ms,
-                prompt_tokens,
-                prompt_ms,
-            })
+    let text = match content {
+        Some(text) if !text.trim().is_empty() => text,
+        _ if finish_reason == "content_filter" => {
+            return Err(ApiError::ContentFiltered(
+                "The provider stopped the reply with finish_reason 'content_filter'".to_string(),
+            ));
         }
+        _ if message.is_none() => {
+            return Err(ApiError::MalformedResponse(
+                "Could not find 'choices[0].message' in the JSON".to_string(),
+            ));
+        }
+        _ if message.is_some_and(|m| m.get("tool_calls").is_some_and(|t| !t.is_null())) => {
+            return Err(ApiError::EmptyContent(
+                "The model replied with tool calls, which aren't supported, instead of text"
+                    .to_string(),
+            ));
+        }
+        _ if finish_reason == "length" => {
+            return Err(ApiError::EmptyContent(
+                "The model ran out of tokens before writing a reply".to_string(),
+            ));
+        }
+        _ => {
+            return Err(ApiError::EmptyContent(
+                "The 'choices[0].message.content' in the JSON was missing or empty".to_string(),
+            ));
+        }
+    };
+
+    // if reasoning tokens is given, include those in the output.
+    let text = if !reasoning.is_empty() && parse_think_block(text.clone()).is_none() {
+        format!("<think>{}</think>\n{}", reasoning, text.trim())
     } else {
-        Err("Could not find 'choices[0].message.content' in the JSON".to_string())
+        text.trim().to_string()
+    };
+
+    Ok(CompletionResponse {
+        text,
+        completion_tokens,
+        predicted_ms,
+        prompt_tokens,
+        prompt_ms,
+    })
+}
+
+// Takes the raw server-sent events of a streamed response, puts the deltas back together into
+// a regular response and parses out the information we want from that.
+fn extract_streamed_chat_completion_response(
+    event_stream: &str,
+) -> Result<CompletionResponse, ApiError> {
+    let mut content: Option<String> = None;
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+    let mut finish_reason = Value::Null;
+    let mut usage = Value::Null;
+    let mut timings = Value::Null;
+
+    for line in event_stream.lines() {
+        let Some(data) = line.strip_prefix("data:").map(|d| d.trim()) else {
+            continue;
+        };
+        if data == "[DONE]" {
+            break;
+        }
+        let chunk: Value = serde_json::from_str(data).map_err(|e| {
+            ApiError::MalformedResponse(format!("Failed to parse a streamed chunk: {}", e))
+        })?;
+        if chunk.get("error").is_some_and(|e| !e.is_null()) {
+            return extract_chat_completion_response(data);
+        }
+        if chunk.get("usage").is_some_and(|u| !u.is_null()) {
+            usage = chunk["usage"].clone();
+        }
+        if chunk.get("timings").is_some_and(|t| !t.is_null()) {
+            timings = chunk["timings"].clone();
+        }
+
+        let Some(choice) = chunk.get("choices").and_then(|choices| choices.get(0)) else {
+            continue;
+        };
+        let delta = &choice["delta"];
+        if let Some(text) = delta["content"].as_str() {
+            content.get_or_insert_with(String::new).push_str(text);
+        }
+        for key in ["reasoning", "reasoning_content"] {
+            if let Some(text) = delta[key].as_str() {
+                reasoning.push_str(text);
+            }
+        }
+        if let Some(calls) = delta["tool_calls"].as_array() {
+            tool_calls.extend(calls.iter().cloned());
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            finish_reason = json!(reason);
+        }
+    }
+
+    let mut message = json!({ "role": "assistant", "content": content });
+    if !reasoning.is_empty() {
+        message["reasoning"] = json!(reasoning);
+    }
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = json!(tool_calls);
+    }
+    let assembled = json!({
+        "choices": [{ "message": message, "finish_reason": finish_reason }],
+        "usage": usage,
+        "timings": timings,
+    });
+    extract_chat_completion_response(&assembled.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_only_send_the_key_to_the_current_endpoint() {
+        let config = ApiEndpointConfig {
+            endpoint: "https://api.example.com/v1".to_string(),
+            api_key: "secret".to_string(),
+            ..Default::default()
+        };
+        let url = chat_completions_url(&config);
+        assert_eq!(url, "https://api.example.com/v1/chat/completions");
+        assert_eq!(replay_config(config.clone(), &url).api_key, "secret");
+
+        let elsewhere = "https://other.example.com/v1/chat/completions";
+        let replayed = replay_config(config.clone(), elsewhere);
+        assert!(replayed.api_key.is_empty());
+        assert_eq!(replayed.endpoint, config.endpoint);
+    }
+
+    fn response_with_message(message: Value) -> String {
+        json!({
+            "choices": [{ "message": message, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 34 },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn plain_and_block_content_is_extracted() {
+        let mut thinking = Vec::new();
+        assert_eq!(
+            extract_content_text(&json!("Hello"), &mut thinking).as_deref(),
+            Some("Hello")
+        );
+
+        // anthropic style content arrays keep the text and move the thinking aside
+        let blocks = json!([
+            { "type": "thinking", "thinking": "Let me see." },
+            { "type": "text", "text": "First" },
+            { "type": "tool_use", "id": "ignored" },
+            { "type": "text", "text": "Second" },
+        ]);
+        assert_eq!(
+            extract_content_text(&blocks, &mut thinking).as_deref(),
+            Some("First\nSecond")
+        );
+        assert_eq!(thinking, ["Let me see."]);
+
+        let only_thinking = json!([{ "type": "thinking", "thinking": "Hmm" }]);
+        assert_eq!(extract_content_text(&only_thinking, &mut thinking), None);
+        assert_eq!(extract_content_text(&Value::Null, &mut thinking), None);
+    }
+
+    #[test]
+    fn reasoning_is_gathered_from_every_known_field() {
+        let mut thinking = Vec::new();
+        extract_reasoning(
+            &json!({ "reasoning": "From reasoning", "reasoning_content": "From content" }),
+            &mut thinking,
+        );
+        assert_eq!(thinking, ["From reasoning", "From content"]);
+
+        let mut thinking = Vec::new();
+        extract_reasoning(
+            &json!({
+                "reasoning_details": [
+                    { "type": "reasoning.text", "text": "Step one" },
+                    { "type": "reasoning.summary", "summary": "In short" },
+                    { "type": "reasoning.encrypted", "data": "secret" },
+                ],
+                "thinking_blocks": [
+                    { "type": "thinking", "thinking": "Block" },
+                    { "type": "redacted_thinking", "data": "secret" },
+                ],
+            }),
+            &mut thinking,
+        );
+        assert_eq!(thinking, ["Step one", "In short", "Block"]);
+    }
+
+    #[test]
+    fn reasoning_details_are_skipped_when_a_text_field_is_present() {
+        let mut thinking = Vec::new();
+        extract_reasoning(
+            &json!({
+                "reasoning": "Plain",
+                "reasoning_details": [{ "type": "reasoning.text", "text": "Plain" }],
+                "thinking_blocks": [{ "type": "thinking", "thinking": "Plain" }],
+            }),
+            &mut thinking,
+        );
+        assert_eq!(thinking, ["Plain"]);
+    }
+
+    #[test]
+    fn responses_are_parsed_with_their_reasoning() {
+        let json = response_with_message(json!({
+            "role": "assistant",
+            "content": "  The answer.  ",
+            "reasoning": "Thinking it over",
+        }));
+        let response = extract_chat_completion_response(&json).unwrap();
+        assert_eq!(
+            response.text,
+            "<think>Thinking it over</think>\nThe answer."
+        );
+        assert_eq!(response.prompt_tokens, Some(12));
+        assert_eq!(response.completion_tokens, Some(34));
+
+        let json = response_with_message(json!({ "content": "No reasoning" }));
+        let response = extract_chat_completion_response(&json).unwrap();
+        assert_eq!(response.text, "No reasoning");
+        assert_eq!(response.predicted_ms, None);
+    }
+
+    #[test]
+    fn duplicated_reasoning_is_only_included_once() {
+        let json = response_with_message(json!({
+            "content": [
+                { "type": "thinking", "thinking": "Same thought" },
+                { "type": "text", "text": "Reply" },
+            ],
+            "reasoning": "Same thought",
+            "reasoning_content": " Same thought\n",
+        }));
+        let response = extract_chat_completion_response(&json).unwrap();
+        assert_eq!(response.text, "<think>Same thought</think>\nReply");
+    }
+
+    #[test]
+    fn content_with_its_own_think_block_is_left_alone() {
+        let json = response_with_message(json!({
+            "content": "<think>Inline</think>\nReply",
+            "reasoning_content": "Separate",
+        }));
+        let response = extract_chat_completion_response(&json).unwrap();
+        assert_eq!(response.text, "<think>Inline</think>\nReply");
+    }
+
+    #[test]
+    fn error_responses_are_classified_by_status_and_body() {
+        let body = r#"{"error":{"message":"This model's maximum context length is 8192 tokens","code":"context_length_exceeded"}}"#;
+        assert_eq!(
+            classify_error_response(400, body),
+            ApiError::ContextLengthExceeded(
+                "This model's maximum context length is 8192 tokens".to_string()
+            )
+        );
+        // a payload that's too large is a context problem whatever the body says
+        assert!(matches!(
+            classify_error_response(413, "Payload Too Large"),
+            ApiError::ContextLengthExceeded(_)
+        ));
+
+        let body = r#"{"error":{"message":"Your request was rejected","type":"content_policy_violation"}}"#;
+        assert_eq!(
+            classify_error_response(400, body),
+            ApiError::ContentFiltered("Your request was rejected".to_string())
+        );
+
+        let body = r#"{"error":{"message":"Invalid API key","type":"invalid_request_error"}}"#;
+        assert_eq!(
+            classify_error_response(401, body),
+            ApiError::HttpStatus {
+                status: 401,
+                message: "Invalid API key".to_string()
+            }
+        );
+
+        // some providers put a plain string in the error
+        assert_eq!(
+            classify_error_response(429, r#"{"error":"Rate limited"}"#),
+            ApiError::HttpStatus {
+                status: 429,
+                message: "Rate limited".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_and_empty_error_bodies_are_kept_as_they_are() {
+        assert_eq!(
+            classify_error_response(502, "<html>Bad Gateway</html>\n"),
+            ApiError::HttpStatus {
+                status: 502,
+                message: "<html>Bad Gateway</html>".to_string()
+            }
+        );
+        assert_eq!(
+            classify_error_response(500, ""),
+            ApiError::HttpStatus {
+                status: 500,
+                message: String::new()
+            }
+        );
+        assert_eq!(
+            classify_error_response(503, r#"{"detail":"overloaded"}"#),
+            ApiError::HttpStatus {
+                status: 503,
+                message: r#"{"detail":"overloaded"}"#.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn errors_in_successful_responses_are_classified() {
+        let body = r#"{"error":{"message":"Flagged by moderation","code":403}}"#;
+        assert_eq!(
+            extract_chat_completion_response(body),
+            Err(ApiError::ContentFiltered(
+                "Flagged by moderation".to_string()
+            ))
+        );
+        let body = r#"{"error":{"message":"Upstream failed"}}"#;
+        assert_eq!(
+            extract_chat_completion_response(body),
+            Err(ApiError::HttpStatus {
+                status: 200,
+                message: "Upstream failed".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn unusable_responses_report_why() {
+        assert!(matches!(
+            extract_chat_completion_response("not json"),
+            Err(ApiError::MalformedResponse(_))
+        ));
+        assert!(matches!(
+            extract_chat_completion_response(r#"{"choices":[]}"#),
+            Err(ApiError::MalformedResponse(_))
+        ));
+        assert!(matches!(
+            extract_chat_completion_response(r#"{"choices":[{"finish_reason":"stop"}]}"#),
+            Err(ApiError::MalformedResponse(_))
+        ));
+        assert!(matches!(
+            extract_chat_completion_response(
+                r#"{"choices":[{"message":{"content":null},"finish_reason":"content_filter"}]}"#
+            ),
+            Err(ApiError::ContentFiltered(_))
+        ));
+        assert!(matches!(
+            extract_chat_completion_response(
+                r#"{"choices":[{"message":{"content":"  "},"finish_reason":"length"}]}"#
+            ),
+            Err(ApiError::EmptyContent(_))
+        ));
     }
 }
@@ -32,12 +32,11 @@ pub fn ButtonBarComponent() -> View {
     let button_fill_color = create_signal(if dark_mode.signal().get() {
         "var(--color-primary-text-dark)".to_string()
     } else {
-        "var(--color-primary-text-dark)".to_string()
+        "var(--color-primary-text)".to_string()
     });
 
     create_effect({
         let dark_mode_signal = dark_mode.signal();
-        let button_fill_color = button_fill_color.clone();
         move || {
             button_fill_color.set(if dark_mode_signal.get() {
                 "var(--color-primary-text-dark)".to_string()
@@ -1,11 +1,164 @@
 use sycamore::{prelude::*, web::events::KeyboardEvent};
-use web_sys::ClipboardEvent;
 use web_sys::js_sys::Function;
 use web_sys::wasm_bindgen::prelude::Closure;
+use web_sys::{ClipboardEvent, DragEvent, File, FileList, HtmlInputElement};
 use web_sys::{FileReader, wasm_bindgen::JsCast};
 
-use crate::components::detect_mobile_device;
-use crate::models::{chatlog::Chatlog, is_response_pending::IsResponsePending};
+use crate::components::{detect_mobile_device, voice_input::VoiceInputButton};
+use crate::image_processing;
+use crate::models::{
+    chatlog::{Chatlog, FileAttachment},
+    documents::is_supported_document,
+    image_compression::{ImageCompression, ImageCompressionSettings},
+    is_response_pending::IsResponsePending,
+    notifications::Notifications,
+};
+
+// files bigger than this are refused rather than inlined into the message
+const MAX_ATTACHED_FILE_BYTES: f64 = 256.0 * 1024.0;
+
+// splits the files of the list into images and text or source files. anything else, and
+// text files that are too large to inline, are reported to the user and skipped.
+fn sort_files(file_list: &FileList, notifications: Notifications) -> (Vec<File>, Vec<File>) {
+    let mut images = Vec::new();
+    let mut text_files = Vec::new();
+    for file in (0..file_list.length()).filter_map(|i| file_list.get(i)) {
+        if file.type_().starts_with("image/") {
+            images.push(file);
+        } else if !file.type_().starts_with("text/") && !is_supported_document(&file.name()) {
+            notifications.warning(format!("'{}' isn't an image or a text file.", file.name()));
+        } else if file.size() > MAX_ATTACHED_FILE_BYTES {
+            notifications.warning(format!(
+                "'{}' is too large to attach ({:.0} KB).",
+                file.name(),
+                file.size() / 1024.0
+            ));
+        } else {
+            text_files.push(file);
+        }
+    }
+    (images, text_files)
+}
+
+// An image attached to the message being drafted, after it was downscaled and recompressed.
+#[derive(Clone, PartialEq)]
+struct DraftImage {
+    data_url: String,
+    original_bytes: f64,
+    compressed_bytes: f64,
+}
+
+// reads the image file as a base64 encoded data URL, compresses it according to the
+// settings and adds it to the images of the message being drafted. `pending_images`
+// counts the images that are still being read or compressed.
+fn attach_image_file(
+    file: &File,
+    attached_images: Signal<Vec<DraftImage>>,
+    pending_images: Signal<u32>,
+    settings: ImageCompressionSettings,
+) {
+    let original_bytes = file.size();
+    let file_reader = match FileReader::new() {
+        Ok(file_reader) => file_reader,
+        Err(err) => {
+            console_log!("FileReader failed to start: {:?}", err);
+            return;
+        }
+    };
+    pending_images.update(|count| *count += 1);
+    let finished = move || pending_images.update(|count| *count = count.saturating_sub(1));
+    let file_reader_clone = file_reader.clone();
+    let handle_onload_js: Function =
+        Closure::wrap(Box::new(move || match file_reader_clone.result() {
+            Ok(res) => {
+                if let Some(data_str) = res.as_string() {
+                    let settings = settings.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let data_url =
+                            match image_processing::compress_image(data_str.clone(), &settings)
+                                .await
+                            {
+                                Ok(compressed) => compressed,
+                                Err(err) => {
+                                    console_log!("Image compression failed: {:?}", err);
+                                    data_str
+                                }
+                            };
+                        let compressed_bytes = image_processing::data_url_size(&data_url) as f64;
+                        attached_images.update(|images| {
+                            images.push(DraftImage {
+                                data_url,
+                                original_bytes,
+                                compressed_bytes,
+                            })
+                        });
+                        finished();
+                    });
+                } else {
+                    console_log!("Failed to read image as string.");
+                    finished();
+                }
+            }
+            Err(err) => {
+                console_log!("Error reading the image: {:?}", err);
+                finished();
+            }
+        }) as Box<dyn FnMut()>)
+        .into_js_value()
+        .into();
+    let handle_onerror_js: Function = Closure::wrap(Box::new(move || {
+        console_log!("The image couldn't be read.");
+        finished();
+    }) as Box<dyn FnMut()>)
+    .into_js_value()
+    .into();
+
+    file_reader.set_onload(Some(&handle_onload_js));
+    file_reader.set_onerror(Some(&handle_onerror_js));
+    if let Err(err) = file_reader.read_as_data_url(file) {
+        console_log!("Image reading failed: {:?}", err);
+        finished();
+    }
+}
+
+// reads the files as text and adds them to the attachments of the message being drafted,
+// replacing any earlier attachment with the same name.
+fn attach_text_files(
+    files: Vec<File>,
+    attached_files: Signal<Vec<FileAttachment>>,
+    notifications: Notifications,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        for file in files {
+            let content = wasm_bindgen_futures::JsFuture::from(file.text())
+                .await
+                .ok()
+                .and_then(|text| text.as_string());
+            let Some(content) = content else {
+                notifications.error(format!("Unable to read '{}'.", file.name()));
+                continue;
+            };
+            let attachment = FileAttachment {
+                name: file.name(),
+                content,
+            };
+            attached_files.update(|files| {
+                files.retain(|f| f.name != attachment.name);
+                files.push(attachment);
+            });
+        }
+    });
+}
+
+// returns the prompt of a message that starts with the `/image` command
+fn parse_image_command(input: &str) -> Option<String> {
+    let rest = input.trim_start().strip_prefix("/image")?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim().to_string())
+    } else {
+        None
+    }
+}
 
 /// A component that renders a chat input field with a send button.
 ///
@@ -20,37 +173,97 @@ pub fn ChatInputComponent() -> View {
     // signal for the main text input control
     let input_text = create_signal(String::new());
 
-    // this stashes the image data, as a base64 encode, from a clipboard paste event
-    let image_data_base64 = create_signal(None::<String>);
+    // this stashes the images, as compressed base64 encoded data URLs, that were pasted,
+    // dropped or picked for the message being drafted
+    let attached_images = create_signal(Vec::<DraftImage>::new());
+    // the message isn't sent while any of its images are still being compressed
+    let pending_images = create_signal(0u32);
+    let image_compression = use_context::<ImageCompression>().signal();
+
+    // when on, messages are sent as prompts for image generation instead of chat replies
+    let image_mode = create_signal(false);
+
+    // text files picked or dropped in, inlined into the message when it's sent
+    let attached_files = create_signal(Vec::<FileAttachment>::new());
+    let is_dragging = create_signal(false);
+    let file_input_ref = create_node_ref();
+    let notifications = use_context::<Notifications>();
+
+    // the data URLs of the images to send with the message
+    let draft_images = move || {
+        attached_images.with_untracked(|images| images.iter().map(|i| i.data_url.clone()).collect())
+    };
 
     // shared send logic to send a new message request
     let send_message = move || {
-        // skip sending messages if we already have one in the oven
-        if is_response_pending.signal().get() {
+        if pending_images.get_untracked() > 0 {
             return;
         }
-
         let input_str = input_text.get_clone_untracked();
+        let has_files = attached_files.with_untracked(|files| !files.is_empty())
+            || attached_images.with_untracked(|images| !images.is_empty());
         let active_chatlog = use_context::<Signal<Chatlog>>();
-        if !input_str.trim().is_empty() {
+
+        // the `/image` command asks for an image of the rest of the message
+        let (wants_image, input_str) = match parse_image_command(&input_str) {
+            Some(prompt) => (true, prompt),
+            None => (image_mode.get_untracked(), input_str),
+        };
+
+        // while a reply is in the oven, new messages are queued up to be sent once it lands
+        if is_response_pending.signal().get() {
+            if !input_str.trim().is_empty() || has_files {
+                let log = active_chatlog.get_clone();
+                log.queue_message(
+                    input_str,
+                    draft_images(),
+                    attached_files.get_clone(),
+                    wants_image,
+                );
+                input_text.set(String::new());
+                attached_images.set(Vec::new());
+                attached_files.set(Vec::new());
+            }
+            return;
+        }
+
+        if !input_str.trim().is_empty() || has_files {
             // we send a message if something was typed in, which will add
-            // the message to the log as well as generate a new reply.
+            // the message to the log as well as generate a new reply. it goes through
+            // the queue so that anything still queued, such as after a failed reply,
+            // is sent before it.
             let mut log = active_chatlog.get_clone();
-
-            let image_data = image_data_base64.get_clone();
-            log.add_message(input_str.clone(), false, image_data);
+            log.queue_message(
+                input_str,
+                draft_images(),
+                attached_files.get_clone_untracked(),
+                wants_image,
+            );
+            log.dequeue_messages(true);
             log.trigger_response_generation();
             active_chatlog.replace(log);
 
             input_text.set(String::new());
-            image_data_base64.set(None::<String>);
+            attached_images.set(Vec::new());
+            attached_files.set(Vec::new());
         } else {
             // on an empty textarea, we just attempt to generate another reply.
-            let log = active_chatlog.get_clone();
+            let mut log = active_chatlog.get_clone();
+            if !log.dequeue_messages(true) {
+                log.is_generating_image.set(wants_image);
+            }
             log.trigger_response_generation();
+            input_text.set(String::new());
         }
     };
 
+    // true when there's neither text nor files to send
+    let is_draft_empty = move || {
+        input_text.with(|text| text.trim().is_empty())
+            && attached_files.with(|files| files.is_empty())
+            && attached_images.with(|images| images.is_empty())
+    };
+
     // send button click handler
     let on_send_press = move |_| {
         send_message();
@@ -58,21 +271,23 @@ pub fn ChatInputComponent() -> View {
 
     // keydown handler for Enter key (&& !Shift) on non-mobile devices.
     let on_keypress = move |event: KeyboardEvent| {
-        if !detect_mobile_device() {
-            if event.key() == "Enter" && !event.shift_key() {
-                event.prevent_default();
-                send_message();
-            }
+        if !detect_mobile_device() && event.key() == "Enter" && !event.shift_key() {
+            event.prevent_default();
+            send_message();
         }
     };
 
-    // 'removes' the image from the message being drafted by clearing the base64 encoded string.
-    let handle_remove_image = move |_| {
-        image_data_base64.set(None);
+    // removes one of the images from the message being drafted.
+    let handle_remove_image = move |index: usize| {
+        attached_images.update(|images| {
+            if index < images.len() {
+                images.remove(index);
+            }
+        });
     };
 
-    // intercept clipboard paste events to check and see if an image is being pasted into
-    // the message textarea. If so, we update our signal with the base64 encoded data.
+    // intercept clipboard paste events to check and see if images are being pasted into
+    // the message textarea. If so, they're added to the images of the message.
     let on_paste = move |event: web_sys::Event| {
         let clipboard_event: ClipboardEvent = event.dyn_into().unwrap();
         if let Some(data_transfer) = clipboard_event.clipboard_data() {
@@ -81,88 +296,200 @@ pub fn ChatInputComponent() -> View {
             for i in 0..items.length() {
                 if let Some(item) = items.get(i) {
                     //console_log!("Item [{}]: kind={}, type={}", i, item.kind(), item.type_());
-                    if item.kind() == "file" && item.type_().starts_with("image/") {
-                        if let Some(file) = item.get_as_file().unwrap() {
-                            clipboard_event.prevent_default();
-
-                            let file_reader_maybe = FileReader::new();
-                            match file_reader_maybe {
-                                Ok(file_reader) => {
-                                    let file_reader_clone = file_reader.clone();
-                                    let image_data_base64_clone = image_data_base64.clone();
-                                    let handle_onload_js: Function =
-                                        Closure::wrap(Box::new(move || {
-                                            match file_reader_clone.result() {
-                                                Ok(res) => {
-                                                    if let Some(data_str) = res.as_string() {
-                                                        image_data_base64_clone.set(Some(data_str));
-                                                    } else {
-                                                        console_log!(
-                                                            "Failed to read image as string."
-                                                        );
-                                                    }
-                                                }
-                                                Err(err) => {
-                                                    console_log!(
-                                                        "Error reading the image: {:?}",
-                                                        err
-                                                    );
-                                                }
-                                            }
-                                        })
-                                            as Box<dyn FnMut()>)
-                                        .into_js_value()
-                                        .into();
-
-                                    file_reader.set_onload(Some(&handle_onload_js));
-                                    if let Err(err) = file_reader.read_as_data_url(&file) {
-                                        console_log!("Clipboard image reading failed: {:?}", err);
-                                    }
-                                }
-                                Err(err) => {
-                                    console_log!("FileReader failed to start: {:?}", err);
-                                }
-                            }
-                        }
-
-                        break; // only handle the first image found
+                    if item.kind() == "file"
+                        && item.type_().starts_with("image/")
+                        && let Ok(Some(file)) = item.get_as_file()
+                    {
+                        clipboard_event.prevent_default();
+                        attach_image_file(
+                            &file,
+                            attached_images,
+                            pending_images,
+                            image_compression.get_clone_untracked(),
+                        );
                     }
                 }
             }
         }
     };
 
-    view! {
-        div(class="input-container") {
-            (if let Some(data_url_str) = image_data_base64.get_clone() {
+    // the file picker and the drag and drop zone both attach images and text files
+    let attach_files = move |file_list: FileList| {
+        let (images, text_files) = sort_files(&file_list, notifications);
+        for image in images {
+            attach_image_file(
+                &image,
+                attached_images,
+                pending_images,
+                image_compression.get_clone_untracked(),
+            );
+        }
+        attach_text_files(text_files, attached_files, notifications);
+    };
+    let on_files_picked = move |event: web_sys::Event| {
+        let Some(input) = event
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+        else {
+            return;
+        };
+        if let Some(file_list) = input.files() {
+            attach_files(file_list);
+        }
+        input.set_value(""); // lets the same file be picked again
+    };
+    let open_file_dialog = move |_| {
+        if let Ok(input) = file_input_ref.get().dyn_into::<HtmlInputElement>() {
+            input.click();
+        }
+    };
+    let on_drag_over = move |event: DragEvent| {
+        event.prevent_default();
+        is_dragging.set(true);
+    };
+    let on_drop = move |event: DragEvent| {
+        event.prevent_default();
+        is_dragging.set(false);
+        if let Some(file_list) = event.data_transfer().and_then(|dt| dt.files()) {
+            attach_files(file_list);
+        }
+    };
+
+    let image_thumbnails = move || {
+        attached_images
+            .get_clone()
+            .into_iter()
+            .enumerate()
+            .map(|(index, image)| {
+                let size = format!(
+                    "{} → {}",
+                    image_processing::format_bytes(image.original_bytes),
+                    image_processing::format_bytes(image.compressed_bytes)
+                );
                 view! {
-                    div(class="flex flex-col items-center") {
-                        h3(class="input-image-label") { "Attached Image:" }
-                        div(class="relative") {
-                            img(src=data_url_str, alt="Pasted Image", class="input-image")
-                            button(
-                                on:click=handle_remove_image,
-                                class="input-image-remove-button"
-                            ) {
-                                "X"
-                            }
+                    div(class="relative") {
+                        img(src=image.data_url, alt="Attached Image", class="input-image")
+                        button(
+                            on:click=move |_| handle_remove_image(index),
+                            class="input-image-remove-button"
+                        ) {
+                            "X"
                         }
+                        span(class="input-image-size") { (size) }
+                    }
+                }
+            })
+            .collect::<Vec<View>>()
+    };
+
+    let file_chips = move || {
+        attached_files
+            .get_clone()
+            .into_iter()
+            .map(|file| {
+                let label = format!("{} ({} lines)", file.name, file.content.lines().count());
+                view! {
+                    span(class="document-chip") {
+                        (label)
+                        button(
+                            class="document-chip-remove",
+                            title="Remove the file",
+                            on:click=move |_| {
+                                attached_files.update(|files| files.retain(|f| f.name != file.name));
+                            },
+                        ) { "×" }
                     }
                 }
-            } else {
-                view! { }
             })
+            .collect::<Vec<View>>()
+    };
 
+    view! {
+        div(
+            class=move || if is_dragging.get() { "drop-zone drop-zone-active" } else { "drop-zone" },
+            on:dragover=on_drag_over,
+            on:dragleave=move |_| is_dragging.set(false),
+            on:drop=on_drop,
+        ) {
+        div(class="input-container") {
             textarea(bind:value=input_text, on:keypress=on_keypress, on:paste=on_paste,
                 class="message-input", rows="2", r#type = "text", placeholder = "Type a message...") {}
 
-            button(on:click=on_send_press, class="send-button", disabled=is_response_pending.signal().get()) {
-                (if input_text.get_clone().trim().is_empty() {
+            input(
+                r#ref=file_input_ref,
+                r#type="file",
+                multiple=true,
+                class="hidden",
+                on:change=on_files_picked,
+            )
+            button(on:click=open_file_dialog, class="image-mode-button",
+                title="Attach images, text or source files to the message (or drop them here)") {
+                "FILE"
+            }
+
+            VoiceInputButton(input_text=input_text, on_auto_send=send_message)
+
+            button(on:click=move |_| image_mode.set(!image_mode.get()),
+                class=move || if image_mode.get() { "image-mode-button image-mode-button-on" } else { "image-mode-button" },
+                title="Generate images instead of text replies (or start a message with /image)") {
+                "IMG"
+            }
+
+            button(on:click=on_send_press, class="send-button",
+                disabled=pending_images.get() > 0 || (is_response_pending.signal().get() && is_draft_empty())) {
+                (if pending_images.get() > 0 {
+                    "Attaching"
+                } else if is_draft_empty() {
                     "Continue"
+                } else if is_response_pending.signal().get() {
+                    "Queue"
+                } else if image_mode.get() || input_text.with(|text| parse_image_command(text).is_some()) {
+                    "Generate"
                 } else {
                     "Send"
                 })
             }
         }
+
+        div(class=if attached_images.with(|images| images.is_empty()) { "hidden" } else { "image-strip mt-2" }) {
+            (image_thumbnails)
+        }
+
+        div(class=if attached_files.with(|files| files.is_empty()) { "hidden" } else { "document-bar mt-2" }) {
+            (file_chips)
+        }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_image_command_takes_the_rest_as_the_prompt() {
+        assert_eq!(
+            parse_image_command("/image a red fox  ").as_deref(),
+            Some("a red fox")
+        );
+        assert_eq!(
+            parse_image_command("  \n/image\ta lighthouse").as_deref(),
+            Some("a lighthouse")
+        );
+    }
+
+    #[test]
+    fn the_image_command_may_come_without_a_prompt() {
+        assert_eq!(parse_image_command("/image").as_deref(), Some(""));
+        assert_eq!(parse_image_command("/image   ").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn only_the_exact_command_counts() {
+        assert_eq!(parse_image_command("/Image a red fox"), None);
+        assert_eq!(parse_image_command("/IMAGE"), None);
+        assert_eq!(parse_image_command("/images of foxes"), None);
+        assert_eq!(parse_image_command("Draw /image a red fox"), None);
+        assert_eq!(parse_image_command(""), None);
     }
 }
@@ -1,8 +1,14 @@
 use crate::{
     components::{
         button_bar::ButtonBarComponent, chat_input::ChatInputComponent, chatlog::ChatlogComponent,
+        document_attachments::DocumentAttachmentsComponent,
+        queued_messages::QueuedMessagesComponent, response_error::ResponseErrorComponent,
+        undo_redo::UndoRedoComponent,
+    },
+    models::{
+        chatlog::Chatlog, dark_mode::DarkMode, is_response_pending::IsResponsePending,
+        jump_to_message::JumpToMessage, response_error::ResponseError,
     },
-    models::{chatlog::Chatlog, dark_mode::DarkMode, is_response_pending::IsResponsePending},
 };
 use sycamore::{prelude::*, web::rt::web_sys::HtmlElement};
 use wasm_bindgen_futures::wasm_bindgen::JsCast;
@@ -27,14 +33,27 @@ pub fn ChatInterface() -> View {
     // creates a reference to our chat-messages container
     let node_ref = create_node_ref();
 
-    // create an effect to scroll to the end every time the chatlog changes.
+    // create an effect to scroll to the end every time the chatlog changes, or to the
+    // message a search result was opened for.
     let active_chatlog = use_context::<Signal<Chatlog>>();
+    let response_error = use_context::<ResponseError>();
+    let jump_to_message = use_context::<JumpToMessage>();
     create_effect(move || {
         let _ = is_response_pending.signal().get(); // Track changes to the response pending (for progress spinner)
-        let _ = active_chatlog.get_clone(); // Track changes to the chatlog
+        let _ = response_error.signal().get_clone(); // Track changes to the inline error message
+        let log = active_chatlog.get_clone(); // Track changes to the chatlog
+        let _ = log.queued_messages.get_clone(); // Track changes to the queued messages
         on_mount(move || {
             let node = node_ref.get();
             if let Ok(elem) = node.dyn_into::<HtmlElement>() {
+                if let Some(msg_id) = jump_to_message.signal().get_untracked() {
+                    jump_to_message.signal().set(None);
+                    if let Ok(Some(target)) = elem.query_selector(&format!("#message-{}", msg_id)) {
+                        target.scroll_into_view();
+                        return;
+                    }
+                }
+
                 let scroll_height = elem.scroll_height();
                 let client_height = elem.client_height();
 
@@ -52,6 +71,7 @@ pub fn ChatInterface() -> View {
             div (class = "chat-window flex flex-col h-[calc(100vh-200px)] md:h-full") {
                 div (class = "chat-messages", r#ref=node_ref) {
                     ChatlogComponent()
+                    ResponseErrorComponent()
 
                     (if is_response_pending.signal().get() {
                         view! {
@@ -62,8 +82,14 @@ pub fn ChatInterface() -> View {
                     } else {
                         view! { }
                     })
+
+                    QueuedMessagesComponent()
                 }
 
+                div(class="flex items-start justify-between") {
+                    DocumentAttachmentsComponent()
+                    UndoRedoComponent()
+                }
                 ChatInputComponent()
             }
         }
@@ -1,10 +1,38 @@
 use crate::{
-    components::detect_mobile_device,
-    models::chatlog::{Chatlog, Message, parse_think_block},
+    components::{detect_mobile_device, saving_interface},
+    models::{
+        chatlog::{Chatlog, FileAttachment, Message, parse_think_block},
+        documents::Citation,
+        is_response_pending::IsResponsePending,
+        notifications::ConfirmationDialog,
+        speech_player::SpeechPlayer,
+    },
+    speech,
 };
 use sycamore::prelude::*;
 use web_sys::KeyboardEvent;
 
+// A file attached to a message, collapsed to its name until it's clicked.
+#[component(inline_props)]
+fn AttachedFileComponent(file: FileAttachment) -> View {
+    let is_expanded = create_signal(false);
+    let label = format!("{} ({} lines) ", file.name, file.content.lines().count());
+    let content = file.content.clone();
+    view! {
+        div(class="attached-file") {
+            div(class="attached-file-header", on:click=move |_| is_expanded.set(!is_expanded.get())) {
+                (label)
+                span(class="think-toggle") {
+                    (if is_expanded.get() { "▼" } else { "▶" })
+                }
+            }
+            pre(class=if is_expanded.get() { "attached-file-content" } else { "hidden" }) {
+                code { (content) }
+            }
+        }
+    }
+}
+
 /// Renders a chat message component with different styling for user and AI messages.
 ///
 /// The component displays messages with different positioning and colors depending on whether
@@ -16,18 +44,25 @@ pub fn ChatMessageComponent(msg: Message) -> View {
     let chatlog = use_context::<Signal<Chatlog>>();
     let tracked_message = chatlog.get_clone_untracked().track_message(msg.id);
     let msg_content = create_signal(String::new());
-    let msg_image = create_signal(None::<String>);
+    let msg_images = create_signal(Vec::<String>::new());
+    let msg_citations = create_signal(Vec::<Citation>::new());
+    let msg_files = create_signal(Vec::<FileAttachment>::new());
     let selected_index = create_signal(msg.selected_message);
     let stack_len = create_signal(msg.message_stack.len());
 
     // signal for tracking if think block is expanded, unexpanded by default
     let show_think_block = create_signal(false);
 
+    // the document excerpts the response was based on are listed on demand
+    let show_citations = create_signal(false);
+
     create_effect(move || {
         if let Some(current_msg) = tracked_message.get_clone() {
             if let Some(selected) = current_msg.get_selected_message() {
                 msg_content.set(selected.message.clone());
-                msg_image.set(selected.image_base64.clone());
+                msg_images.set(selected.images.clone());
+                msg_citations.set(selected.citations.clone());
+                msg_files.set(selected.files.clone());
                 selected_index.set(current_msg.selected_message);
             }
             stack_len.set(current_msg.message_stack.len());
@@ -38,6 +73,10 @@ pub fn ChatMessageComponent(msg: Message) -> View {
     // we use a default message here for safety.
     debug_assert!(msg.get_selected_message().is_some());
 
+    // the messages can't be edited, deleted, forked or switched to another branch while a
+    // response is pending, since the response goes to the message it was asked for
+    let is_response_pending = use_context::<IsResponsePending>().signal();
+
     let is_editing = create_signal(false);
     let show_actions = create_signal(false);
     let toggle_actions = move |_| {
@@ -50,44 +89,77 @@ pub fn ChatMessageComponent(msg: Message) -> View {
         let new_msg = msg_content.get_clone_untracked();
         if !new_msg.trim().is_empty() {
             let mut active_chatlog = use_context::<Signal<Chatlog>>().get_clone_untracked();
-            active_chatlog.update_msg(msg.id, new_msg, msg_image.get_clone());
+            active_chatlog.update_msg(msg.id, new_msg, msg_images.get_clone());
             show_actions.set(false);
         }
     };
 
-    let handle_remove_image = move || {
-        msg_image.set(None);
+    let handle_remove_image = move |index: usize| {
+        msg_images.update(|images| {
+            if index < images.len() {
+                images.remove(index);
+            }
+        });
         let mut active_chatlog = use_context::<Signal<Chatlog>>().get_clone_untracked();
-        active_chatlog.update_msg(msg.id, msg_content.get_clone(), None);
+        active_chatlog.update_msg(msg.id, msg_content.get_clone(), msg_images.get_clone());
+    };
+
+    // the branches of the other variants of the deleted messages are deleted with them
+    let removal_warning = move |message: &str, with_following: bool| {
+        let hidden = chatlog
+            .get_clone_untracked()
+            .hidden_messages_removed_with(msg.id, with_following);
+        match hidden {
+            0 => message.to_string(),
+            1 => format!(
+                "{} The message in the branch of another variant is deleted as well.",
+                message
+            ),
+            _ => format!(
+                "{} The {} messages in the branches of the other variants are deleted as well.",
+                message, hidden
+            ),
+        }
     };
 
+    let confirmation_dialog = use_context::<ConfirmationDialog>();
     let handle_purge_msgs = move || {
-        let confirmed = window().confirm_with_message(
-            "Are you sure you want to delete this message AND all older messages also?",
-        );
-        if let Ok(is_ok) = confirmed {
-            if is_ok {
+        confirmation_dialog.confirm(
+            removal_warning(
+                "Are you sure you want to delete this message AND all the messages after it?",
+                true,
+            ),
+            "Purge",
+            move || {
+                if is_response_pending.get_untracked() {
+                    return;
+                }
                 let mut active_chatlog = use_context::<Signal<Chatlog>>().get_clone_untracked();
                 active_chatlog.purge_messages(msg.id);
-            }
-        }
+            },
+        );
     };
 
     let handle_delete_msg = move || {
-        let confirmed =
-            window().confirm_with_message("Are you sure you want to delete this message?");
-        if let Ok(is_ok) = confirmed {
-            if is_ok {
+        confirmation_dialog.confirm(
+            removal_warning("Are you sure you want to delete this message?", false),
+            "Delete",
+            move || {
+                if is_response_pending.get_untracked() {
+                    return;
+                }
                 let mut active_chatlog = use_context::<Signal<Chatlog>>().get_clone_untracked();
                 active_chatlog.remove_message(msg.id);
-            }
-        }
+            },
+        );
     };
 
+    // generates a new variant of the message from the messages before it. the messages
+    // that follow it stay on the branch of the variant that's shown now.
     let handle_regeneration = move || {
         let active_chatlog = use_context::<Signal<Chatlog>>();
         active_chatlog.update(|log| {
-            log.is_regenerating_msg.set(true);
+            log.regenerating_msg.set(Some(msg.id));
         });
 
         let log = active_chatlog.get_clone();
@@ -95,8 +167,50 @@ pub fn ChatMessageComponent(msg: Message) -> View {
         show_actions.set(false);
     };
 
+    // every variant of the message has its own branch of the messages that follow it.
+    // switching the variant switches the rest of the chatlog to its branch.
+    let switch_branch = move |delta: i16| {
+        if is_response_pending.get_untracked() {
+            return;
+        }
+        chatlog
+            .get_clone_untracked()
+            .update_selected_index(msg.id, delta);
+    };
+    let messages = chatlog.get_clone_untracked().messages;
+    let branch_summary = move || {
+        let following = messages.with(|msgs| {
+            msgs.iter()
+                .position(|m| m.id == msg.id)
+                .map_or(0, |index| msgs.len() - index - 1)
+        });
+        tracked_message
+            .get_clone()
+            .map(|current| current.branch_lengths(following))
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(index, count)| match count {
+                1 => format!("Branch {}: 1 message follows", index + 1),
+                _ => format!("Branch {}: {} messages follow", index + 1, count),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    // reads the message aloud, or stops the reading if it's already being read
+    let speech_player = use_context::<SpeechPlayer>();
+    let is_speaking = move || speech_player.speaking_message().get() == Some(msg.id);
+    let handle_speak = move || {
+        if is_speaking() {
+            speech_player.stop();
+        } else {
+            speech::read_message_aloud(msg.id, msg_content.get_clone_untracked());
+        }
+    };
+
     view! {
-    div (class = if !msg.ai_generated {
+    div (id = format!("message-{}", msg.id), class = if !msg.ai_generated {
         "message-container-user"
     } else {
         "message-container-ai"
@@ -149,32 +263,29 @@ pub fn ChatMessageComponent(msg: Message) -> View {
                                 handle_edit_done();
                             },
                             on:keydown = move |e:KeyboardEvent| {
-                                if !detect_mobile_device() {
-                                    if e.key() == "Enter" && !e.shift_key() {
+                                if !detect_mobile_device()
+                                    && e.key() == "Enter" && !e.shift_key() {
                                         e.prevent_default();
                                         handle_edit_done();
                                     }
-                                }
                             },
                         )
                     }
                 })
 
-                (if let Some(data_url_str) = msg_image.get_clone() {
-                    if !is_editing.get() {
-                        view! {
-                            div {
+                div(class="image-strip") {
+                    (msg_images.get_clone().into_iter().enumerate().map(|(index, data_url_str)| {
+                        if !is_editing.get() {
+                            view! {
                                 img(src=data_url_str, alt="Image for Message", class="message-image")
                             }
-                        }
-                    } else {
-                        view! {
-                            div(class="flex flex-col items-start") {
+                        } else {
+                            view! {
                                 div(class="relative") {
                                     img(src=data_url_str, alt="Image for Message", class="message-image")
                                     button(
                                         on:click=move |_| {
-                                            handle_remove_image();
+                                            handle_remove_image(index);
                                         },
                                         class="input-image-remove-button"
                                     ) {
@@ -182,13 +293,67 @@ pub fn ChatMessageComponent(msg: Message) -> View {
                                     }
                                 }
                             }
-                         }
-                    }
-                } else {
+                        }
+                    }).collect::<Vec<View>>())
+                }
+
+                (msg_files.get_clone().into_iter().map(|file| {
+                    view! { AttachedFileComponent(file=file) }
+                }).collect::<Vec<View>>())
+
+                (if msg_citations.with(|c| c.is_empty()) {
                     view! { }
+                } else {
+                    let sources = msg_citations
+                        .get_clone()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, citation)| {
+                            let label = format!("[{}] {}, part {}", i + 1, citation.document, citation.chunk);
+                            view! {
+                                li(class="citation", title=citation.text) { (label) }
+                            }
+                        })
+                        .collect::<Vec<View>>();
+                    view! {
+                        div(class="citations") {
+                            div(class="think-toggle cursor-pointer", on:click=move |_| {
+                                show_citations.set(!show_citations.get());
+                            }) {
+                                (format!("Sources ({}) ", msg_citations.with(|c| c.len())))
+                                (if show_citations.get() { "▼" } else { "▶" })
+                            }
+                            ul(class=if show_citations.get() { "mt-1" } else { "hidden" }) {
+                                (sources)
+                            }
+                        }
+                    }
                 })
             }
 
+            (if stack_len.get() > 1 {
+                view! {
+                    div(class="branch-navigator", title=branch_summary()) {
+                        button(
+                            class="action-button",
+                            title="Show the previous variant and the messages that follow it",
+                            disabled=is_response_pending.get() || selected_index.get() == 0,
+                            on:click=move |_| switch_branch(-1),
+                        ) { "‹" }
+                        span { (format!("Branch {}/{}", selected_index.get() + 1, stack_len.get())) }
+                        button(
+                            class="action-button",
+                            title="Show the next variant and the messages that follow it",
+                            disabled=is_response_pending.get()
+                                || selected_index.get() + 1 >= stack_len.get(),
+                            on:click=move |_| switch_branch(1),
+                        ) { "›" }
+                    }
+                }
+            } else {
+                view! {}
+            })
+
             div(class="message-actions") {
                 div(
                     class = "action-badge",
@@ -203,12 +368,14 @@ pub fn ChatMessageComponent(msg: Message) -> View {
                     ) {
                         button(
                             class="action-button",
+                            disabled=is_response_pending.get(),
                             on:click=move |_| {
                                 handle_purge_msgs();
                             }
                         ) { "Purge" }
                         button(
                             class="action-button",
+                            disabled=is_response_pending.get(),
                             on:click=move |_| {
                                 handle_delete_msg();
                             }
@@ -218,57 +385,42 @@ pub fn ChatMessageComponent(msg: Message) -> View {
 
                         button(
                             class="action-button",
+                            disabled=is_response_pending.get(),
                             on:click=move |_| {
                                 is_editing.set(!is_editing.get())
                             }
                         ) { "Edit" }
-                        button(
-                            class="action-button",
-                            on:click=move |_| {
-                                handle_regeneration();
-                            }
-                        ) { "Regenerate" }
-
-                        (if stack_len.get() > 1 {
+                        (if msg.ai_generated {
                             view! {
-                                span(class="action-separator") { "|" }
                                 button(
                                     class="action-button",
+                                    disabled=is_response_pending.get(),
                                     on:click=move |_| {
-                                        let active_chatlog = use_context::<Signal<Chatlog>>();
-                                        active_chatlog.get_clone().update_selected_index(msg.id, -1);
-                                        active_chatlog.update(|log| {
-                                            if let Some(msg) = log.get_message(msg.id) {
-                                                let selected_msg = msg.get_selected_message().unwrap();
-                                                msg_content.set(selected_msg.message);
-                                                msg_image.set(selected_msg.image_base64);
-                                            }
-                                        });
-                                        selected_index.set(selected_index.get().saturating_sub(1));
-                                    },
-                                ) { "Prev" }
-
-                                p { (format!("{}/{}", selected_index.clone().get() + 1, stack_len.get())) }
-
-                                button(
-                                    class="action-button",
-                                    on:click=move |_| {
-                                        let active_chatlog = use_context::<Signal<Chatlog>>();
-                                        active_chatlog.get_clone().update_selected_index(msg.id, 1);
-                                        active_chatlog.update(|log| {
-                                            if let Some(msg) = log.get_message(msg.id) {
-                                                let selected_msg = msg.get_selected_message().unwrap();
-                                                msg_content.set(selected_msg.message);
-                                                msg_image.set(selected_msg.image_base64);                                            }
-                                        });
-                                        selected_index.set((selected_index.get() + 1).min(stack_len.get() - 1));
-                                    },
-                                ) { "Next" }
+                                        handle_regeneration();
+                                    }
+                                ) { "Regenerate" }
                             }
                         } else {
                             view! {}
                         })
-
+                        button(
+                            class="action-button",
+                            title="Save the conversation up to this message as a new chatlog",
+                            disabled=is_response_pending.get(),
+                            on:click=move |_| {
+                                if is_response_pending.get_untracked() {
+                                    return;
+                                }
+                                saving_interface::fork_chatlog(msg.id);
+                                show_actions.set(false);
+                            }
+                        ) { "Fork" }
+                        button(
+                            class="action-button",
+                            on:click=move |_| {
+                                handle_speak();
+                            }
+                        ) { (if is_speaking() { "Stop" } else { "Speak" }) }
                     }
                 }
             }
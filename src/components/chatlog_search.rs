@@ -0,0 +1,162 @@
+use crate::{
+    components::saving_interface::load_chatlog,
+    models::{chatlog::ChatLogMetadata, config::ApiEndpointConfig, notifications::Notifications},
+    search::{self, SearchResult},
+    storage::AppStorage,
+};
+use sycamore::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::KeyboardEvent;
+
+// how much of a matching message is shown in the results
+const SNIPPET_CHARS: usize = 240;
+
+/// Renders the search box that finds the messages across all saved chatlogs that are
+/// the most similar in meaning to the query, using the configured embeddings endpoint.
+#[component(inline_props)]
+pub fn ChatlogSearchComponent() -> View {
+    let api_config = use_context::<Signal<ApiEndpointConfig>>();
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let notifications = use_context::<Notifications>();
+    let storage = use_context::<AppStorage>();
+
+    let query = create_signal(String::new());
+    let results = create_signal(Vec::<SearchResult>::new());
+    let is_busy = create_signal(false);
+    let has_searched = create_signal(false);
+    let is_enabled = move || api_config.with(|config| config.embeddings.enabled);
+
+    let handle_search = move || {
+        let query_text = query.get_clone_untracked().trim().to_string();
+        if query_text.is_empty() || is_busy.get_untracked() {
+            return;
+        }
+        let storage = use_context::<AppStorage>();
+        let config = api_config.get_clone_untracked();
+        let entries = metadata.get_clone_untracked().saved_logs;
+        is_busy.set(true);
+        spawn_local(async move {
+            match search::search_chatlogs(&config, &storage, &query_text, &entries).await {
+                Ok(found) => {
+                    results.set(found);
+                    has_searched.set(true);
+                }
+                Err(e) => notifications.error(format!("Unable to search the chatlogs: {}", e)),
+            }
+            is_busy.set(false);
+        });
+    };
+
+    let handle_rebuild = move |_| {
+        if is_busy.get_untracked() {
+            return;
+        }
+        let storage = storage.clone();
+        let config = api_config.get_clone_untracked();
+        let entries = metadata.get_clone_untracked().saved_logs;
+        is_busy.set(true);
+        spawn_local(async move {
+            match search::index_all_chatlogs(&config, &storage, &entries).await {
+                Ok(embedded) => notifications.success(format!(
+                    "Search index is up to date ({} new chunks embedded).",
+                    embedded
+                )),
+                Err(e) => notifications.error(format!("Unable to index the chatlogs: {}", e)),
+            }
+            is_busy.set(false);
+        });
+    };
+
+    let result_rows = move || {
+        results
+            .get_clone()
+            .into_iter()
+            .map(|result| {
+                let mut snippet: String = result.text.chars().take(SNIPPET_CHARS).collect();
+                if snippet.len() < result.text.len() {
+                    snippet.push('…');
+                }
+                let title = result.chatlog_title.clone();
+                let score = format!("{:.0}% match", result.score.max(0.0) * 100.0);
+                let handle_open = move |_| {
+                    load_chatlog(
+                        &result.storage_key,
+                        &result.chatlog_title,
+                        &result.chatlog_id,
+                        Some(result.message_id),
+                    )
+                };
+                view! {
+                    li(class="search-result") {
+                        div(class="flex items-center") {
+                            p(class="flex-1 font-semibold") { (title) }
+                            p(class="save-slot-header-secondary mr-2") {
+                                (score)
+                            }
+                            button(on:click=handle_open, class="save-slot-button") { "Open" }
+                        }
+                        p(class="search-result-snippet") { (snippet) }
+                    }
+                }
+            })
+            .collect::<Vec<View>>()
+    };
+
+    view! {
+        div(class="config-container mt-4") {
+            div(class="mb-4") {
+                h3(class="save-slot-header") {
+                    "Search"
+                }
+                p(class="save-slot-header-secondary") {
+                    (if is_enabled() {
+                        "Find messages by meaning across all saved chatlogs. Chatlogs are indexed when they are saved."
+                    } else {
+                        "Enable indexing in the embeddings settings of the API configuration to search the saved chatlogs."
+                    })
+                }
+            }
+
+            div(class="flex items-center gap-2") {
+                input(
+                    bind:value=query,
+                    class="message-input",
+                    placeholder="Search the saved chatlogs...",
+                    disabled=!is_enabled(),
+                    on:keydown=move |e: KeyboardEvent| {
+                        if e.key() == "Enter" {
+                            handle_search();
+                        }
+                    },
+                    r#type="text",
+                )
+                button(
+                    on:click=move |_| handle_search(),
+                    class="save-slot-button",
+                    disabled=!is_enabled() || is_busy.get(),
+                ) { "Search" }
+                button(
+                    on:click=handle_rebuild,
+                    class="save-slot-button",
+                    disabled=!is_enabled() || is_busy.get(),
+                ) { "Rebuild Index" }
+            }
+
+            (if is_busy.get() {
+                view! {
+                    div(class="flex justify-center items-center p-4") {
+                        div(class="progress-spinner") {}
+                    }
+                }
+            } else if has_searched.get() && results.with(|r| r.is_empty()) {
+                view! { p(class="mt-4") { "No matching messages found." } }
+            } else {
+                view! { }
+            })
+
+            ul(class="mt-2") {
+                (result_rows)
+            }
+        }
+    }
+}
@@ -1,7 +1,27 @@
 use crate::{
-    components::button_bar::ButtonBarComponent,
-    models::{config::ApiEndpointConfig, dark_mode::DarkMode, system_message::SystemMessage},
+    api_endpoint,
+    components::{
+        button_bar::ButtonBarComponent, embedding_config::EmbeddingConfigComponent,
+        image_compression_config::ImageCompressionConfigComponent,
+        image_generation_config::ImageGenerationConfigComponent,
+        mock_provider_config::MockProviderConfigComponent,
+        request_inspector::RequestInspectorComponent, speech_config::SpeechConfigComponent,
+        transcription_config::TranscriptionConfigComponent,
+    },
+    models::{
+        chatlog::Chatlog,
+        combine_queued_messages::CombineQueuedMessages,
+        config::{ApiEndpointConfig, ApiProvider},
+        dark_mode::DarkMode,
+        notifications::Notifications,
+        system_message::SystemMessage,
+    },
 };
+
+// formats a price for a text input, leaving it empty if there's no price
+fn price_to_string(price: Option<f64>) -> String {
+    price.map(|p| p.to_string()).unwrap_or_default()
+}
 use sycamore::prelude::*;
 
 #[component(inline_props)]
@@ -14,6 +34,17 @@ pub fn ConfigInterface() -> View {
     let api_endpoint = create_signal(config.endpoint.clone());
     let model_id = create_signal(config.model_id.clone());
     let api_key = create_signal(config.api_key.clone());
+    let use_mock_provider = create_signal(config.provider == ApiProvider::Mock);
+    create_effect(move || {
+        let provider = if use_mock_provider.get() {
+            ApiProvider::Mock
+        } else {
+            ApiProvider::OpenAiCompatible
+        };
+        if config_context_signal.with_untracked(|config| config.provider != provider) {
+            config_context_signal.update(|config| config.provider = provider);
+        }
+    });
 
     // create the signals for the advanced sampling parameters in the API configuration
     let show_advanced_settings = create_signal(false);
@@ -24,6 +55,30 @@ pub fn ConfigInterface() -> View {
     let top_k = create_signal(config.top_k.clone().unwrap_or_default());
     let min_p = create_signal(config.min_p.clone().unwrap_or_default());
     let repetition_penalty = create_signal(config.repetition_penalty.clone().unwrap_or_default());
+    let reasoning_effort = create_signal(config.reasoning_effort.clone().unwrap_or_default());
+    let thinking_budget = create_signal(config.thinking_budget.clone().unwrap_or_default());
+
+    // create the signals for the spending caps
+    let daily_token_limit = create_signal(config.daily_token_limit.clone().unwrap_or_default());
+    let monthly_token_limit = create_signal(config.monthly_token_limit.clone().unwrap_or_default());
+    let daily_cost_limit = create_signal(config.daily_cost_limit.clone().unwrap_or_default());
+    let monthly_cost_limit = create_signal(config.monthly_cost_limit.clone().unwrap_or_default());
+    let limit_warning_threshold =
+        create_signal(config.limit_warning_threshold.clone().unwrap_or_default());
+
+    // the price inputs show the entry in the price table for the current model
+    let prompt_price = create_signal(String::new());
+    let completion_price = create_signal(String::new());
+    let refresh_prices = move || {
+        let config = config_context_signal.get_clone_untracked();
+        let price = config.get_model_price(&model_id.get_clone_untracked());
+        prompt_price.set(price_to_string(price.map(|p| p.prompt)));
+        completion_price.set(price_to_string(price.map(|p| p.completion)));
+    };
+    create_effect(move || {
+        model_id.track();
+        refresh_prices();
+    });
 
     let toggle_advanced_settings = move || {
         show_advanced_settings.set(!show_advanced_settings.get());
@@ -42,6 +97,13 @@ pub fn ConfigInterface() -> View {
         let new_top_k = top_k.get_clone();
         let new_min_p = min_p.get_clone();
         let new_reppen = repetition_penalty.get_clone();
+        let new_reasoning_effort = reasoning_effort.get_clone();
+        let new_thinking_budget = thinking_budget.get_clone();
+        let new_daily_token_limit = daily_token_limit.get_clone();
+        let new_monthly_token_limit = monthly_token_limit.get_clone();
+        let new_daily_cost_limit = daily_cost_limit.get_clone();
+        let new_monthly_cost_limit = monthly_cost_limit.get_clone();
+        let new_limit_warning_threshold = limit_warning_threshold.get_clone();
         let new_config = ApiEndpointConfig {
             name: new_name,
             endpoint: new_endpoint,
@@ -82,19 +144,105 @@ pub fn ConfigInterface() -> View {
             } else {
                 Some(new_context_size)
             },
+            reasoning_effort: if new_reasoning_effort.is_empty() {
+                None
+            } else {
+                Some(new_reasoning_effort)
+            },
+            thinking_budget: if new_thinking_budget.is_empty() {
+                None
+            } else {
+                Some(new_thinking_budget)
+            },
+            daily_token_limit: if new_daily_token_limit.is_empty() {
+                None
+            } else {
+                Some(new_daily_token_limit)
+            },
+            monthly_token_limit: if new_monthly_token_limit.is_empty() {
+                None
+            } else {
+                Some(new_monthly_token_limit)
+            },
+            daily_cost_limit: if new_daily_cost_limit.is_empty() {
+                None
+            } else {
+                Some(new_daily_cost_limit)
+            },
+            monthly_cost_limit: if new_monthly_cost_limit.is_empty() {
+                None
+            } else {
+                Some(new_monthly_cost_limit)
+            },
+            limit_warning_threshold: if new_limit_warning_threshold.is_empty() {
+                None
+            } else {
+                Some(new_limit_warning_threshold)
+            },
+            // the settings that are edited elsewhere are kept as they are
+            ..config_context_signal.get_clone_untracked()
         };
         config_context_signal.set(new_config);
     };
 
+    // updates the price table entry for the current model; clearing both inputs removes it
+    let on_price_input = move |_| {
+        let new_prompt = prompt_price.get_clone();
+        let new_completion = completion_price.get_clone();
+        let price = if new_prompt.trim().is_empty() && new_completion.trim().is_empty() {
+            None
+        } else {
+            Some((
+                new_prompt.trim().parse().unwrap_or(0.0),
+                new_completion.trim().parse().unwrap_or(0.0),
+            ))
+        };
+        let current_model = model_id.get_clone();
+        config_context_signal.update(|config| config.set_model_price(&current_model, price));
+    };
+
+    // fetches the prices of all models from the API's `/models` listing and merges
+    // them into the price table.
+    let notifications = use_context::<Notifications>();
+    let handle_fetch_prices = move |_| {
+        let config = config_context_signal.get_clone_untracked();
+        api_endpoint::fetch_model_prices(config, move |result| match result {
+            Ok(prices) if prices.is_empty() => {
+                notifications.info("The API's model listing didn't include any prices.");
+            }
+            Ok(prices) => {
+                config_context_signal.update(|config| {
+                    for price in prices.iter() {
+                        config.set_model_price(
+                            &price.model_id,
+                            Some((price.prompt, price.completion)),
+                        );
+                    }
+                });
+                refresh_prices();
+                notifications.success(format!("Fetched the prices of {} models.", prices.len()));
+            }
+            Err(e) => notifications.error(format!("Unable to fetch the model prices: {}", e)),
+        });
+    };
+
     // create a signal for the system message
     let system_message_context = use_context::<SystemMessage>();
     let system_message = create_signal(system_message_context.signal().get_clone());
-    let system_message_signal = system_message_context.signal().clone();
+    let system_message_signal = system_message_context.signal();
     create_effect(move || {
         let new_sysmsg = system_message.get_clone();
         system_message_signal.set(new_sysmsg);
     });
 
+    // the reasoning setting is stored with the active chatlog so we bind directly to it
+    let keep_reasoning = use_context::<Signal<Chatlog>>()
+        .get_clone_untracked()
+        .keep_reasoning;
+
+    // how the messages queued during a pending response are sent
+    let combine_queued = use_context::<CombineQueuedMessages>().signal();
+
     // handle dark mode by applying the class to the overall container
     let dark_mode = use_context::<DarkMode>();
     let get_chat_container_classes = move || {
@@ -144,6 +292,16 @@ pub fn ConfigInterface() -> View {
                             r#type = "text")
                     }
 
+                    label(class = "config-checkbox-label") {
+                        input(r#type="checkbox", bind:checked=use_mock_provider)
+                        "Use the built-in offline mock provider"
+                    }
+                    (if use_mock_provider.get() {
+                        view! { MockProviderConfigComponent() }
+                    } else {
+                        view! {}
+                    })
+
                     div{
                         div(class="mb-2 mt-2 flex items-center cursor-pointer", on:click=move |_| {
                             toggle_advanced_settings();
@@ -234,6 +392,115 @@ pub fn ConfigInterface() -> View {
                                     placeholder="4096"
                                 )
                             }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Reasoning Effort:" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=reasoning_effort,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="low, medium or high"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Thinking Budget:" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=thinking_budget,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="1024"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Prompt Price (USD per million tokens):" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=prompt_price,
+                                    on:input=on_price_input,
+                                    r#type="text",
+                                    placeholder="0.0"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Completion Price (USD per million tokens):" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=completion_price,
+                                    on:input=on_price_input,
+                                    r#type="text",
+                                    placeholder="0.0"
+                                )
+                            }
+
+                            div(class="flex justify-end") {
+                                button(on:click=handle_fetch_prices, r#type="button", class="save-slot-button") {
+                                    "Fetch Prices From API"
+                                }
+                            }
+
+                            p(class="text-secondary-text dark:text-secondary-text-dark mt-4 mb-4") {
+                                "Spending caps for this endpoint. Once a cap is reached, requests are refused unless explicitly sent anyway."
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Daily Token Limit:" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=daily_token_limit,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="none"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Monthly Token Limit:" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=monthly_token_limit,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="none"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Daily Cost Limit (USD):" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=daily_cost_limit,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="none"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Monthly Cost Limit (USD):" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=monthly_cost_limit,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="none"
+                                )
+                            }
+
+                            div(class="config-group") {
+                                span(class="config-label") { "Warning Threshold (% of a limit):" }
+                                input(
+                                    class="config-textinput",
+                                    bind:value=limit_warning_threshold,
+                                    on:input=on_api_config_key,
+                                    r#type="text",
+                                    placeholder="80"
+                                )
+                            }
                         }
                     }
                 }
@@ -252,6 +519,46 @@ pub fn ConfigInterface() -> View {
                         textarea(class="message-input", rows="8", r#type = "text", bind:value=system_message)
                     }
                 }
+
+                div(class="config-container") {
+                    div(class = "mb-4") {
+                        h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                            "Reasoning"
+                        }
+                        p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                            "By default, the thought process of previous replies is removed before the conversation is sent back to the model. This setting is saved with the current chatlog."
+                        }
+                    }
+
+                    label(class = "config-checkbox-label") {
+                        input(r#type="checkbox", bind:checked=keep_reasoning)
+                        "Send prior reasoning back to the model"
+                    }
+                }
+
+                TranscriptionConfigComponent()
+                SpeechConfigComponent()
+                ImageGenerationConfigComponent()
+                ImageCompressionConfigComponent()
+                EmbeddingConfigComponent()
+
+                div(class="config-container") {
+                    div(class = "mb-4") {
+                        h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                            "Queued Messages"
+                        }
+                        p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                            "Messages sent while a reply is still on its way are queued and sent once it arrives. They can go out together in one request, or one at a time with a reply to each."
+                        }
+                    }
+
+                    label(class = "config-checkbox-label") {
+                        input(r#type="checkbox", bind:checked=combine_queued)
+                        "Send all queued messages in one request"
+                    }
+                }
+
+                RequestInspectorComponent()
             }
         }
     }
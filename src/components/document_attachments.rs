@@ -0,0 +1,157 @@
+use sycamore::prelude::*;
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::{File, HtmlInputElement};
+
+use crate::{
+    models::{
+        chatlog::Chatlog,
+        config::ApiEndpointConfig,
+        documents::{self, AttachedDocument},
+        notifications::Notifications,
+    },
+    retrieval,
+};
+
+// reads the file as text, chunks it and embeds the chunks when indexing is enabled. a
+// failure to embed only means the document is searched by keywords instead.
+async fn load_document(
+    api_config: &ApiEndpointConfig,
+    file: File,
+    notifications: Notifications,
+) -> Result<AttachedDocument, String> {
+    let text = wasm_bindgen_futures::JsFuture::from(file.text())
+        .await
+        .ok()
+        .and_then(|text| text.as_string())
+        .ok_or_else(|| format!("unable to read '{}' as text", file.name()))?;
+    let document = AttachedDocument::new(&file.name(), &text);
+    if document.chunks.is_empty() {
+        return Err(format!("'{}' is empty", file.name()));
+    }
+    if !api_config.embeddings.enabled {
+        return Ok(document);
+    }
+
+    match retrieval::embed_document(api_config, document.clone()).await {
+        Ok(embedded) => Ok(embedded),
+        Err(e) => {
+            notifications.warning(format!(
+                "'{}' couldn't be embedded and will be searched by keywords: {}",
+                file.name(),
+                e
+            ));
+            Ok(document)
+        }
+    }
+}
+
+/// Shows the documents attached to the active chatlog as removable chips, along with a
+/// button to attach more. The relevant parts of the documents are sent with every request.
+#[component(inline_props)]
+pub fn DocumentAttachmentsComponent() -> View {
+    let active_chatlog = use_context::<Signal<Chatlog>>();
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+    let notifications = use_context::<Notifications>();
+    let documents = active_chatlog.get_clone_untracked().documents;
+    let is_loading = create_signal(false);
+    let file_input_ref = create_node_ref();
+
+    let handle_files = move |event: web_sys::Event| {
+        let Some(input) = event
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+        else {
+            return;
+        };
+        let Some(file_list) = input.files() else {
+            return;
+        };
+        let files: Vec<File> = (0..file_list.length())
+            .filter_map(|i| file_list.get(i))
+            .collect();
+        input.set_value(""); // lets the same file be picked again
+
+        let config = config_context_signal.get_clone_untracked();
+        is_loading.set(true);
+        wasm_bindgen_futures::spawn_local(async move {
+            for file in files {
+                if !documents::is_supported_document(&file.name()) {
+                    notifications.warning(format!(
+                        "'{}' isn't a supported text document.",
+                        file.name()
+                    ));
+                    continue;
+                }
+                match load_document(&config, file, notifications).await {
+                    Ok(document) => {
+                        let name = document.name.clone();
+                        let parts = document.chunks.len();
+                        active_chatlog
+                            .get_clone_untracked()
+                            .attach_document(document);
+                        notifications.success(format!("Attached '{}' ({} parts).", name, parts));
+                    }
+                    Err(e) => notifications.error(format!("Unable to attach the document: {}", e)),
+                }
+            }
+            is_loading.set(false);
+        });
+    };
+
+    let open_file_dialog = move |_| {
+        if let Ok(input) = file_input_ref.get().dyn_into::<HtmlInputElement>() {
+            input.click();
+        }
+    };
+
+    let accepted_files = documents::SUPPORTED_EXTENSIONS
+        .iter()
+        .map(|ext| format!(".{}", ext))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let document_chips = move || {
+        documents
+            .get_clone()
+            .into_iter()
+            .enumerate()
+            .map(|(index, document)| {
+                let label = format!("{} ({} parts)", document.name, document.chunks.len());
+                view! {
+                    span(class="document-chip") {
+                        (label)
+                        button(
+                            class="document-chip-remove",
+                            title="Remove the document",
+                            on:click=move |_| {
+                                active_chatlog.get_clone_untracked().remove_document(index);
+                            },
+                        ) { "×" }
+                    }
+                }
+            })
+            .collect::<Vec<View>>()
+    };
+
+    view! {
+        div(class="document-bar") {
+            input(
+                r#ref=file_input_ref,
+                r#type="file",
+                multiple=true,
+                class="hidden",
+                accept=accepted_files,
+                on:change=handle_files,
+            )
+            button(
+                class="action-button",
+                disabled=is_loading.get(),
+                title="Attach text documents whose relevant parts are sent along with every message",
+                on:click=open_file_dialog,
+            ) {
+                (if is_loading.get() { "Attaching..." } else { "+ Document" })
+            }
+            (document_chips)
+        }
+    }
+}
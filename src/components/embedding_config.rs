@@ -0,0 +1,66 @@
+use sycamore::prelude::*;
+
+use crate::models::config::ApiEndpointConfig;
+
+/// The settings for the embeddings endpoint used to search the saved chatlogs. Changes are
+/// written straight into the API configuration context.
+#[component(inline_props)]
+pub fn EmbeddingConfigComponent() -> View {
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+
+    let embeddings = config_context_signal.get_clone_untracked().embeddings;
+    let enabled = create_signal(embeddings.enabled);
+    let endpoint = create_signal(embeddings.endpoint);
+    let api_key = create_signal(embeddings.api_key);
+    let model_id = create_signal(embeddings.model_id);
+
+    // writes the inputs back into the configuration whenever any of them change
+    create_effect(move || {
+        let new_enabled = enabled.get();
+        let new_endpoint = endpoint.get_clone();
+        let new_api_key = api_key.get_clone();
+        let new_model_id = model_id.get_clone();
+
+        let mut config = config_context_signal.get_clone_untracked();
+        config.embeddings.enabled = new_enabled;
+        config.embeddings.endpoint = new_endpoint;
+        config.embeddings.api_key = new_api_key;
+        config.embeddings.model_id = new_model_id;
+        if config != config_context_signal.get_clone_untracked() {
+            config_context_signal.set(config);
+        }
+    });
+
+    view! {
+        div(class="config-container") {
+            div(class = "mb-4") {
+                h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                    "Chatlog Search"
+                }
+                p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                    "Saved chatlogs are indexed with the `/embeddings` endpoint of an OpenAI compatible API so they can be searched by meaning. Only new or edited messages are sent when a chatlog is saved again. Leave the endpoint and key empty to use the ones above."
+                }
+            }
+
+            label(class = "config-checkbox-label mb-4") {
+                input(r#type="checkbox", bind:checked=enabled)
+                "Index chatlogs when they are saved"
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Embeddings Endpoint:" }
+                input(class="config-textinput", bind:value=endpoint, r#type="text",
+                    placeholder="same as the API endpoint")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Embeddings API Key:" }
+                input(class="config-textinput", bind:value=api_key, r#type="text",
+                    placeholder="same as the API key")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Embeddings Model ID:" }
+                input(class="config-textinput", bind:value=model_id, r#type="text")
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use sycamore::prelude::*;
+
+use crate::models::image_compression::{ImageCompression, ImageCompressionSettings, ImageFormat};
+
+/// The settings for how images attached to messages are downscaled and recompressed.
+/// Changes are written straight into the image compression context.
+#[component(inline_props)]
+pub fn ImageCompressionConfigComponent() -> View {
+    let compression_signal = use_context::<ImageCompression>().signal();
+
+    let settings = compression_signal.get_clone_untracked();
+    let enabled = create_signal(settings.enabled);
+    let max_width = create_signal(settings.max_width.to_string());
+    let max_height = create_signal(settings.max_height.to_string());
+    let use_webp = create_signal(settings.format == ImageFormat::Webp);
+    let quality = create_signal(format!("{:.0}", settings.quality * 100.0));
+
+    // writes the inputs back into the settings whenever any of them change. numbers that
+    // don't parse keep their previous value.
+    create_effect(move || {
+        let new_enabled = enabled.get();
+        let new_max_width = max_width.get_clone();
+        let new_max_height = max_height.get_clone();
+        let new_use_webp = use_webp.get();
+        let new_quality = quality.get_clone();
+
+        let old_settings = compression_signal.get_clone_untracked();
+        let new_settings = ImageCompressionSettings {
+            enabled: new_enabled,
+            max_width: new_max_width
+                .trim()
+                .parse()
+                .ok()
+                .filter(|w| *w > 0)
+                .unwrap_or(old_settings.max_width),
+            max_height: new_max_height
+                .trim()
+                .parse()
+                .ok()
+                .filter(|h| *h > 0)
+                .unwrap_or(old_settings.max_height),
+            format: if new_use_webp {
+                ImageFormat::Webp
+            } else {
+                ImageFormat::Jpeg
+            },
+            quality: new_quality
+                .trim()
+                .parse::<f64>()
+                .map(|q| (q / 100.0).clamp(0.01, 1.0))
+                .unwrap_or(old_settings.quality),
+        };
+        if new_settings != old_settings {
+            compression_signal.set(new_settings);
+        }
+    });
+
+    view! {
+        div(class="config-container") {
+            div(class = "mb-4") {
+                h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                    "Image Compression"
+                }
+                p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                    "Images attached to a message are scaled down to fit the maximum size and recompressed before they're sent, which keeps the saved chatlogs small and uses fewer tokens. GIFs are left untouched."
+                }
+            }
+
+            label(class = "config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=enabled)
+                "Compress attached images"
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Max Width:" }
+                input(class="config-textinput", bind:value=max_width, r#type="text",
+                    disabled=!enabled.get())
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Max Height:" }
+                input(class="config-textinput", bind:value=max_height, r#type="text",
+                    disabled=!enabled.get())
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Quality (%):" }
+                input(class="config-textinput", bind:value=quality, r#type="text",
+                    disabled=!enabled.get())
+            }
+            label(class = "config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=use_webp, disabled=!enabled.get())
+                "Use WebP instead of JPEG"
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use sycamore::prelude::*;
+
+use crate::models::config::ApiEndpointConfig;
+
+/// The settings for the image generation endpoint used by the `/image` command. Changes
+/// are written straight into the API configuration context.
+#[component(inline_props)]
+pub fn ImageGenerationConfigComponent() -> View {
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+
+    let image_generation = config_context_signal.get_clone_untracked().image_generation;
+    let endpoint = create_signal(image_generation.endpoint);
+    let api_key = create_signal(image_generation.api_key);
+    let model_id = create_signal(image_generation.model_id);
+    let size = create_signal(image_generation.size);
+
+    // writes the inputs back into the configuration whenever any of them change
+    create_effect(move || {
+        let new_endpoint = endpoint.get_clone();
+        let new_api_key = api_key.get_clone();
+        let new_model_id = model_id.get_clone();
+        let new_size = size.get_clone();
+
+        let mut config = config_context_signal.get_clone_untracked();
+        config.image_generation.endpoint = new_endpoint;
+        config.image_generation.api_key = new_api_key;
+        config.image_generation.model_id = new_model_id;
+        config.image_generation.size = new_size;
+        if config != config_context_signal.get_clone_untracked() {
+            config_context_signal.set(config);
+        }
+    });
+
+    view! {
+        div(class="config-container") {
+            div(class = "mb-4") {
+                h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                    "Image Generation"
+                }
+                p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                    "Messages starting with /image, or sent with the IMG toggle on, are sent to the `/images/generations` endpoint of an OpenAI compatible API, such as a stable diffusion server with an OpenAI shim. Leave the endpoint and key empty to use the ones above."
+                }
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Image Endpoint:" }
+                input(class="config-textinput", bind:value=endpoint, r#type="text",
+                    placeholder="same as the API endpoint")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Image API Key:" }
+                input(class="config-textinput", bind:value=api_key, r#type="text",
+                    placeholder="same as the API key")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Image Model ID:" }
+                input(class="config-textinput", bind:value=model_id, r#type="text")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Image Size:" }
+                input(class="config-textinput", bind:value=size, r#type="text", placeholder="1024x1024")
+            }
+        }
+    }
+}
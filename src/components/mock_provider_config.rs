@@ -0,0 +1,232 @@
+use sycamore::prelude::*;
+use web_sys::js_sys::Function;
+use web_sys::wasm_bindgen::prelude::Closure;
+use web_sys::{FileReader, HtmlInputElement, wasm_bindgen::JsCast};
+
+use crate::models::{
+    config::{ApiEndpointConfig, MockErrorKind, MockReply, MockReplyMode},
+    notifications::Notifications,
+};
+
+// the reply modes with the values used for them in the select element
+const REPLY_MODES: &[(MockReplyMode, &str, &str)] = &[
+    (MockReplyMode::Echo, "echo", "Echo the last message"),
+    (MockReplyMode::LoremIpsum, "lorem", "Lorem ipsum"),
+    (MockReplyMode::Scripted, "scripted", "Scripted replies"),
+];
+
+// the error kinds with the values used for them in the select element
+const ERROR_KINDS: &[(MockErrorKind, &str, &str)] = &[
+    (MockErrorKind::None, "none", "None"),
+    (MockErrorKind::Network, "network", "Network failure"),
+    (
+        MockErrorKind::Unauthorized,
+        "unauthorized",
+        "401 Unauthorized",
+    ),
+    (
+        MockErrorKind::RateLimited,
+        "rate_limited",
+        "429 Rate limited",
+    ),
+    (
+        MockErrorKind::ServerError,
+        "server_error",
+        "500 Server error",
+    ),
+    (
+        MockErrorKind::ContentFiltered,
+        "content_filtered",
+        "Content filtered",
+    ),
+    (
+        MockErrorKind::ContextLengthExceeded,
+        "context_length",
+        "Context length exceeded",
+    ),
+    (
+        MockErrorKind::MalformedResponse,
+        "malformed",
+        "Malformed response",
+    ),
+    (MockErrorKind::EmptyContent, "empty", "Empty reply"),
+];
+
+/// The settings for the built-in mock provider, shown in the API configuration when the
+/// mock provider is selected. Changes are written straight into the API configuration context.
+#[component(inline_props)]
+pub fn MockProviderConfigComponent() -> View {
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+    let notifications = use_context::<Notifications>();
+
+    let mock = config_context_signal.get_clone_untracked().mock;
+    let reply_mode = create_signal(
+        REPLY_MODES
+            .iter()
+            .find(|(mode, _, _)| *mode == mock.reply_mode)
+            .map(|(_, value, _)| value.to_string())
+            .unwrap_or_default(),
+    );
+    let error_kind = create_signal(
+        ERROR_KINDS
+            .iter()
+            .find(|(kind, _, _)| *kind == mock.error_kind)
+            .map(|(_, value, _)| value.to_string())
+            .unwrap_or_default(),
+    );
+    let latency_ms = create_signal(mock.latency_ms.clone().unwrap_or_default());
+    let error_rate = create_signal(mock.error_rate.clone().unwrap_or_default());
+    let simulate_streaming = create_signal(mock.simulate_streaming);
+    let include_thinking = create_signal(mock.include_thinking);
+    let reply_with_tool_calls = create_signal(mock.reply_with_tool_calls);
+    let scripted_reply_count = create_signal(mock.scripted_replies.len());
+
+    // writes the inputs back into the configuration whenever any of them change
+    create_effect(move || {
+        let new_reply_mode = REPLY_MODES
+            .iter()
+            .find(|(_, value, _)| *value == reply_mode.get_clone())
+            .map(|(mode, _, _)| *mode)
+            .unwrap_or_default();
+        let new_error_kind = ERROR_KINDS
+            .iter()
+            .find(|(_, value, _)| *value == error_kind.get_clone())
+            .map(|(kind, _, _)| *kind)
+            .unwrap_or_default();
+        let new_latency_ms = latency_ms.get_clone();
+        let new_error_rate = error_rate.get_clone();
+        let new_simulate_streaming = simulate_streaming.get();
+        let new_include_thinking = include_thinking.get();
+        let new_reply_with_tool_calls = reply_with_tool_calls.get();
+        let mut config = config_context_signal.get_clone_untracked();
+        config.mock.reply_mode = new_reply_mode;
+        config.mock.error_kind = new_error_kind;
+        config.mock.latency_ms = if new_latency_ms.is_empty() {
+            None
+        } else {
+            Some(new_latency_ms)
+        };
+        config.mock.error_rate = if new_error_rate.is_empty() {
+            None
+        } else {
+            Some(new_error_rate)
+        };
+        config.mock.simulate_streaming = new_simulate_streaming;
+        config.mock.include_thinking = new_include_thinking;
+        config.mock.reply_with_tool_calls = new_reply_with_tool_calls;
+        if config != config_context_signal.get_clone_untracked() {
+            config_context_signal.set(config);
+        }
+    });
+
+    // loads the scripted replies from a JSON file holding an array of strings
+    // or an array of `{ "content": ..., "reasoning": ... }` objects.
+    let on_script_file_change = move |event: web_sys::Event| {
+        let Some(input) = event
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+        else {
+            return;
+        };
+        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+
+        match FileReader::new() {
+            Ok(file_reader) => {
+                let file_reader_clone = file_reader.clone();
+                let handle_onload_js: Function = Closure::wrap(Box::new(move || {
+                    let text = file_reader_clone
+                        .result()
+                        .ok()
+                        .and_then(|res| res.as_string())
+                        .unwrap_or_default();
+                    match serde_json::from_str::<Vec<MockReply>>(&text) {
+                        Ok(replies) => {
+                            scripted_reply_count.set(replies.len());
+                            config_context_signal
+                                .update(|config| config.mock.scripted_replies = replies);
+                            notifications.success(format!(
+                                "Loaded {} scripted replies.",
+                                scripted_reply_count.get()
+                            ));
+                        }
+                        Err(e) => notifications
+                            .error(format!("Unable to read the scripted replies: {}", e)),
+                    }
+                })
+                    as Box<dyn FnMut()>)
+                .into_js_value()
+                .into();
+
+                file_reader.set_onload(Some(&handle_onload_js));
+                if let Err(err) = file_reader.read_as_text(&file) {
+                    console_log!("Reading the scripted replies failed: {:?}", err);
+                }
+            }
+            Err(err) => {
+                console_log!("FileReader failed to start: {:?}", err);
+            }
+        }
+        // clear the input so the same file can be loaded again after editing it
+        input.set_value("");
+    };
+
+    view! {
+        div(class="think-block-content") {
+            p(class="text-secondary-text dark:text-secondary-text-dark mb-4") {
+                "The mock provider answers offline without sending anything over the network, which is handy for development and for trying out error handling."
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Reply Mode:" }
+                select(class="config-textinput", bind:value=reply_mode) {
+                    (REPLY_MODES.iter().map(|(_, value, label)| view! {
+                        option(value=*value) { (*label) }
+                    }).collect::<Vec<_>>())
+                }
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Scripted Replies:" }
+                span(class="text-secondary-text dark:text-secondary-text-dark") {
+                    (format!("{} loaded", scripted_reply_count.get()))
+                }
+                input(class="config-textinput", r#type="file", accept=".json,application/json",
+                    on:change=on_script_file_change)
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Latency (ms):" }
+                input(class="config-textinput", bind:value=latency_ms, r#type="text", placeholder="500")
+            }
+
+            label(class="config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=simulate_streaming)
+                "Reply as a stream of server-sent events"
+            }
+            label(class="config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=include_thinking)
+                "Include a think block"
+            }
+            label(class="config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=reply_with_tool_calls)
+                "Reply with tool calls instead of text"
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Injected Error:" }
+                select(class="config-textinput", bind:value=error_kind) {
+                    (ERROR_KINDS.iter().map(|(_, value, label)| view! {
+                        option(value=*value) { (*label) }
+                    }).collect::<Vec<_>>())
+                }
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Error Rate (% of requests):" }
+                input(class="config-textinput", bind:value=error_rate, r#type="text", placeholder="100")
+            }
+        }
+    }
+}
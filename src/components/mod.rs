@@ -3,8 +3,23 @@ pub mod chat_input;
 pub mod chat_interface;
 pub mod chat_message;
 pub mod chatlog;
+pub mod chatlog_search;
 pub mod config_interface;
+pub mod document_attachments;
+pub mod embedding_config;
+pub mod image_compression_config;
+pub mod image_generation_config;
+pub mod mock_provider_config;
+pub mod notifications;
+pub mod queued_messages;
+pub mod request_inspector;
+pub mod response_error;
 pub mod saving_interface;
+pub mod speech_config;
+pub mod storage_usage;
+pub mod transcription_config;
+pub mod undo_redo;
+pub mod voice_input;
 
 // Helper function to detect mobile devices, based on user agent heuristics
 fn detect_mobile_device() -> bool {
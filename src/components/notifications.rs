@@ -0,0 +1,105 @@
+use sycamore::prelude::*;
+use web_sys::KeyboardEvent;
+
+use crate::models::notifications::{ConfirmationDialog, NotificationKind, Notifications};
+
+/// Renders the stack of non-blocking notifications in the corner of the page.
+#[component(inline_props)]
+pub fn NotificationsComponent() -> View {
+    let notifications = use_context::<Notifications>();
+    let items = notifications.signal();
+
+    view! {
+        div(class="notification-container") {
+            Keyed(
+                list=items,
+                view=move |item| {
+                    let item_id = item.id;
+                    view! {
+                        div(class=match item.kind {
+                            NotificationKind::Info => "notification notification-info",
+                            NotificationKind::Success => "notification notification-success",
+                            NotificationKind::Warning => "notification notification-warning",
+                            NotificationKind::Error => "notification notification-error",
+                        }) {
+                            span(class="flex-1") { (item.text.clone()) }
+                            button(
+                                class="notification-close-button",
+                                title="Dismiss",
+                                on:click=move |_| notifications.dismiss(item_id),
+                            ) { "X" }
+                        }
+                    }
+                },
+                key=|item| item.id,
+            )
+        }
+    }
+}
+
+/// Renders the modal confirmation dialog whenever a `ConfirmationRequest` is pending.
+#[component(inline_props)]
+pub fn ConfirmationDialogComponent() -> View {
+    let dialog = use_context::<ConfirmationDialog>();
+    let input_text = create_signal(String::new());
+
+    // reset the text input to the initial value of every new request
+    create_effect(move || {
+        let initial = dialog
+            .signal()
+            .with(|req| req.as_ref().and_then(|r| r.input.clone()));
+        input_text.set(initial.unwrap_or_default());
+    });
+
+    // the dialog gets closed before running the callback so that the callback
+    // is free to open another dialog.
+    let handle_confirm = move || {
+        if let Some(req) = dialog.signal().get_clone_untracked() {
+            dialog.close();
+            (req.on_confirm)(input_text.get_clone_untracked());
+        }
+    };
+
+    let on_keydown = move |event: KeyboardEvent| {
+        if event.key() == "Enter" {
+            event.prevent_default();
+            handle_confirm();
+        } else if event.key() == "Escape" {
+            dialog.close();
+        }
+    };
+
+    view! {
+        (if let Some(req) = dialog.signal().get_clone() {
+            view! {
+                div(class="modal-backdrop") {
+                    div(class="modal-dialog") {
+                        p(class="modal-message") { (req.message.clone()) }
+
+                        (if req.input.is_some() {
+                            view! {
+                                input(
+                                    class="config-textinput",
+                                    bind:value=input_text,
+                                    on:keydown=on_keydown,
+                                    r#type="text",
+                                )
+                            }
+                        } else {
+                            view! { }
+                        })
+
+                        div(class="modal-buttons") {
+                            button(class="save-slot-button", on:click=move |_| dialog.close()) { "Cancel" }
+                            button(class="save-slot-button", on:click=move |_| handle_confirm()) {
+                                (req.confirm_label.clone())
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            view! { }
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use sycamore::prelude::*;
+
+use crate::models::chatlog::Chatlog;
+
+/// Shows the user messages that were queued while a response was pending. Each one
+/// can be removed before it's sent.
+#[component(inline_props)]
+pub fn QueuedMessagesComponent() -> View {
+    let active_chatlog = use_context::<Signal<Chatlog>>();
+    let queued_messages = active_chatlog.get_clone_untracked().queued_messages;
+
+    view! {
+        (queued_messages.get_clone().into_iter().enumerate().map(|(index, queued)| {
+            let handle_remove = move |_| {
+                active_chatlog.get_clone_untracked().remove_queued_message(index);
+            };
+            view! {
+                div(class="message-container-user") {
+                    div(class="message-bubble-user message-bubble-queued") {
+                        div(class="message-content") { (queued.message.clone()) }
+                        div(class="image-strip") {
+                            (queued.images.iter().map(|data_url_str| {
+                                let data_url_str = data_url_str.clone();
+                                view! { img(src=data_url_str, alt="Queued Image", class="message-image") }
+                            }).collect::<Vec<_>>())
+                        }
+                        (queued.files.iter().map(|file| {
+                            let name = file.name.clone();
+                            view! { span(class="document-chip") { (name) } }
+                        }).collect::<Vec<_>>())
+                        div(class="actions-row") {
+                            span(class="queued-label") {
+                                (if queued.wants_image { "Queued image" } else { "Queued" })
+                            }
+                            button(on:click=handle_remove, class="action-button", title="Remove from the queue") {
+                                "Remove"
+                            }
+                        }
+                    }
+                }
+            }
+        }).collect::<Vec<_>>())
+    }
+}
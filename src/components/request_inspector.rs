@@ -0,0 +1,239 @@
+use sycamore::prelude::*;
+
+use crate::{
+    api_endpoint,
+    models::{
+        config::ApiEndpointConfig,
+        notifications::{ConfirmationDialog, Notifications},
+        request_inspector::{MAX_RECORDED_EXCHANGES, RecordedExchange, RequestInspector},
+        usage::{SpendingCapStatus, Usage, UsageSummary, current_day, current_month},
+    },
+};
+
+// formats headers one per line for display
+fn headers_to_string(headers: &[(String, String)]) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// pretty prints JSON bodies, leaving anything else as it is
+fn format_body(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// The opt-in developer panel that lists the raw requests sent to the API and the
+/// responses that came back.
+#[component(inline_props)]
+pub fn RequestInspectorComponent() -> View {
+    let inspector = use_context::<RequestInspector>();
+    let enabled = inspector.enabled();
+    let exchanges = inspector.exchanges();
+
+    view! {
+        div(class="config-container") {
+            div(class = "mb-4") {
+                h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                    "Request Inspector"
+                }
+                p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                    (format!("A developer panel that keeps the last {} requests sent to the API along with the raw responses. The API key is redacted from everything recorded here.", MAX_RECORDED_EXCHANGES))
+                }
+            }
+
+            label(class = "config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=enabled)
+                "Record requests and responses"
+            }
+
+            (if enabled.get() {
+                view! {
+                    div(class="flex justify-end") {
+                        button(on:click=move |_| inspector.clear(), r#type="button", class="save-slot-button") {
+                            "Clear"
+                        }
+                    }
+                    (if exchanges.with(|e| e.is_empty()) {
+                        view! {
+                            p(class="text-secondary-text dark:text-secondary-text-dark") {
+                                "No requests have been recorded yet."
+                            }
+                        }
+                    } else {
+                        view! {}
+                    })
+                    Keyed(
+                        list=exchanges,
+                        view=|exchange| view! { RecordedExchangeComponent(exchange=exchange) },
+                        key=|exchange| exchange.id,
+                    )
+                }
+            } else {
+                view! {}
+            })
+        }
+    }
+}
+
+// Replays the exchange; the result only shows up in the inspector, not in the chatlog, but
+// it's held to the spending caps and counts towards the usage like any other request.
+fn replay_exchange(
+    replayed: &RecordedExchange,
+    config: ApiEndpointConfig,
+    inspector: RequestInspector,
+    notifications: Notifications,
+    usage_summary: Signal<UsageSummary>,
+) {
+    let cap_status = usage_summary.with_untracked(|summary| {
+        summary.check_spending_caps(&config, &current_day(), &current_month())
+    });
+    match cap_status {
+        SpendingCapStatus::Exceeded(message) => {
+            notifications.error(format!("The request wasn't replayed. {}", message));
+            return;
+        }
+        SpendingCapStatus::Warning(message) => notifications.warning(message),
+        SpendingCapStatus::WithinLimits => {}
+    }
+
+    // the request is priced by the model it was sent for
+    let model_id = serde_json::from_str::<serde_json::Value>(&replayed.request_body)
+        .ok()
+        .and_then(|body| body["model"].as_str().map(|m| m.to_string()))
+        .unwrap_or_else(|| config.model_id.clone());
+    let price = config.get_model_price(&model_id).cloned();
+    let request_config = config.clone();
+    api_endpoint::replay_chat_completion_request(config, replayed, inspector, move |result| {
+        match result {
+            Ok(response) => {
+                let usage = Usage::from_tokens(
+                    response.prompt_tokens,
+                    response.completion_tokens,
+                    price.as_ref(),
+                );
+                usage_summary.update(|summary| {
+                    summary.record(
+                        &current_day(),
+                        &current_month(),
+                        &request_config.endpoint,
+                        &request_config.name,
+                        &usage,
+                    )
+                });
+                notifications.success("The replayed request succeeded.");
+            }
+            Err(e) => notifications.error(format!("The replayed request failed: {}", e)),
+        }
+    });
+}
+
+#[component(inline_props)]
+fn RecordedExchangeComponent(exchange: RecordedExchange) -> View {
+    let inspector = use_context::<RequestInspector>();
+    let notifications = use_context::<Notifications>();
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+    let usage_summary = use_context::<Signal<UsageSummary>>();
+    let confirmation = use_context::<ConfirmationDialog>();
+    let is_expanded = create_signal(false);
+
+    let summary = format!(
+        "{}  POST {}  {}  ({} ms)",
+        exchange.sent_at,
+        exchange.url,
+        match exchange.status {
+            Some(status) => status.to_string(),
+            None => "no response".to_string(),
+        },
+        exchange.elapsed_ms
+    );
+    let status_class = match exchange.status {
+        Some(status) if (200..300).contains(&status) => "inspector-summary",
+        _ => "inspector-summary inspector-summary-failed",
+    };
+
+    // copies the request as a curl command; the key is left as an environment variable
+    let curl = exchange.to_curl();
+    let handle_copy_curl = move |_| {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        let promise = window.navigator().clipboard().write_text(&curl);
+        wasm_bindgen_futures::spawn_local(async move {
+            match wasm_bindgen_futures::JsFuture::from(promise).await {
+                Ok(_) => notifications.success(
+                    "Copied the request as a curl command. Set API_KEY before running it.",
+                ),
+                Err(e) => {
+                    console_log!("Failed to write to the clipboard: {:?}", e);
+                    notifications.error("Unable to copy to the clipboard.");
+                }
+            }
+        });
+    };
+
+    // sends the same request again. a request that went to some other endpoint than the
+    // current one is only sent after asking, and then without the API key.
+    let replayed = exchange.clone();
+    let handle_replay = move |_| {
+        let config = config_context_signal.get_clone_untracked();
+        let current_url = api_endpoint::chat_completions_url(&config);
+        if replayed.url == current_url {
+            replay_exchange(&replayed, config, inspector, notifications, usage_summary);
+        } else {
+            let replayed = replayed.clone();
+            confirmation.confirm(
+                format!(
+                    "This request was sent to {}, which isn't the current endpoint ({}). Replay it there without the API key?",
+                    replayed.url, current_url
+                ),
+                "Replay without the key",
+                move || {
+                    let config = config_context_signal.get_clone_untracked();
+                    replay_exchange(&replayed, config, inspector, notifications, usage_summary)
+                },
+            );
+        }
+    };
+
+    let request_headers = headers_to_string(&exchange.request_headers);
+    let request_body = format_body(&exchange.request_body);
+    let response_headers = headers_to_string(&exchange.response_headers);
+    let response_body = match &exchange.error {
+        Some(error) => error.clone(),
+        None => format_body(&exchange.response_body),
+    };
+
+    view! {
+        div(class="inspector-entry") {
+            div(class=status_class, on:click=move |_| is_expanded.set(!is_expanded.get())) {
+                span(class="think-toggle") {
+                    (if is_expanded.get() { "▼" } else { "▶" })
+                }
+                (summary)
+            }
+            div(class=if is_expanded.get() { "think-block-content" } else { "hidden" }) {
+                div(class="flex justify-end gap-2 mb-2") {
+                    button(on:click=handle_copy_curl, r#type="button", class="save-slot-button") {
+                        "Copy as curl"
+                    }
+                    button(on:click=handle_replay, r#type="button", class="save-slot-button") {
+                        "Replay"
+                    }
+                }
+                h4(class="inspector-heading") { "Request Headers" }
+                pre(class="inspector-pre") { (request_headers.clone()) }
+                h4(class="inspector-heading") { "Request Body" }
+                pre(class="inspector-pre") { (request_body.clone()) }
+                h4(class="inspector-heading") { "Response Headers" }
+                pre(class="inspector-pre") { (response_headers.clone()) }
+                h4(class="inspector-heading") { "Response Body" }
+                pre(class="inspector-pre") { (response_body.clone()) }
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use sycamore::prelude::*;
+
+use crate::models::{
+    chatlog::Chatlog,
+    ignore_spending_cap::IgnoreSpendingCap,
+    is_editing_config::IsEditingConfig,
+    is_response_pending::IsResponsePending,
+    is_saving_chatlog::IsSavingChatlog,
+    response_error::{ErrorRecovery, ResponseError},
+};
+
+/// Renders the error of the last failed response generation beneath the chatlog
+/// along with the action most likely to recover from it.
+#[component(inline_props)]
+pub fn ResponseErrorComponent() -> View {
+    let response_error = use_context::<ResponseError>();
+    let is_response_pending = use_context::<IsResponsePending>();
+
+    let handle_retry = move |regenerating: Option<u32>| {
+        response_error.clear();
+        let log = use_context::<Signal<Chatlog>>().get_clone_untracked();
+        log.regenerating_msg.set(regenerating);
+        log.trigger_response_generation();
+    };
+
+    let handle_ignore_cap = move |regenerating: Option<u32>| {
+        use_context::<IgnoreSpendingCap>().signal().set(true);
+        handle_retry(regenerating);
+    };
+
+    let handle_open_config = move || {
+        use_context::<IsSavingChatlog>().signal().set(false);
+        use_context::<IsEditingConfig>().signal().set(true);
+    };
+
+    view! {
+        (if let Some(failed) = response_error.signal().get_clone() {
+            let regenerating = failed.regenerating;
+            view! {
+                div(class="message-container-ai") {
+                    div(class="response-error") {
+                        p(class="response-error-text") { (failed.message.clone()) }
+                        div(class="actions-row") {
+                            (match failed.recovery {
+                                ErrorRecovery::Retry => view! {
+                                    button(
+                                        class="action-button",
+                                        disabled=is_response_pending.signal().get(),
+                                        on:click=move |_| handle_retry(regenerating),
+                                    ) { "Retry" }
+                                },
+                                ErrorRecovery::OpenConfig => view! {
+                                    button(
+                                        class="action-button",
+                                        on:click=move |_| handle_open_config(),
+                                    ) { "Settings" }
+                                    button(
+                                        class="action-button",
+                                        disabled=is_response_pending.signal().get(),
+                                        on:click=move |_| handle_retry(regenerating),
+                                    ) { "Retry" }
+                                },
+                                ErrorRecovery::IgnoreCap => view! {
+                                    button(
+                                        class="action-button",
+                                        on:click=move |_| handle_open_config(),
+                                    ) { "Settings" }
+                                    button(
+                                        class="action-button",
+                                        disabled=is_response_pending.signal().get(),
+                                        on:click=move |_| handle_ignore_cap(regenerating),
+                                    ) { "Send Anyway" }
+                                },
+                                ErrorRecovery::None => view! { },
+                            })
+                            button(
+                                class="action-button",
+                                on:click=move |_| response_error.clear(),
+                            ) { "Dismiss" }
+                        }
+                    }
+                }
+            }
+        } else {
+            view! { }
+        })
+    }
+}
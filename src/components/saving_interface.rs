@@ -1,14 +1,27 @@
 use crate::{
-    components::button_bar::ButtonBarComponent,
+    components::{
+        button_bar::ButtonBarComponent,
+        chatlog_search::ChatlogSearchComponent,
+        storage_usage::{StorageUsageComponent, describe_storage_error},
+    },
     models::{
-        chatlog::{ChatLogMetadata, ChatLogMetadataEntry, Chatlog},
+        app_scope::AppScope,
+        autosave_to_slot::AutosaveToSlot,
+        chatlog::{ChatLogMetadata, ChatLogMetadataEntry, Chatlog, ForkOrigin},
         config::ApiEndpointConfig,
         dark_mode::DarkMode,
+        is_saving_chatlog::IsSavingChatlog,
+        jump_to_message::JumpToMessage,
+        linked_chatlog::LinkedChatlog,
+        notifications::{ConfirmationDialog, Notifications},
         system_message::SystemMessage,
+        usage::{Usage, UsageSummary},
     },
-    storage,
+    search,
+    storage::{AppStorage, StorageError, chatlogs},
 };
 use sycamore::prelude::*;
+use wasm_bindgen_futures::spawn_local;
 use web_sys::KeyboardEvent;
 
 // helper function to serialize the chatlog to JSON or return an error message.
@@ -19,72 +32,115 @@ fn serialize_chatlog(log: &Chatlog) -> Result<String, String> {
         .map_err(|e| format!("Serialization failed: {e}"))
 }
 
-// serialize the chatlog and save it to the browser's local storage using the key specific
+// serialize the chatlog and save it to the browser's storage using the key specific
 // for this chatlog that is derived from the chatlog's `id` as well as updates
 // the metadata for the chatlog. the user is asked to confirm this operation and
-// notified of the results.
-fn save_chatlog(
-    log: &Chatlog,
-    key: &str,
-    entry_title: &str,
-    entry_id: &str,
-    metadata: &Signal<ChatLogMetadata>,
-) {
-    let maybe_json_str = serialize_chatlog(&log);
-    if let Ok(json_str) = maybe_json_str {
-        // console_log!("JSON:\n{:?}", json_str);
-
-        if let Ok(confirmed) = window().confirm_with_message(&format!(
+// notified of the results. the search index of the chatlog is updated afterwards.
+fn save_chatlog(log: &Chatlog, entry: &ChatLogMetadataEntry, metadata: &Signal<ChatLogMetadata>) {
+    let notifications = use_context::<Notifications>();
+    let json_str = match serialize_chatlog(log) {
+        Ok(json_str) => json_str,
+        Err(e) => {
+            notifications.error(format!("ERROR: unable to save the chatlog: {}", e));
+            return;
+        }
+    };
+    // console_log!("JSON:\n{:?}", json_str);
+
+    let api_config = use_context::<Signal<ApiEndpointConfig>>().get_clone_untracked();
+    let storage = use_context::<AppStorage>();
+    let linked_chatlog = use_context::<LinkedChatlog>();
+    let log = log.clone();
+    let entry = entry.clone();
+    let metadata = *metadata;
+    use_context::<ConfirmationDialog>().confirm(
+        format!(
             "Save chatlog '{}'? This will overwrite the old chatlog permanently!",
-            entry_title
-        )) {
-            if confirmed {
-                match crate::storage::save_to_local_storage::<String>(key, &json_str) {
+            entry.title
+        ),
+        "Save",
+        move || {
+            let log = log.clone();
+            let json_str = json_str.clone();
+            let entry = entry.clone();
+            let api_config = api_config.clone();
+            let storage = storage.clone();
+            spawn_local(async move {
+                match chatlogs::save_chatlog(&storage, &entry, &json_str).await {
                     Ok(_) => {
+                        linked_chatlog.signal().set(Some(entry.id.clone()));
                         metadata.update(|meta| {
-                            if let Some(entry) =
-                                meta.saved_logs.iter_mut().find(|e| e.id == entry_id)
-                            {
-                                entry.message_count = log.messages.get_clone().len();
-                                entry.last_accessed_time =
-                                    web_sys::js_sys::Date::now().round() as i64;
-                            }
+                            meta.touch_entry(
+                                &entry.id,
+                                log.messages.get_clone().len(),
+                                log.usage.get(),
+                                web_sys::js_sys::Date::now().round() as i64,
+                            )
                         });
 
-                        let _ = window().alert_with_message(
-                            format!("Chatlog saved successfully ('{}').", entry_title).as_str(),
+                        notifications
+                            .success(format!("Chatlog saved successfully ('{}').", entry.title));
+                        search::index_saved_chatlog(
+                            api_config,
+                            storage.clone(),
+                            notifications,
+                            entry.id,
+                            log.messages.get_clone_untracked(),
                         );
                     }
                     Err(e) => {
-                        console_log!("save_to_local_storage error: {:?}", e);
-                        let _ = window().alert_with_message(
-                            format!("ERROR: unable to save the chatlog ('{}')!", entry_title)
-                                .as_str(),
-                        );
+                        notifications.error(format!(
+                            "ERROR: unable to save the chatlog ('{}'): {}",
+                            entry.title,
+                            describe_storage_error(&e)
+                        ));
                     }
-                };
-            }
-        }
-    }
+                }
+            });
+        },
+    );
 }
 
-// loads the chatlog from the browser's local storage under the key associated
+// loads the chatlog from the browser's storage under the key associated
 // with this chatlog in the metadata, deserializes it and then sets the relevant
 // data: the chatlog, API settings and system message. The metadata last accessed time
 // is updated and the user is queried to confirm the loading operation and is notified
-// of the results.
-fn load_chatlog(key: &str, entry_title: &str, entry_id: &str) {
-    let confirmed = match window().confirm_with_message(&format!("Load chatlog '{}'?", entry_title))
-    {
-        Ok(c) => c,
-        Err(_) => return,
-    };
-    if !confirmed {
-        return;
-    }
+// of the results. when `jump_to` is set, the chat interface is shown scrolled to that message.
+pub(crate) fn load_chatlog(key: &str, entry_title: &str, entry_id: &str, jump_to: Option<u32>) {
+    let app_scope = use_context::<AppScope>();
+    let storage = use_context::<AppStorage>();
+    let key = key.to_string();
+    let entry_title = entry_title.to_string();
+    let entry_id = entry_id.to_string();
+    use_context::<ConfirmationDialog>().confirm(
+        format!("Load chatlog '{}'?", entry_title),
+        "Load",
+        move || {
+            let key = key.clone();
+            let entry_title = entry_title.clone();
+            let entry_id = entry_id.clone();
+            let storage = storage.clone();
+            spawn_local(async move {
+                let json_str = chatlogs::load_chatlog(&storage, &key).await;
+                app_scope.run_in(move || {
+                    apply_loaded_chatlog(json_str, &entry_title, &entry_id, jump_to)
+                });
+            });
+        },
+    );
+}
 
-    let Some(json_str) = storage::load_from_local_storage::<String>(key) else {
-        let _ = window().alert_with_message(&format!(
+// sets the chatlog, API settings and system message from the JSON of a chatlog that was
+// loaded, or reports that it couldn't be.
+fn apply_loaded_chatlog(
+    json_str: Option<String>,
+    entry_title: &str,
+    entry_id: &str,
+    jump_to: Option<u32>,
+) {
+    let notifications = use_context::<Notifications>();
+    let Some(json_str) = json_str else {
+        notifications.error(format!(
             "ERROR: unable to load chatlog ('{}').",
             entry_title
         ));
@@ -96,56 +152,73 @@ fn load_chatlog(key: &str, entry_title: &str, entry_id: &str) {
             use_context::<Signal<ApiEndpointConfig>>().set(new_api);
             use_context::<SystemMessage>().signal().set(new_sysmsg);
             use_context::<Signal<Chatlog>>().update(|log| log.clone_from(&new_log));
+            use_context::<LinkedChatlog>()
+                .signal()
+                .set(Some(entry_id.to_string()));
             use_context::<Signal<ChatLogMetadata>>().update(|meta| {
-                if let Some(entry) = meta.saved_logs.iter_mut().find(|e| e.id == entry_id) {
-                    entry.message_count = new_log.messages.get_clone().len();
-                    entry.last_accessed_time = web_sys::js_sys::Date::now().round() as i64;
-                }
+                meta.touch_entry(
+                    entry_id,
+                    new_log.messages.get_clone().len(),
+                    new_log.usage.get(),
+                    web_sys::js_sys::Date::now().round() as i64,
+                )
             });
 
-            let _ = window()
-                .alert_with_message(&format!("Chatlog successfully loaded ('{}').", entry_title));
+            notifications.success(format!("Chatlog successfully loaded ('{}').", entry_title));
+
+            // the chatlog is shown at the message asked for, e.g. where it was forked, if it's
+            // still there
+            if let Some(msg_id) = jump_to {
+                if new_log.get_message(msg_id).is_some() {
+                    use_context::<JumpToMessage>().signal().set(Some(msg_id));
+                } else {
+                    notifications.warning(format!(
+                        "The message to show is no longer in '{}', so it's shown from the end.",
+                        entry_title
+                    ));
+                }
+                use_context::<IsSavingChatlog>().signal().set(false);
+            }
         }
         Err(e) => {
-            let _ = window().alert_with_message(&format!("Chatlog could not be loaded: {}", e));
+            notifications.error(format!("Chatlog could not be loaded: {}", e));
         }
     }
 }
 
 // deletes the key associated with the chatlog from the metadata in the browser's
-// local storage. the user is queried to confirm the deletion and is notified of
+// storage. the user is queried to confirm the deletion and is notified of
 // the results.
-fn delete_chatlog(key: &str, entry_title: &str, entry_id: &str) {
-    let confirmed = match window()
-        .confirm_with_message(&format!("Delete chatlog '{}' permanently?", entry_title))
-    {
-        Ok(c) => c,
-        Err(_) => return,
-    };
-    if !confirmed {
-        return;
-    }
-
-    match storage::delete_from_local_storage(key) {
-        Ok(()) => {
-            use_context::<Signal<ChatLogMetadata>>().update(|meta| {
-                if let Some(idx) = meta.saved_logs.iter().position(|e| e.id == entry_id) {
-                    meta.saved_logs.remove(idx);
+fn delete_chatlog(entry: &ChatLogMetadataEntry) {
+    let notifications = use_context::<Notifications>();
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let storage = use_context::<AppStorage>();
+    let linked_chatlog = use_context::<LinkedChatlog>();
+    let entry = entry.clone();
+    use_context::<ConfirmationDialog>().confirm(
+        format!("Delete chatlog '{}' permanently?", entry.title),
+        "Delete",
+        move || {
+            let entry = entry.clone();
+            let storage = storage.clone();
+            spawn_local(async move {
+                match chatlogs::delete_chatlog(&storage, &entry).await {
+                    Ok(()) => {
+                        metadata.update(|meta| meta.remove_entry(&entry.id));
+                        linked_chatlog.unlink_from(&entry.id);
+                        notifications
+                            .success(format!("Chatlog deleted successfully ('{}').", entry.title));
+                    }
+                    Err(e) => {
+                        notifications.error(format!(
+                            "ERROR: unable to delete the chatlog ('{}'): {}",
+                            entry.title, e
+                        ));
+                    }
                 }
             });
-            let _ = window().alert_with_message(&format!(
-                "Chatlog deleted successfully ('{}').",
-                entry_title
-            ));
-        }
-        Err(e) => {
-            console_log!("delete_from_local_storage error: {:?}", e);
-            let _ = window().alert_with_message(&format!(
-                "ERROR: unable to delete the chatlog ('{}')!",
-                entry_title
-            ));
-        }
-    }
+        },
+    );
 }
 
 // Component to display and manage a single chat log entry
@@ -154,33 +227,74 @@ fn ChatLogEntryComponent(entry: ChatLogMetadataEntry) -> View {
     let metadata_signal = use_context::<Signal<ChatLogMetadata>>();
     let is_editing = create_signal(false);
     let edited_title = create_signal(entry.title.clone());
-    let entry_id = entry.id.clone();
-    let key = format!("chatlog_{}", entry_id);
-
-    let key_clone = key.clone();
-    let entry_id_clone = entry_id.clone();
-    let entry_title_clone = entry.title.clone();
+    let saved_entry = entry.clone();
     let handle_save = move |_| {
         let log = use_context::<Signal<Chatlog>>().get_clone();
         let metadata = use_context::<Signal<ChatLogMetadata>>();
-        save_chatlog(
-            &log,
-            &key_clone,
-            &entry_title_clone,
-            &entry_id_clone,
-            &metadata,
-        );
+        save_chatlog(&log, &saved_entry, &metadata);
     };
 
-    let key_clone = key.clone();
-    let entry_id_clone = entry_id.clone();
-    let entry_title_clone = entry.title.clone();
-    let handle_load = move |_| load_chatlog(&key_clone, &entry_title_clone, &entry_id_clone);
+    let loaded_entry = entry.clone();
+    let handle_load = move |_| {
+        load_chatlog(
+            &loaded_entry.storage_key,
+            &loaded_entry.title,
+            &loaded_entry.id,
+            None,
+        )
+    };
 
-    let key_clone = key.clone();
-    let entry_id_clone = entry_id.clone();
-    let entry_title_clone = entry.title.clone();
-    let handle_delete = move |_| delete_chatlog(&key_clone, &entry_title_clone, &entry_id_clone);
+    let deleted_entry = entry.clone();
+    let handle_delete = move |_| delete_chatlog(&deleted_entry);
+
+    let linked_chatlog = use_context::<LinkedChatlog>();
+    let autosave_to_slot = use_context::<AutosaveToSlot>().signal();
+    let linked_id = entry.id.clone();
+    let link_status = move || match (
+        linked_chatlog.is_linked_to(&linked_id),
+        autosave_to_slot.get(),
+    ) {
+        (true, true) => "Currently open; changes are saved into it automatically",
+        (true, false) => "Currently open",
+        _ => "",
+    };
+
+    // where a fork came from, with a way to open its parent at the message it was forked at
+    let fork_origin = match &entry.forked_from {
+        Some(origin) => {
+            let parent = origin.parent_id.as_ref().and_then(|id| {
+                metadata_signal
+                    .with_untracked(|meta| meta.saved_logs.iter().find(|e| e.id == *id).cloned())
+            });
+            match parent {
+                Some(parent) => {
+                    let message_id = origin.message_id;
+                    let description = format!("Forked from '{}'", parent.title);
+                    view! {
+                        p(class="save-slot-header-secondary") {
+                            (description)
+                            button(
+                                class="action-button ml-2",
+                                title="Load the chatlog this one was forked from, at the message it was forked at",
+                                on:click=move |_| load_chatlog(
+                                    &parent.storage_key,
+                                    &parent.title,
+                                    &parent.id,
+                                    Some(message_id),
+                                ),
+                            ) { "Show" }
+                        }
+                    }
+                }
+                None => view! {
+                    p(class="save-slot-header-secondary") {
+                        "Forked from a chatlog that isn't saved"
+                    }
+                },
+            }
+        }
+        None => view! {},
+    };
 
     let entry_id = entry.id.clone();
     view! {
@@ -232,6 +346,13 @@ fn ChatLogEntryComponent(entry: ChatLogMetadataEntry) -> View {
                     p {
                         (format!("{} messages", entry.message_count))
                     }
+                    (fork_origin)
+                    p(class=if entry.usage.is_empty() { "hidden" } else { "save-slot-header-secondary" }) {
+                        (entry.usage.describe())
+                    }
+                    p(class="save-slot-header-secondary") {
+                        (link_status())
+                    }
                 }
 
                 div(class="flex-none ml-2") {
@@ -258,9 +379,9 @@ fn import_json(json_str: String) -> Result<(), String> {
             use_context::<Signal<ApiEndpointConfig>>().set(new_api);
             use_context::<SystemMessage>().signal().set(new_sysmsg);
             use_context::<Signal<Chatlog>>().update(|log| log.clone_from(&new_log));
-            window()
-                .alert_with_message("Chatlog successfully imported.")
-                .ok();
+            // the imported chatlog isn't one of the saved ones, so it's kept from overwriting them
+            use_context::<LinkedChatlog>().signal().set(None);
+            use_context::<Notifications>().success("Chatlog successfully imported.");
             Ok(())
         }
         Err(e) => Err(format!("Failed to import JSON: {e}")),
@@ -275,80 +396,223 @@ fn export_json() -> Result<String, String> {
 
 // prompts the user for a new chatlog name and then serializes the active chatlog
 // into a new chatlog with the new name and adds it to the list of chatlogs
-// saved in the local storage of the browser.
+// saved in the storage of the browser.
 fn save_as() -> Result<(), String> {
     let log = use_context::<Signal<Chatlog>>().get_clone();
     let json_str = serialize_chatlog(&log)?;
+    let messages = log.messages.get_clone_untracked();
+    let message_count = messages.len();
+    let usage = log.usage.get_untracked();
+    let api_config = use_context::<Signal<ApiEndpointConfig>>().get_clone_untracked();
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let storage = use_context::<AppStorage>();
+    let linked_chatlog = use_context::<LinkedChatlog>();
+
+    let notifications = use_context::<Notifications>();
+    use_context::<ConfirmationDialog>().prompt(
+        "Enter a new name for the chatlog:",
+        "",
+        move |title| {
+            if title.trim().is_empty() {
+                return; // nothing to name the chatlog with
+            }
 
-    let Some(title) = window()
-        .prompt_with_message("Enter a new name for the chatlog:")
-        .ok()
-        .flatten()
-        .filter(|s| !s.trim().is_empty())
-    else {
-        return Ok(()); // user cancelled or empty
-    };
+            let json_str = json_str.clone();
+            let messages = messages.clone();
+            let api_config = api_config.clone();
+            let storage = storage.clone();
+            spawn_local(async move {
+                let result = save_new_chatlog(
+                    &storage,
+                    &json_str,
+                    &title,
+                    message_count,
+                    usage,
+                    None,
+                    metadata,
+                )
+                .await;
+                match result {
+                    Ok(new_log_id) => {
+                        linked_chatlog.signal().set(Some(new_log_id.clone()));
+                        notifications.success(format!("Chatlog '{}' saved successfully.", title));
+                        search::index_saved_chatlog(
+                            api_config,
+                            storage.clone(),
+                            notifications,
+                            new_log_id,
+                            messages,
+                        );
+                    }
+                    Err(e) => notifications.error(format!(
+                        "ERROR: unable to save the chatlog as a new log: {}",
+                        describe_storage_error(&e)
+                    )),
+                }
+            });
+        },
+    );
+    Ok(())
+}
 
-    let new_log_id = storage::generate_chatlog_id();
-    let new_storage_key = format!("chatlog_{}", new_log_id);
-    storage::save_to_local_storage::<String>(&new_storage_key, &json_str)
-        .map_err(|js| js.as_string().unwrap_or_else(|| "JS storage error".into()))?;
-
-    use_context::<Signal<ChatLogMetadata>>().update(|meta| {
-        meta.saved_logs.push(ChatLogMetadataEntry {
-            id: new_log_id,
-            title: title.clone(),
-            last_accessed_time: web_sys::js_sys::Date::now().round() as i64,
-            storage_key: new_storage_key,
-            message_count: log.messages.get_clone_untracked().len(),
+// prompts the user for a name and saves the messages of the active chatlog up to and
+// including the one with the id as a new chatlog, along with the API settings and the
+// system message. the new chatlog remembers the saved chatlog it was forked from, if
+// any, so that it's listed with it. the active chatlog stays open.
+pub(crate) fn fork_chatlog(msg_id: u32) {
+    let notifications = use_context::<Notifications>();
+    let log = use_context::<Signal<Chatlog>>().get_clone_untracked();
+    let Some(fork) = log.fork(msg_id) else {
+        notifications.error("ERROR: unable to fork the chatlog: the message is gone.");
+        return;
+    };
+    let json_str = match serialize_chatlog(&fork) {
+        Ok(json_str) => json_str,
+        Err(e) => {
+            notifications.error(format!("ERROR: unable to fork the chatlog: {}", e));
+            return;
+        }
+    };
+    let messages = fork.messages.get_clone_untracked();
+    let api_config = use_context::<Signal<ApiEndpointConfig>>().get_clone_untracked();
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let storage = use_context::<AppStorage>();
+
+    // the saved chatlog the active one is linked to is its parent, if it still holds
+    // the messages of the fork as they're shown
+    let parent = use_context::<LinkedChatlog>()
+        .signal()
+        .get_clone_untracked()
+        .and_then(|id| {
+            metadata.with_untracked(|meta| meta.saved_logs.iter().find(|e| e.id == id).cloned())
         });
-    });
+    let parent_title = parent.as_ref().map(|e| e.title.clone());
+
+    use_context::<ConfirmationDialog>().prompt(
+        "Enter a name for the chatlog forked from this message:",
+        format!("{} (fork)", parent_title.as_deref().unwrap_or("Chatlog")),
+        move |title| {
+            if title.trim().is_empty() {
+                return; // nothing to name the chatlog with
+            }
 
-    window()
-        .alert_with_message(&format!("Chatlog '{}' saved successfully.", title))
-        .ok();
-    Ok(())
+            let json_str = json_str.clone();
+            let messages = messages.clone();
+            let api_config = api_config.clone();
+            let storage = storage.clone();
+            let parent = parent.clone();
+            spawn_local(async move {
+                let forked_from = ForkOrigin {
+                    parent_id: chatlogs::fork_parent(&storage, parent.as_ref(), &messages).await,
+                    message_id: msg_id,
+                };
+                let result = save_new_chatlog(
+                    &storage,
+                    &json_str,
+                    &title,
+                    messages.len(),
+                    Usage::default(),
+                    Some(forked_from),
+                    metadata,
+                )
+                .await;
+                match result {
+                    Ok(new_log_id) => {
+                        notifications.success(format!(
+                            "Forked into the new chatlog '{}', which can be loaded from the chatlogs.",
+                            title
+                        ));
+                        search::index_saved_chatlog(
+                            api_config,
+                            storage.clone(),
+                            notifications,
+                            new_log_id,
+                            messages,
+                        );
+                    }
+                    Err(e) => notifications.error(format!(
+                        "ERROR: unable to fork the chatlog: {}",
+                        describe_storage_error(&e)
+                    )),
+                }
+            });
+        },
+    );
+}
+
+// saves the serialized chatlog under a newly generated storage key and adds
+// an entry for it to the chatlog metadata. returns the id of the new chatlog.
+async fn save_new_chatlog(
+    storage: &AppStorage,
+    json_str: &str,
+    title: &str,
+    message_count: usize,
+    usage: Usage,
+    forked_from: Option<ForkOrigin>,
+    metadata: Signal<ChatLogMetadata>,
+) -> Result<String, StorageError> {
+    let now = web_sys::js_sys::Date::now().round() as i64;
+    let entry = chatlogs::save_new_chatlog(
+        storage,
+        json_str,
+        title,
+        message_count,
+        usage,
+        forked_from,
+        now,
+    )
+    .await?;
+    let new_log_id = entry.id.clone();
+    metadata.update(|meta| meta.saved_logs.push(entry));
+    Ok(new_log_id)
 }
 
 // creates a brand new chatlog, serializes and saves it to the list of
-// chatlogs saved in the local storage of the browser.
+// chatlogs saved in the storage of the browser.
 fn new_chatlog() -> Result<(), String> {
     let log = Chatlog::new(crate::generate_response);
     let json_str = serialize_chatlog(&log)?;
-
-    let log_id = storage::generate_chatlog_id();
-    let key = format!("chatlog_{}", log_id);
-    storage::save_to_local_storage::<String>(&key, &json_str)
-        .map_err(|js| js.as_string().unwrap_or_else(|| "JS storage error".into()))?;
-
-    let timestamp = web_sys::js_sys::Date::now().round() as i64;
-    use_context::<Signal<ChatLogMetadata>>().update(|meta| {
-        meta.saved_logs.push(ChatLogMetadataEntry {
-            id: log_id,
-            title: "New Chatlog".to_string(),
-            last_accessed_time: timestamp,
-            storage_key: key,
-            message_count: 0,
-        });
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let notifications = use_context::<Notifications>();
+    let storage = use_context::<AppStorage>();
+
+    spawn_local(async move {
+        let result = save_new_chatlog(
+            &storage,
+            &json_str,
+            "New Chatlog",
+            0,
+            Usage::default(),
+            None,
+            metadata,
+        )
+        .await;
+        if let Err(e) = result {
+            notifications.error(format!(
+                "Failed to create a new chatlog: {}",
+                describe_storage_error(&e)
+            ));
+        }
     });
     Ok(())
 }
 
-#[component(inline_props)]
+#[component]
 pub fn SavingInterface() -> View {
     // this is bound to the textarea for JSON importing
     let imported_json = create_signal(String::new());
+    let notifications = use_context::<Notifications>();
     let handle_import_json = move |_| {
         let json_str = imported_json.get_clone();
         if let Err(e) = import_json(json_str) {
-            let _ = window().alert_with_message(&e);
+            notifications.error(e);
         }
     };
 
     let handle_export_json = move |_| match export_json() {
         Ok(json_str) => imported_json.set(json_str),
         Err(e) => {
-            let _ = window().alert_with_message(&format!(
+            notifications.error(format!(
                 "ERROR: failed to export the active chatlog to JSON: {}",
                 e
             ));
@@ -357,7 +621,7 @@ pub fn SavingInterface() -> View {
 
     let handle_save_as = move |_| {
         if let Err(e) = save_as() {
-            let _ = window().alert_with_message(&format!(
+            notifications.error(format!(
                 "ERROR: unable to save the chatlog as a new log: {}",
                 e
             ));
@@ -366,7 +630,7 @@ pub fn SavingInterface() -> View {
 
     let handle_new_chatlog = move |_| {
         if let Err(e) = new_chatlog() {
-            let _ = window().alert_with_message(&format!("Failed to create a new chatlog: {}", e));
+            notifications.error(format!("Failed to create a new chatlog: {}", e));
         }
     };
 
@@ -385,30 +649,44 @@ pub fn SavingInterface() -> View {
 
     let entries_components = move || {
         let metadata_signal = use_context::<Signal<ChatLogMetadata>>();
-        let current_metadata = metadata_signal.get_clone();
-        let mut sorted_entries = current_metadata.saved_logs.clone();
-        // Sort in descending order by timestamp
-        sorted_entries.sort_by(|a, b| b.last_accessed_time.cmp(&a.last_accessed_time));
-        sorted_entries
-            .iter()
-            .map(|entry| {
-                let cloned = entry.clone();
-                ChatLogEntryComponent(cloned)
+        // newest first, with the forks of a chatlog indented below it
+        metadata_signal
+            .get_clone()
+            .grouped_by_fork()
+            .into_iter()
+            .map(|(depth, entry)| {
+                let entry_view = ChatLogEntryComponent(entry);
+                view! {
+                    div(style=format!("margin-left: {}rem", depth * 2)) {
+                        (entry_view)
+                    }
+                }
             })
             .collect::<Vec<View>>()
     };
 
-    // add effect to save metadata on change
-    let metadata_clone = use_context::<Signal<ChatLogMetadata>>().clone();
-    create_effect(move || {
-        let metadata = metadata_clone.get_clone();
-        if let Err(e) = storage::save_to_local_storage::<ChatLogMetadata>(
-            crate::LSKEY_CHATLOG_METADATA,
-            &metadata,
-        ) {
-            console_log!("Failed to save chatlog metadata: {:?}", e);
-        }
-    });
+    let autosave_to_slot = use_context::<AutosaveToSlot>().signal();
+
+    // the usage of the active chatlog and the monthly usage of every endpoint, newest first
+    let active_usage = use_context::<Signal<Chatlog>>().get_clone_untracked().usage;
+    let usage_rows = move || {
+        let mut entries = use_context::<Signal<UsageSummary>>().get_clone().entries;
+        entries.sort_by(|a, b| b.month.cmp(&a.month).then(a.endpoint.cmp(&b.endpoint)));
+        entries
+            .into_iter()
+            .map(|entry| {
+                view! {
+                    tr {
+                        td(class="usage-cell") { (entry.month) }
+                        td(class="usage-cell", title=entry.endpoint) { (entry.endpoint_name) }
+                        td(class="usage-cell text-right") { (entry.request_count) }
+                        td(class="usage-cell text-right") { (entry.usage.total_tokens()) }
+                        td(class="usage-cell text-right") { (format!("${:.4}", entry.usage.cost)) }
+                    }
+                }
+            })
+            .collect::<Vec<View>>()
+    };
 
     view! {
         div (class = get_chat_container_classes()) {
@@ -421,7 +699,11 @@ pub fn SavingInterface() -> View {
                             "Chatlogs"
                         }
                         p(class = "save-slot-header-secondary") {
-                            "This is the list of currently saved chatlogs in your web browser's local storage."
+                            "This is the list of currently saved chatlogs in your web browser's storage."
+                        }
+                        label(class = "config-checkbox-label") {
+                            input(r#type="checkbox", bind:checked=autosave_to_slot)
+                            "Auto-save changes into the open chatlog, without asking"
                         }
                     }
                     div(class="flex-none ml-2") {
@@ -436,6 +718,40 @@ pub fn SavingInterface() -> View {
                     (entries_components)
                 }
 
+                ChatlogSearchComponent()
+
+                StorageUsageComponent()
+
+                div(class="config-container mt-4") {
+                    div(class="mb-4") {
+                        h3(class="save-slot-header") {
+                            "Usage"
+                        }
+                        p(class="save-slot-header-secondary") {
+                            "Tokens used and the estimated cost based on the model prices in the API configuration."
+                        }
+                    }
+
+                    p(class="mb-4") {
+                        (format!("Current chatlog: {}", active_usage.get().describe()))
+                    }
+
+                    table(class="w-full text-sm") {
+                        thead {
+                            tr {
+                                th(class="usage-cell text-left") { "Month" }
+                                th(class="usage-cell text-left") { "Endpoint" }
+                                th(class="usage-cell text-right") { "Requests" }
+                                th(class="usage-cell text-right") { "Tokens" }
+                                th(class="usage-cell text-right") { "Cost" }
+                            }
+                        }
+                        tbody {
+                            (usage_rows)
+                        }
+                    }
+                }
+
                 div(class="config-container mt-4") {
                     div(class="mb-4") {
                         h3(class="save-slot-header") {
@@ -0,0 +1,73 @@
+use sycamore::prelude::*;
+
+use crate::models::config::ApiEndpointConfig;
+
+/// The settings for the text-to-speech endpoint used to read messages aloud. Changes are
+/// written straight into the API configuration context.
+#[component(inline_props)]
+pub fn SpeechConfigComponent() -> View {
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+
+    let speech = config_context_signal.get_clone_untracked().speech;
+    let endpoint = create_signal(speech.endpoint);
+    let api_key = create_signal(speech.api_key);
+    let model_id = create_signal(speech.model_id);
+    let voice = create_signal(speech.voice);
+    let auto_read = create_signal(speech.auto_read);
+
+    // writes the inputs back into the configuration whenever any of them change
+    create_effect(move || {
+        let new_endpoint = endpoint.get_clone();
+        let new_api_key = api_key.get_clone();
+        let new_model_id = model_id.get_clone();
+        let new_voice = voice.get_clone();
+        let new_auto_read = auto_read.get();
+
+        let mut config = config_context_signal.get_clone_untracked();
+        config.speech.endpoint = new_endpoint;
+        config.speech.api_key = new_api_key;
+        config.speech.model_id = new_model_id;
+        config.speech.voice = new_voice;
+        config.speech.auto_read = new_auto_read;
+        if config != config_context_signal.get_clone_untracked() {
+            config_context_signal.set(config);
+        }
+    });
+
+    view! {
+        div(class="config-container") {
+            div(class = "mb-4") {
+                h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                    "Read Aloud"
+                }
+                p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                    "Messages are read aloud with the `/audio/speech` endpoint of an OpenAI compatible API. Only the main content is read, without the thought process or any markdown formatting. Leave the endpoint and key empty to use the ones above."
+                }
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Speech Endpoint:" }
+                input(class="config-textinput", bind:value=endpoint, r#type="text",
+                    placeholder="same as the API endpoint")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Speech API Key:" }
+                input(class="config-textinput", bind:value=api_key, r#type="text",
+                    placeholder="same as the API key")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Speech Model ID:" }
+                input(class="config-textinput", bind:value=model_id, r#type="text")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Voice:" }
+                input(class="config-textinput", bind:value=voice, r#type="text")
+            }
+
+            label(class = "config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=auto_read)
+                "Read every new response aloud"
+            }
+        }
+    }
+}
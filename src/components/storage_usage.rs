@@ -0,0 +1,387 @@
+use sycamore::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::js_sys::Array;
+use web_sys::wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+use crate::{
+    image_processing::format_bytes,
+    models::{
+        chatlog::{ChatLogMetadata, ChatLogMetadataEntry},
+        linked_chatlog::LinkedChatlog,
+        notifications::{ConfirmationDialog, Notifications},
+        search_index::ChatlogIndex,
+    },
+    storage::{
+        AppStorage, Storage, StorageError, chatlogs,
+        meter::{self, ChatlogFootprint, QuotaEstimate},
+    },
+};
+
+// the meter turns red once this much of the quota is used
+const QUOTA_WARNING_PERCENT: f64 = 90.0;
+
+// tells what went wrong with the storage and, once it's full, how to make room.
+pub(crate) fn describe_storage_error(error: &StorageError) -> String {
+    match error {
+        StorageError::QuotaExceeded => format!(
+            "{}. Free up space in the Storage section of the chatlogs by removing the images \
+             of old chatlogs or exporting and deleting them",
+            error
+        ),
+        StorageError::Failed(_) => error.to_string(),
+    }
+}
+
+// A stored chatlog and how much room it takes. The current chatlog has no entry.
+#[derive(Clone, PartialEq)]
+struct StoredChatlog {
+    entry: Option<ChatLogMetadataEntry>,
+    footprint: ChatlogFootprint,
+}
+
+// measures the current chatlog and every saved one along with its search index,
+// biggest first.
+async fn measure_chatlogs(
+    storage: &AppStorage,
+    entries: Vec<ChatLogMetadataEntry>,
+) -> Vec<StoredChatlog> {
+    let mut measured = Vec::new();
+    if let Some(json) = storage.load(crate::LSKEY_CURRENTLOG).await {
+        measured.push(StoredChatlog {
+            entry: None,
+            footprint: meter::measure_chatlog(&json),
+        });
+    }
+    for entry in entries {
+        let Some(json) = storage.load(&entry.storage_key).await else {
+            continue;
+        };
+        let mut footprint = meter::measure_chatlog(&json);
+        footprint.index_bytes = storage
+            .load(&ChatlogIndex::storage_key(&entry.id))
+            .await
+            .map_or(0, |index| index.len());
+        measured.push(StoredChatlog {
+            footprint,
+            entry: Some(entry),
+        });
+    }
+    measured.sort_by_key(|c| std::cmp::Reverse(c.footprint.total()));
+    measured
+}
+
+// starts a download of the JSON as a file. the object URL is released a little later,
+// once the browser has picked up the download.
+fn download_json(file_name: &str, json: &str) -> Result<(), JsValue> {
+    let options = BlobPropertyBag::new();
+    options.set_type("application/json");
+    let blob =
+        Blob::new_with_str_sequence_and_options(&Array::of1(&JsValue::from_str(json)), &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window"))?;
+    let anchor: HtmlAnchorElement = window
+        .document()
+        .ok_or_else(|| JsValue::from_str("no document"))?
+        .create_element("a")?
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    let revoke = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&url);
+    });
+    window.set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 1000)?;
+    Ok(())
+}
+
+// turns the title of a chatlog into a file name that's safe on every system.
+fn export_file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim();
+    format!("{}.json", if name.is_empty() { "chatlog" } else { name })
+}
+
+/// Shows how much of the browser's storage is used, the room every chatlog and its images
+/// take, and ways to free some up: removing the images of a chatlog, or downloading it as
+/// a file and deleting it from the browser.
+#[component]
+pub fn StorageUsageComponent() -> View {
+    let storage = use_context::<AppStorage>();
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let notifications = use_context::<Notifications>();
+    let confirmation = use_context::<ConfirmationDialog>();
+    let linked_chatlog = use_context::<LinkedChatlog>();
+
+    let chatlogs = create_signal(Vec::<StoredChatlog>::new());
+    let estimate = create_signal(None::<QuotaEstimate>);
+    let is_busy = create_signal(false);
+
+    let refresh = {
+        let storage = storage.clone();
+        move || {
+            if is_busy.get_untracked() {
+                return;
+            }
+            is_busy.set(true);
+            let storage = storage.clone();
+            let entries = metadata.get_clone_untracked().saved_logs;
+            spawn_local(async move {
+                chatlogs.set(measure_chatlogs(&storage, entries).await);
+                estimate.set(meter::estimate_quota().await);
+                is_busy.set(false);
+            });
+        }
+    };
+    refresh();
+
+    // removes the images of a saved chatlog, keeping its messages.
+    let strip_images = {
+        let storage = storage.clone();
+        let refresh = refresh.clone();
+        move |entry: ChatLogMetadataEntry, image_count: usize| {
+            let storage = storage.clone();
+            let refresh = refresh.clone();
+            confirmation.confirm(
+                format!(
+                    "Remove the {} images from '{}'? The messages are kept, but the images can't be brought back.",
+                    image_count, entry.title
+                ),
+                "Remove Images",
+                move || {
+                    let storage = storage.clone();
+                    let refresh = refresh.clone();
+                    let entry = entry.clone();
+                    spawn_local(async move {
+                        let Some(json) = storage.load(&entry.storage_key).await else {
+                            notifications
+                                .error(format!("Unable to load the chatlog '{}'.", entry.title));
+                            return;
+                        };
+                        let result = match meter::strip_images(&json) {
+                            Ok((stripped, removed)) => storage
+                                .save(&entry.storage_key, &stripped)
+                                .await
+                                .map(|_| removed)
+                                .map_err(|e| describe_storage_error(&e)),
+                            Err(e) => Err(e),
+                        };
+                        match result {
+                            Ok(removed) => {
+                                notifications.success(format!(
+                                    "Removed {} images from '{}'.",
+                                    removed, entry.title
+                                ));
+                                refresh();
+                            }
+                            Err(e) => notifications.error(format!(
+                                "Unable to remove the images from '{}': {}",
+                                entry.title, e
+                            )),
+                        }
+                    });
+                },
+            );
+        }
+    };
+
+    // downloads a saved chatlog as a JSON file, then deletes it from the browser.
+    let export_and_delete = {
+        let storage = storage.clone();
+        let refresh = refresh.clone();
+        move |entry: ChatLogMetadataEntry| {
+            let storage = storage.clone();
+            let refresh = refresh.clone();
+            confirmation.confirm(
+                format!(
+                    "Download '{}' as a JSON file and delete it from the browser? The file can be imported again later.",
+                    entry.title
+                ),
+                "Export & Delete",
+                move || {
+                    let storage = storage.clone();
+                    let refresh = refresh.clone();
+                    let entry = entry.clone();
+                    spawn_local(async move {
+                        let Some(json) = storage.load(&entry.storage_key).await else {
+                            notifications
+                                .error(format!("Unable to load the chatlog '{}'.", entry.title));
+                            return;
+                        };
+                        // nothing is deleted unless the download got started
+                        if let Err(e) = download_json(&export_file_name(&entry.title), &json) {
+                            console_log!("Failed to download the chatlog: {:?}", e);
+                            notifications.error(format!(
+                                "Unable to export '{}', so it was kept.",
+                                entry.title
+                            ));
+                            return;
+                        }
+                        match chatlogs::delete_chatlog(&storage, &entry).await {
+                            Ok(()) => {
+                                metadata.update(|meta| meta.remove_entry(&entry.id));
+                                linked_chatlog.unlink_from(&entry.id);
+                                notifications.success(format!(
+                                    "Exported and deleted '{}'.",
+                                    entry.title
+                                ));
+                                refresh();
+                            }
+                            Err(e) => notifications.error(format!(
+                                "'{}' was exported but couldn't be deleted: {}",
+                                entry.title, e
+                            )),
+                        }
+                    });
+                },
+            );
+        }
+    };
+
+    let quota_meter = move || match estimate.get() {
+        Some(QuotaEstimate { usage, quota }) if quota > 0.0 => {
+            let percent = (usage / quota * 100.0).clamp(0.0, 100.0);
+            let fill_class = if percent >= QUOTA_WARNING_PERCENT {
+                "storage-meter-fill storage-meter-fill-warning"
+            } else {
+                "storage-meter-fill"
+            };
+            view! {
+                div(class="storage-meter") {
+                    div(class=fill_class, style=format!("width: {:.1}%", percent)) {}
+                }
+                p(class="save-slot-header-secondary mt-1") {
+                    (format!(
+                        "{} of about {} used ({:.0}%)",
+                        format_bytes(usage),
+                        format_bytes(quota),
+                        percent
+                    ))
+                }
+            }
+        }
+        _ => view! {
+            p(class="save-slot-header-secondary") {
+                "The browser doesn't tell how much storage is available."
+            }
+        },
+    };
+
+    let rows = move || {
+        chatlogs
+            .get_clone()
+            .into_iter()
+            .map(|chatlog| {
+                let footprint = chatlog.footprint;
+                let image_count = footprint.image_bytes.len();
+                let images = if image_count == 0 {
+                    "none".to_string()
+                } else {
+                    format!(
+                        "{} ({})",
+                        image_count,
+                        format_bytes(footprint.images_total() as f64)
+                    )
+                };
+                let index = if footprint.index_bytes == 0 {
+                    "none".to_string()
+                } else {
+                    format_bytes(footprint.index_bytes as f64)
+                };
+                let image_sizes = footprint
+                    .image_bytes
+                    .iter()
+                    .map(|bytes| format_bytes(*bytes as f64))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let title = chatlog
+                    .entry
+                    .as_ref()
+                    .map_or("Current chatlog".to_string(), |e| e.title.clone());
+                let actions = match chatlog.entry {
+                    Some(entry) => {
+                        let strip_images = strip_images.clone();
+                        let export_and_delete = export_and_delete.clone();
+                        let strip_entry = entry.clone();
+                        view! {
+                            button(
+                                class="save-slot-button mr-1",
+                                disabled=image_count == 0,
+                                on:click=move |_| strip_images(strip_entry.clone(), image_count),
+                            ) { "Remove Images" }
+                            button(
+                                class="save-slot-button",
+                                on:click=move |_| export_and_delete(entry.clone()),
+                            ) { "Export & Delete" }
+                        }
+                    }
+                    None => view! {},
+                };
+                view! {
+                    tr {
+                        td(class="usage-cell") { (title) }
+                        td(class="usage-cell text-right") { (format_bytes(footprint.text_bytes as f64)) }
+                        td(class="usage-cell text-right", title=image_sizes) { (images) }
+                        td(class="usage-cell text-right") { (index) }
+                        td(class="usage-cell text-right") { (format_bytes(footprint.total() as f64)) }
+                        td(class="usage-cell text-right whitespace-nowrap") { (actions) }
+                    }
+                }
+            })
+            .collect::<Vec<View>>()
+    };
+
+    view! {
+        div(class="config-container mt-4") {
+            div(class="mb-4") {
+                h3(class="save-slot-header") {
+                    "Storage"
+                }
+                p(class="save-slot-header-secondary") {
+                    "The room the chatlogs and their search indexes take in the browser. Once it runs out, chatlogs can't be saved anymore; removing the images of old chatlogs, or exporting and deleting them, frees some up."
+                }
+            }
+
+            div(class="mb-4") {
+                (quota_meter)
+            }
+
+            table(class="w-full text-sm") {
+                thead {
+                    tr {
+                        th(class="usage-cell text-left") { "Chatlog" }
+                        th(class="usage-cell text-right") { "Text" }
+                        th(class="usage-cell text-right") { "Images" }
+                        th(class="usage-cell text-right") { "Search Index" }
+                        th(class="usage-cell text-right") { "Total" }
+                        th(class="usage-cell") {}
+                    }
+                }
+                tbody {
+                    (rows)
+                }
+            }
+
+            div(class="flex justify-center mt-2") {
+                button(
+                    on:click=move |_| refresh(),
+                    class="save-slot-button",
+                    disabled=is_busy.get(),
+                ) {
+                    (if is_busy.get() { "Measuring..." } else { "Refresh" })
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use sycamore::prelude::*;
+
+use crate::models::config::ApiEndpointConfig;
+
+/// The settings for the speech-to-text endpoint used by the microphone button in the chat
+/// input. Changes are written straight into the API configuration context.
+#[component(inline_props)]
+pub fn TranscriptionConfigComponent() -> View {
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+
+    let transcription = config_context_signal.get_clone_untracked().transcription;
+    let endpoint = create_signal(transcription.endpoint);
+    let api_key = create_signal(transcription.api_key);
+    let model_id = create_signal(transcription.model_id);
+    let language = create_signal(transcription.language.unwrap_or_default());
+    let auto_send = create_signal(transcription.auto_send);
+
+    // writes the inputs back into the configuration whenever any of them change
+    create_effect(move || {
+        let new_endpoint = endpoint.get_clone();
+        let new_api_key = api_key.get_clone();
+        let new_model_id = model_id.get_clone();
+        let new_language = language.get_clone();
+        let new_auto_send = auto_send.get();
+
+        let mut config = config_context_signal.get_clone_untracked();
+        config.transcription.endpoint = new_endpoint;
+        config.transcription.api_key = new_api_key;
+        config.transcription.model_id = new_model_id;
+        config.transcription.language = if new_language.trim().is_empty() {
+            None
+        } else {
+            Some(new_language.trim().to_string())
+        };
+        config.transcription.auto_send = new_auto_send;
+        if config != config_context_signal.get_clone_untracked() {
+            config_context_signal.set(config);
+        }
+    });
+
+    view! {
+        div(class="config-container") {
+            div(class = "mb-4") {
+                h3(class = "text-lg font-semibold text-primary-text dark:text-primary-text-dark mb-2") {
+                    "Voice Input"
+                }
+                p(class = "text-sm text-secondary-text dark:text-secondary-text-dark") {
+                    "Recordings from the microphone button are sent to the `/audio/transcriptions` endpoint of an OpenAI compatible API, such as a whisper.cpp server. Leave the endpoint and key empty to use the ones above."
+                }
+            }
+
+            div(class="config-group") {
+                span(class="config-label") { "Transcription Endpoint:" }
+                input(class="config-textinput", bind:value=endpoint, r#type="text",
+                    placeholder="same as the API endpoint")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Transcription API Key:" }
+                input(class="config-textinput", bind:value=api_key, r#type="text",
+                    placeholder="same as the API key")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Transcription Model ID:" }
+                input(class="config-textinput", bind:value=model_id, r#type="text")
+            }
+            div(class="config-group") {
+                span(class="config-label") { "Language:" }
+                input(class="config-textinput", bind:value=language, r#type="text",
+                    placeholder="detect automatically")
+            }
+
+            label(class = "config-checkbox-label") {
+                input(r#type="checkbox", bind:checked=auto_send)
+                "Send the message as soon as it's transcribed"
+            }
+        }
+    }
+}
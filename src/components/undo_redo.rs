@@ -0,0 +1,78 @@
+use sycamore::prelude::*;
+use web_sys::wasm_bindgen::{JsCast, closure::Closure};
+use web_sys::{HtmlElement, KeyboardEvent};
+
+use crate::models::{chatlog::Chatlog, is_response_pending::IsResponsePending};
+
+// returns true if the key event goes to a text field, which has its own undo and redo.
+fn is_typing(event: &KeyboardEvent) -> bool {
+    event
+        .target()
+        .and_then(|target| target.dyn_into::<HtmlElement>().ok())
+        .is_some_and(|element| {
+            matches!(element.tag_name().as_str(), "INPUT" | "TEXTAREA")
+                || element.is_content_editable()
+        })
+}
+
+/// Buttons to undo and redo the changes of the messages in the active chatlog, which are
+/// also bound to Ctrl+Z and Ctrl+Shift+Z or Ctrl+Y while no text field has the focus.
+/// Nothing can be undone while a response is pending.
+#[component(inline_props)]
+pub fn UndoRedoComponent() -> View {
+    let active_chatlog = use_context::<Signal<Chatlog>>();
+    let is_response_pending = use_context::<IsResponsePending>().signal();
+    let history = active_chatlog.get_clone_untracked().history;
+
+    let can_undo = move || !is_response_pending.get() && history.with(|h| h.can_undo());
+    let can_redo = move || !is_response_pending.get() && history.with(|h| h.can_redo());
+    let undo = move || {
+        if !is_response_pending.get_untracked() {
+            active_chatlog.get_clone_untracked().undo();
+        }
+    };
+    let redo = move || {
+        if !is_response_pending.get_untracked() {
+            active_chatlog.get_clone_untracked().redo();
+        }
+    };
+
+    // the shortcuts work anywhere on the page, so they're listened for on the window
+    let on_keydown = Closure::<dyn Fn(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+        if !(event.ctrl_key() || event.meta_key()) || is_typing(&event) {
+            return;
+        }
+        match (event.key().to_lowercase().as_str(), event.shift_key()) {
+            ("z", false) => undo(),
+            ("z", true) | ("y", false) => redo(),
+            _ => return,
+        }
+        event.prevent_default();
+    });
+    if let Err(e) =
+        window().add_event_listener_with_callback("keydown", on_keydown.as_ref().unchecked_ref())
+    {
+        console_log!("Failed to listen for the undo shortcuts: {:?}", e);
+    }
+    on_cleanup(move || {
+        let _ = window()
+            .remove_event_listener_with_callback("keydown", on_keydown.as_ref().unchecked_ref());
+    });
+
+    view! {
+        div(class="undo-bar") {
+            button(
+                class="action-button",
+                title="Undo the last change of the messages (Ctrl+Z)",
+                disabled=!can_undo(),
+                on:click=move |_| undo(),
+            ) { "Undo" }
+            button(
+                class="action-button",
+                title="Redo the last change that was undone (Ctrl+Shift+Z)",
+                disabled=!can_redo(),
+                on:click=move |_| redo(),
+            ) { "Redo" }
+        }
+    }
+}
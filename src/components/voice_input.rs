@@ -0,0 +1,176 @@
+use std::{cell::RefCell, rc::Rc};
+
+use sycamore::prelude::*;
+use web_sys::js_sys::{Array, Function};
+use web_sys::wasm_bindgen::{JsCast, JsValue, prelude::Closure};
+use web_sys::{
+    Blob, BlobEvent, BlobPropertyBag, MediaRecorder, MediaStream, MediaStreamConstraints,
+    MediaStreamTrack,
+};
+
+use crate::{
+    api_endpoint,
+    models::{app_scope::AppScope, config::ApiEndpointConfig, notifications::Notifications},
+};
+
+// asks the browser for access to the microphone
+async fn open_microphone() -> Result<MediaStream, JsValue> {
+    let media_devices = web_sys::window()
+        .ok_or_else(|| JsValue::from_str("no global window exists"))?
+        .navigator()
+        .media_devices()?;
+    let constraints = MediaStreamConstraints::new();
+    constraints.set_audio(&JsValue::TRUE);
+    let stream = wasm_bindgen_futures::JsFuture::from(
+        media_devices.get_user_media_with_constraints(&constraints)?,
+    )
+    .await?;
+    Ok(stream.unchecked_into())
+}
+
+// picks a file name the transcription endpoint can guess the audio format from
+fn recording_file_name(mime_type: &str) -> &'static str {
+    if mime_type.contains("ogg") {
+        "recording.ogg"
+    } else if mime_type.contains("mp4") {
+        "recording.mp4"
+    } else {
+        "recording.webm"
+    }
+}
+
+/// A microphone button that records audio while active, has it transcribed by the endpoint
+/// configured for voice input and adds the transcript to `input_text`. If auto-send is
+/// enabled, `on_auto_send` is called once the transcript is in.
+#[component(inline_props)]
+pub fn VoiceInputButton<F: Fn() + Copy + 'static>(
+    input_text: Signal<String>,
+    on_auto_send: F,
+) -> View {
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+    let notifications = use_context::<Notifications>();
+    let app_scope = use_context::<AppScope>();
+
+    let recorder = create_signal(None::<MediaRecorder>);
+    let is_transcribing = create_signal(false);
+
+    // sends the finished recording off to be transcribed
+    let transcribe = move |audio: Blob, file_name: &str| {
+        is_transcribing.set(true);
+        let config = config_context_signal.get_clone_untracked();
+        let auto_send = config.transcription.auto_send;
+        api_endpoint::send_transcription_request(config, audio, file_name, move |result| {
+            is_transcribing.set(false);
+            match result {
+                Ok(transcript) => {
+                    input_text.update(|text| {
+                        if !text.trim().is_empty() {
+                            text.push(' ');
+                        }
+                        text.push_str(&transcript);
+                    });
+                    if auto_send {
+                        app_scope.run_in(on_auto_send);
+                    }
+                }
+                Err(e) => notifications.error(format!("Unable to transcribe the recording: {}", e)),
+            }
+        });
+    };
+
+    // starts recording from the microphone; the audio is collected in chunks until stopped
+    let start_recording = move |stream: MediaStream| -> Result<(), JsValue> {
+        let media_recorder = MediaRecorder::new_with_media_stream(&stream)?;
+        let chunks = Rc::new(RefCell::new(Vec::<Blob>::new()));
+
+        let chunks_clone = chunks.clone();
+        let handle_data_js: Function = Closure::wrap(Box::new(move |event: BlobEvent| {
+            if let Some(data) = event.data() {
+                chunks_clone.borrow_mut().push(data);
+            }
+        }) as Box<dyn FnMut(BlobEvent)>)
+        .into_js_value()
+        .into();
+
+        let media_recorder_clone = media_recorder.clone();
+        let handle_stop_js: Function = Closure::wrap(Box::new(move || {
+            // release the microphone so the browser stops showing it as in use
+            for track in stream.get_tracks().iter() {
+                track.unchecked_into::<MediaStreamTrack>().stop();
+            }
+            recorder.set(None);
+
+            let mime_type = media_recorder_clone.mime_type();
+            let parts = chunks.borrow().iter().collect::<Array>();
+            let options = BlobPropertyBag::new();
+            options.set_type(&mime_type);
+            match Blob::new_with_blob_sequence_and_options(&parts, &options) {
+                Ok(audio) if audio.size() > 0.0 => {
+                    transcribe(audio, recording_file_name(&mime_type))
+                }
+                Ok(_) => notifications.warning("Nothing was recorded."),
+                Err(e) => console_log!("Failed to put the recording together: {:?}", e),
+            }
+        }) as Box<dyn FnMut()>)
+        .into_js_value()
+        .into();
+
+        media_recorder.set_ondataavailable(Some(&handle_data_js));
+        media_recorder.set_onstop(Some(&handle_stop_js));
+        media_recorder.start()?;
+        recorder.set(Some(media_recorder));
+        Ok(())
+    };
+
+    // the button toggles between starting and stopping a recording
+    let handle_click = move |_| {
+        if let Some(media_recorder) = recorder.get_clone_untracked() {
+            if let Err(e) = media_recorder.stop() {
+                console_log!("Failed to stop the recording: {:?}", e);
+            }
+            return;
+        }
+        if is_transcribing.get_untracked() {
+            return;
+        }
+        wasm_bindgen_futures::spawn_local(async move {
+            let started = match open_microphone().await {
+                Ok(stream) => start_recording(stream),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = started {
+                console_log!("Failed to start recording: {:?}", e);
+                notifications.error("Unable to record from the microphone.");
+            }
+        });
+    };
+
+    let get_button_classes = move || {
+        if recorder.with(|r| r.is_some()) {
+            "voice-button voice-button-recording"
+        } else {
+            "voice-button"
+        }
+    };
+    let get_button_title = move || {
+        if recorder.with(|r| r.is_some()) {
+            "Stop recording and transcribe"
+        } else if is_transcribing.get() {
+            "Transcribing..."
+        } else {
+            "Record a voice message"
+        }
+    };
+
+    view! {
+        button(on:click=handle_click, class=get_button_classes, title=get_button_title,
+            disabled=is_transcribing.get()) {
+            svg(width="20", height="20", viewBox="0 0 24 24", fill="none", stroke="currentColor",
+                stroke-width="2", stroke-linecap="round", stroke-linejoin="round", xmlns="http://www.w3.org/2000/svg") {
+                rect(x="9", y="2", width="6", height="12", rx="3")
+                path(d="M5 11a7 7 0 0 0 14 0")
+                path(d="M12 18v4")
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use web_sys::js_sys::Promise;
+use web_sys::wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement};
+
+use crate::models::image_compression::{ImageCompressionSettings, ImageFormat};
+
+// Downscales the image in the data URL to fit the maximum dimensions of the settings and
+// recompresses it in the configured format. The original is returned as it is when
+// compression is disabled, for animated GIFs, and when the result would be larger.
+pub async fn compress_image(
+    data_url: String,
+    settings: &ImageCompressionSettings,
+) -> Result<String, JsValue> {
+    if !settings.enabled || data_url.starts_with("data:image/gif") {
+        return Ok(data_url);
+    }
+
+    let image = load_image(&data_url).await?;
+    let (width, height) = (image.natural_width(), image.natural_height());
+    if width == 0 || height == 0 {
+        return Ok(data_url);
+    }
+    let scale = (settings.max_width as f64 / width as f64)
+        .min(settings.max_height as f64 / height as f64)
+        .min(1.0);
+    let target_width = ((width as f64 * scale).round() as u32).max(1);
+    let target_height = ((height as f64 * scale).round() as u32).max(1);
+
+    let canvas: HtmlCanvasElement = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| JsValue::from_str("no document to draw the image in"))?
+        .create_element("canvas")?
+        .unchecked_into();
+    canvas.set_width(target_width);
+    canvas.set_height(target_height);
+    let context: CanvasRenderingContext2d = canvas
+        .get_context("2d")?
+        .ok_or_else(|| JsValue::from_str("no 2d canvas context"))?
+        .unchecked_into();
+    // JPEG has no transparency, which would otherwise turn transparent areas black
+    if settings.format == ImageFormat::Jpeg {
+        context.set_fill_style_str("white");
+        context.fill_rect(0.0, 0.0, target_width as f64, target_height as f64);
+    }
+    context.draw_image_with_html_image_element_and_dw_and_dh(
+        &image,
+        0.0,
+        0.0,
+        target_width as f64,
+        target_height as f64,
+    )?;
+
+    let compressed = canvas.to_data_url_with_type_and_encoder_options(
+        settings.format.mime_type(),
+        &JsValue::from_f64(settings.quality.clamp(0.0, 1.0)),
+    )?;
+    if scale >= 1.0 && compressed.len() >= data_url.len() {
+        Ok(data_url)
+    } else {
+        Ok(compressed)
+    }
+}
+
+// waits for the browser to decode the image in the data URL
+async fn load_image(data_url: &str) -> Result<HtmlImageElement, JsValue> {
+    let image = HtmlImageElement::new()?;
+    let loaded = Promise::new(&mut |resolve, reject| {
+        image.set_onload(Some(&resolve));
+        image.set_onerror(Some(&reject));
+    });
+    image.set_src(data_url);
+    wasm_bindgen_futures::JsFuture::from(loaded).await?;
+    Ok(image)
+}
+
+// returns the number of bytes encoded in a base64 data URL
+pub fn data_url_size(data_url: &str) -> usize {
+    let payload = data_url.split_once(',').map_or(data_url, |(_, data)| data);
+    let padding = payload.chars().rev().take_while(|c| *c == '=').count();
+    (payload.len() * 3 / 4).saturating_sub(padding)
+}
+
+// formats a number of bytes for display, such as "1.4 MB" or "210 KB"
+pub fn format_bytes(bytes: f64) -> String {
+    if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MB", bytes / (1024.0 * 1024.0))
+    } else {
+        format!("{:.0} KB", (bytes / 1024.0).max(1.0))
+    }
+}
@@ -1,19 +1,54 @@
 use components::{
-    chat_interface::ChatInterface, config_interface::ConfigInterface,
+    chat_interface::ChatInterface,
+    config_interface::ConfigInterface,
+    notifications::{ConfirmationDialogComponent, NotificationsComponent},
     saving_interface::SavingInterface,
+    storage_usage::describe_storage_error,
 };
 use models::{
-    chatlog::Chatlog, config::ApiEndpointConfig, dark_mode::DarkMode,
-    is_editing_config::IsEditingConfig, is_response_pending::IsResponsePending,
-    is_saving_chatlog::IsSavingChatlog, system_message::SystemMessage,
+    app_scope::AppScope,
+    autosave_to_slot::AutosaveToSlot,
+    chatlog::Chatlog,
+    combine_queued_messages::CombineQueuedMessages,
+    config::ApiEndpointConfig,
+    dark_mode::DarkMode,
+    ignore_spending_cap::IgnoreSpendingCap,
+    image_compression::{ImageCompression, ImageCompressionSettings},
+    is_editing_config::IsEditingConfig,
+    is_response_pending::IsResponsePending,
+    is_saving_chatlog::IsSavingChatlog,
+    jump_to_message::JumpToMessage,
+    linked_chatlog::LinkedChatlog,
+    notifications::{ConfirmationDialog, Notifications},
+    request_inspector::RequestInspector,
+    response_error::{ErrorRecovery, FailedResponse, ResponseError},
+    speech_player::SpeechPlayer,
+    system_message::SystemMessage,
+    usage::{SpendingCapStatus, Usage, UsageSummary, current_day, current_month},
 };
 use sycamore::prelude::*;
+use web_sys::wasm_bindgen::{JsCast, closure::Closure};
 
-use crate::models::chatlog::ChatLogMetadata;
+use crate::{
+    api_endpoint::ApiError,
+    models::{
+        chatlog::{ChatLogMetadata, ChatLogMetadataEntry},
+        documents::Citation,
+    },
+    storage::{
+        AppStorage, StorageError, chatlogs,
+        schema::{self, SchemaError, UnreadableRecord},
+    },
+};
 
 pub mod api_endpoint;
 pub mod components;
+pub mod image_processing;
+pub mod mock_provider;
 pub mod models;
+pub mod retrieval;
+pub mod search;
+pub mod speech;
 pub mod storage;
 
 // keys used in local storage
@@ -22,112 +57,656 @@ const LSKEY_SYSMSG: &str = "system_message";
 const LSKEY_CURRENTLOG: &str = "current_chatlog";
 const LSKEY_DARK_MODE: &str = "dark_mode";
 const LSKEY_CHATLOG_METADATA: &str = "chatlog_metadata";
+const LSKEY_USAGE_SUMMARY: &str = "usage_summary";
+const LSKEY_REQUEST_INSPECTOR: &str = "request_inspector";
+const LSKEY_COMBINE_QUEUED: &str = "combine_queued_messages";
+const LSKEY_IMAGE_COMPRESSION: &str = "image_compression";
+const LSKEY_LINKED_CHATLOG: &str = "linked_chatlog";
+const LSKEY_AUTOSAVE_TO_SLOT: &str = "autosave_to_slot";
+
+// how long the active chatlog has to stay unchanged before it's saved
+const AUTOSAVE_DELAY_MS: i32 = 1000;
+
+// Maps an `ApiError` to a message describing what went wrong for the user and the
+// recovery action that is most likely to help.
+fn describe_api_error(err: &ApiError) -> (String, ErrorRecovery) {
+    match err {
+        ApiError::Network(msg) => (
+            format!(
+                "Unable to reach the API endpoint. Check the endpoint URL and your connection.\n\n({})",
+                msg
+            ),
+            ErrorRecovery::Retry,
+        ),
+        ApiError::HttpStatus { status, message } => match status {
+            401 | 403 => (
+                format!(
+                    "The API rejected the request; check that the API key is valid.\n\n({})",
+                    message
+                ),
+                ErrorRecovery::OpenConfig,
+            ),
+            404 => (
+                format!(
+                    "The endpoint or model wasn't found; check the endpoint URL and model ID.\n\n({})",
+                    message
+                ),
+                ErrorRecovery::OpenConfig,
+            ),
+            429 => (
+                format!(
+                    "The API is rate limiting requests or the account is out of credits. Wait a moment before retrying.\n\n({})",
+                    message
+                ),
+                ErrorRecovery::Retry,
+            ),
+            500..=599 => (
+                format!(
+                    "The API provider had an internal error ({}).\n\n({})",
+                    status, message
+                ),
+                ErrorRecovery::Retry,
+            ),
+            _ => (
+                format!("The API request failed ({}).\n\n({})", status, message),
+                ErrorRecovery::OpenConfig,
+            ),
+        },
+        ApiError::ContentFiltered(msg) => (
+            format!(
+                "The provider's content filter blocked the reply. Try rephrasing or editing the last message.\n\n({})",
+                msg
+            ),
+            ErrorRecovery::None,
+        ),
+        ApiError::MalformedResponse(msg) => (
+            format!(
+                "The endpoint's reply couldn't be understood; make sure it is OpenAI compatible.\n\n({})",
+                msg
+            ),
+            ErrorRecovery::OpenConfig,
+        ),
+        ApiError::EmptyContent(msg) => (
+            format!(
+                "The model didn't write a reply. Retrying or raising Max Tokens may help.\n\n({})",
+                msg
+            ),
+            ErrorRecovery::Retry,
+        ),
+        ApiError::ContextLengthExceeded(msg) => (
+            format!(
+                "The conversation is too long for the model. Lower the Target Context Size or purge older messages.\n\n({})",
+                msg
+            ),
+            ErrorRecovery::OpenConfig,
+        ),
+    }
+}
 
 // This function is used as a callback for the Chatlog for when an AI response is
 // requested.
 fn generate_response() {
+    let active_chatlog = use_context::<Signal<Chatlog>>();
+    let log = active_chatlog.get_clone_untracked();
+    let usage_summary = use_context::<Signal<UsageSummary>>();
+    let request_config = use_context::<Signal<ApiEndpointConfig>>().get_clone_untracked();
+
+    // any previous failure is replaced by the outcome of this request
+    let response_error = use_context::<ResponseError>();
+    response_error.clear();
+
+    // nothing is sent once a spending cap of the endpoint is reached, images included
+    let regenerating = log.regenerating_msg.get_untracked();
+    if !check_spending_caps(&request_config, regenerating) {
+        log.regenerating_msg.set(None);
+        return;
+    }
+
+    // images are generated when asked for, and image messages are regenerated as images
+    let is_regenerating_image = regenerating
+        .and_then(|msg_id| log.get_message(msg_id))
+        .is_some_and(|m| {
+            m.ai_generated
+                && m.get_selected_message()
+                    .is_some_and(|s| !s.images.is_empty())
+        });
+    if log.is_generating_image.get_untracked() || is_regenerating_image {
+        generate_image();
+        return;
+    }
+
+    // a regenerated message is generated from the messages before it
+    let Some(msgs) = log.response_context() else {
+        log.regenerating_msg.set(None);
+        use_context::<Notifications>().warning("The message to regenerate is gone.");
+        return;
+    };
+
     let is_response_pending = use_context::<IsResponsePending>();
     is_response_pending.signal().set(true);
+    let notifications = use_context::<Notifications>();
+    let keep_reasoning = log.keep_reasoning.get_untracked();
+    let app_scope = use_context::<AppScope>();
+
+    // the attached documents are searched with the last user message
+    let documents = log.documents.get_clone_untracked();
+    let query = msgs
+        .iter()
+        .rev()
+        .find(|m| !m.ai_generated)
+        .and_then(|m| m.get_selected_message())
+        .map(|m| m.message)
+        .unwrap_or_default();
+    let retrieval_config = request_config.clone();
+
+    let send_request = move |citations: Vec<Citation>| {
+        let document_context = citations.clone();
+        api_endpoint::send_chat_completion_request(
+            msgs,
+            keep_reasoning,
+            &document_context,
+            move |maybe_response: Result<api_endpoint::CompletionResponse, ApiError>| {
+                is_response_pending.signal().set(false);
+                match maybe_response {
+                    Ok(response) => {
+                        //console_log!("main::on_user_send response received: {:?}", response_text);
+
+                        let usage = Usage::from_tokens(
+                            response.prompt_tokens,
+                            response.completion_tokens,
+                            request_config.get_model_price(&request_config.model_id),
+                        );
+                        let mut log = active_chatlog.get_clone();
+                        record_usage(usage_summary, &log, &request_config, &usage);
+                        let response_text = response.text.clone();
+                        let msg_id = add_response(
+                            &mut log,
+                            notifications,
+                            regenerating,
+                            response.text,
+                            Vec::new(),
+                        );
+
+                        // remember which document chunks the response was based on
+                        if let (false, Some(msg_id)) = (citations.is_empty(), msg_id) {
+                            log.set_citations(msg_id, citations);
+                        }
+
+                        // read the new response aloud if the user asked for every one to be
+                        if let (true, Some(msg_id)) = (request_config.speech.auto_read, msg_id) {
+                            app_scope.run_in(|| speech::read_message_aloud(msg_id, response_text));
+                        }
+
+                        // anything typed while waiting for this response goes out next
+                        app_scope.run_in(dispatch_queued_messages);
+                    }
+                    Err(e) => {
+                        // clear the regeneration flag on error too
+                        if regenerating.is_some() {
+                            active_chatlog.update(|log| {
+                                log.regenerating_msg.set(None);
+                            });
+                        }
+
+                        let (message, recovery) = describe_api_error(&e);
+                        response_error.signal().set(Some(FailedResponse {
+                            message,
+                            recovery,
+                            regenerating,
+                        }));
+                    }
+                };
+            },
+        );
+    };
+
+    if documents.is_empty() {
+        send_request(Vec::new());
+    } else {
+        let token_budget = api_endpoint::document_context_budget(&retrieval_config);
+        wasm_bindgen_futures::spawn_local(async move {
+            let citations = retrieval::retrieve_document_context(
+                &retrieval_config,
+                &documents,
+                &query,
+                token_budget,
+            )
+            .await;
+            app_scope.run_in(move || send_request(citations));
+        });
+    }
+}
+
+// Checks the spending caps of the endpoint before a request is sent. A reached cap is
+// reported as a failed response that can be sent anyway, unless the user already chose
+// to; returns whether the request may be sent.
+fn check_spending_caps(request_config: &ApiEndpointConfig, regenerating: Option<u32>) -> bool {
+    let ignore_cap = use_context::<IgnoreSpendingCap>().take();
+    let cap_status = use_context::<Signal<UsageSummary>>().with_untracked(|summary| {
+        summary.check_spending_caps(request_config, &current_day(), &current_month())
+    });
+    match cap_status {
+        SpendingCapStatus::Exceeded(message) if !ignore_cap => {
+            use_context::<ResponseError>()
+                .signal()
+                .set(Some(FailedResponse {
+                    message,
+                    recovery: ErrorRecovery::IgnoreCap,
+                    regenerating,
+                }));
+            false
+        }
+        SpendingCapStatus::Warning(message) => {
+            use_context::<Notifications>().warning(message);
+            true
+        }
+        _ => true,
+    }
+}
+
+// Adds a response to the chatlog: as a new variant of the message that was regenerated,
+// whose following messages stay on the branch of the variant they followed, or as a new
+// message. If the regenerated message is gone, the response is added as a new message
+// rather than lost. Returns the id of the message the response was added to.
+fn add_response(
+    log: &mut Chatlog,
+    notifications: Notifications,
+    regenerating: Option<u32>,
+    text: String,
+    images: Vec<String>,
+) -> Option<u32> {
+    if let Some(msg_id) = regenerating {
+        log.regenerating_msg.set(None);
+        if log.push_to_message_stack(msg_id, text.clone(), images.clone()) {
+            return Some(msg_id);
+        }
+        notifications.warning(
+            "The regenerated message is gone, so the response was added as a new message.",
+        );
+    }
+    log.add_message(text, true, images);
+    log.messages.with_untracked(|m| m.last().map(|m| m.id))
+}
+
+// Tracks the tokens and cost of a request for the chatlog and the monthly summary of
+// the endpoint.
+fn record_usage(
+    usage_summary: Signal<UsageSummary>,
+    log: &Chatlog,
+    request_config: &ApiEndpointConfig,
+    usage: &Usage,
+) {
+    usage_summary.update(|summary| {
+        summary.record(
+            &current_day(),
+            &current_month(),
+            &request_config.endpoint,
+            &request_config.name,
+            usage,
+        )
+    });
+    log.usage.update(|total| total.add(usage));
+}
 
+// Generates an image from the last user message instead of a text reply. When an image
+// message is regenerated, the new image is added to its message stack as a variant.
+fn generate_image() {
+    let response_error = use_context::<ResponseError>();
+    let usage_summary = use_context::<Signal<UsageSummary>>();
     let active_chatlog = use_context::<Signal<Chatlog>>();
     let log = active_chatlog.get_clone_untracked();
-    let msgs = log.messages.get_clone_untracked();
+    let request_config = use_context::<Signal<ApiEndpointConfig>>().get_clone_untracked();
+    let regenerated_id = log.regenerating_msg.get_untracked();
 
-    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
-    let system_message_context = use_context::<SystemMessage>();
+    // the prompt is the last user message, before the image message being regenerated
+    let prompt = log
+        .response_context()
+        .unwrap_or_default()
+        .iter()
+        .rev()
+        .find(|m| !m.ai_generated)
+        .and_then(|m| m.get_selected_message())
+        .map(|m| m.message.trim().to_string())
+        .filter(|prompt| !prompt.is_empty());
+    let Some(prompt) = prompt else {
+        log.regenerating_msg.set(None);
+        log.is_generating_image.set(false);
+        use_context::<Notifications>().warning("There's no prompt to generate an image from.");
+        return;
+    };
 
-    let is_regenerating = log.is_regenerating_msg.get_untracked();
-    let last_message_id = msgs.last().map(|m| m.id);
-
-    api_endpoint::send_chat_completion_request(msgs, is_regenerating, move |maybe_response| {
-        is_response_pending.signal().set(false);
-        match maybe_response {
-            Ok(response) => {
-                //console_log!("main::on_user_send response received: {:?}", response_text);
-
-                let mut log = active_chatlog.get_clone();
-                if is_regenerating && last_message_id.is_some() {
-                    // if we are regenerating a message, then the completion gets added
-                    // to the existing message stack.
-                    log.push_to_message_stack(last_message_id.unwrap(), response.text, None);
-                    log.is_regenerating_msg.set(false);
-                } else {
-                    log.add_message(response.text, true, None);
+    let is_response_pending = use_context::<IsResponsePending>();
+    is_response_pending.signal().set(true);
+    let notifications = use_context::<Notifications>();
+    let app_scope = use_context::<AppScope>();
+
+    let price = request_config
+        .get_model_price(&request_config.image_generation.model_id)
+        .cloned();
+    api_endpoint::send_image_generation_request(
+        request_config.clone(),
+        prompt.clone(),
+        move |maybe_image| {
+            is_response_pending.signal().set(false);
+            let mut log = active_chatlog.get_clone();
+            match maybe_image {
+                Ok(image) => {
+                    let usage = Usage::from_tokens(
+                        image.prompt_tokens,
+                        image.completion_tokens,
+                        price.as_ref(),
+                    );
+                    record_usage(usage_summary, &log, &request_config, &usage);
+                    let text = format!(
+                        "*Generated image for:* {}",
+                        image.revised_prompt.unwrap_or(prompt)
+                    );
+                    add_response(
+                        &mut log,
+                        notifications,
+                        regenerated_id,
+                        text,
+                        vec![image.image_url],
+                    );
+                    log.is_generating_image.set(false);
+                    app_scope.run_in(dispatch_queued_messages);
                 }
+                Err(e) => {
+                    // the image flag is kept so that retrying generates the image again
+                    log.regenerating_msg.set(None);
 
-                // save the active chatlog into a separate local storage key so that
-                // current progress is always saved.
-                let chatlog_json = active_chatlog.get_clone_untracked().to_json(
-                    config_context_signal.get_clone(),
-                    system_message_context.signal().get_clone(),
-                );
-                if let Ok(json) = chatlog_json {
-                    if let Err(e) =
-                        storage::save_to_local_storage::<String>(LSKEY_CURRENTLOG, &json)
-                    {
-                        console_log!(
-                            "ERROR: attempt to save_to_local_storage for current log failed: {:?}",
-                            e
-                        );
-                    }
-                } else {
-                    console_log!("Failed to serialize the current chatlog to JSON.");
+                    let (message, recovery) = describe_api_error(&e);
+                    response_error.signal().set(Some(FailedResponse {
+                        message,
+                        recovery,
+                        regenerating: regenerated_id,
+                    }));
                 }
             }
-            Err(e) => {
-                // clear the regeneration flag on error too
-                if is_regenerating {
-                    active_chatlog.update(|log| {
-                        log.is_regenerating_msg.set(false);
-                    });
-                }
+        },
+    );
+}
+
+// returns the saved chatlog that auto-saving overwrites along with the active one: the
+// chatlog it's linked to, as long as auto-saving to it is on and it wasn't deleted.
+fn resolve_autosave_slot(
+    autosave: bool,
+    linked: Option<String>,
+    metadata: &ChatLogMetadata,
+) -> Option<ChatLogMetadataEntry> {
+    let id = linked.filter(|_| autosave)?;
+    metadata.saved_logs.iter().find(|e| e.id == id).cloned()
+}
 
-                let _ = window().alert_with_message(
-                    format!("ERROR: Failed to generate the AI's response:\n\n{}", e).as_str(),
-                );
+// Saves the active chatlog into a separate storage key so that current
+// progress is always saved. With auto-saving to the slot turned on, the saved chatlog
+// it's linked to is overwritten as well; its search index is only refreshed when it's
+// saved by hand, since indexing calls the embeddings endpoint. A failed save is only
+// reported once until a save goes through again, so a full storage doesn't raise a
+// notification on every change.
+fn save_current_chatlog(failure_reported: Signal<bool>) {
+    let active_chatlog = use_context::<Signal<Chatlog>>();
+    let config_context_signal = use_context::<Signal<ApiEndpointConfig>>();
+    let system_message_context = use_context::<SystemMessage>();
+    let metadata = use_context::<Signal<ChatLogMetadata>>();
+    let storage = use_context::<AppStorage>();
+    let notifications = use_context::<Notifications>();
+    let log = active_chatlog.get_clone_untracked();
+    let Ok(json) = log.to_json(
+        config_context_signal.get_clone_untracked(),
+        system_message_context.signal().get_clone_untracked(),
+    ) else {
+        console_log!("Failed to serialize the current chatlog to JSON.");
+        return;
+    };
+
+    let slot = metadata.with_untracked(|meta| {
+        resolve_autosave_slot(
+            use_context::<AutosaveToSlot>().signal().get_untracked(),
+            use_context::<LinkedChatlog>()
+                .signal()
+                .get_clone_untracked(),
+            meta,
+        )
+    });
+    let message_count = log.messages.with_untracked(Vec::len);
+    let usage = log.usage.get_untracked();
+
+    let report_failure = move |text: String| {
+        if !failure_reported.replace(true) {
+            notifications.error(text);
+        }
+    };
+    wasm_bindgen_futures::spawn_local(async move {
+        // failing to autosave loses work if the page closes, so the user is told
+        if let Err(e) = chatlogs::save_current_chatlog(&storage, &json).await {
+            report_failure(format!(
+                "The current chatlog couldn't be saved: {}",
+                describe_storage_error(&e)
+            ));
+            return;
+        }
+        if let Some(entry) = slot {
+            if let Err(e) = chatlogs::save_chatlog(&storage, &entry, &json).await {
+                report_failure(format!(
+                    "The chatlog '{}' couldn't be auto-saved: {}",
+                    entry.title,
+                    describe_storage_error(&e)
+                ));
+                return;
             }
-        };
+            metadata.update(|meta| {
+                meta.touch_entry(
+                    &entry.id,
+                    message_count,
+                    usage,
+                    web_sys::js_sys::Date::now().round() as i64,
+                )
+            });
+        }
+        failure_reported.set(false);
     });
 }
 
-/// A component that renders the application.
-#[component]
-fn MainComponent() -> View {
+// The save of the active chatlog that's waiting for it to stop changing.
+#[derive(Clone, Copy)]
+struct Autosave {
+    timer: Signal<Option<i32>>,
+    failure_reported: Signal<bool>,
+}
+
+impl Autosave {
+    fn new() -> Self {
+        Self {
+            timer: create_signal(None),
+            failure_reported: create_signal(false),
+        }
+    }
+
+    // (re)starts the timer that saves the active chatlog once it stops changing.
+    fn schedule(self) {
+        let window = window();
+        if let Some(handle) = self.timer.get_untracked() {
+            window.clear_timeout_with_handle(handle);
+        }
+        let app_scope = use_context::<AppScope>();
+        let on_timeout = Closure::once_into_js(move || {
+            self.timer.set(None);
+            app_scope.run_in(|| save_current_chatlog(self.failure_reported));
+        });
+        match window.set_timeout_with_callback_and_timeout_and_arguments_0(
+            on_timeout.unchecked_ref(),
+            AUTOSAVE_DELAY_MS,
+        ) {
+            Ok(handle) => self.timer.set(Some(handle)),
+            Err(e) => console_log!("Failed to schedule saving the current chatlog: {:?}", e),
+        }
+    }
+
+    // saves the active chatlog right away if a save is waiting on the timer.
+    fn flush(self) {
+        if let Some(handle) = self.timer.get_untracked() {
+            window().clear_timeout_with_handle(handle);
+            self.timer.set(None);
+            save_current_chatlog(self.failure_reported);
+        }
+    }
+}
+
+// Tells the user that a stored record couldn't be read and where a copy of it was kept.
+// Without a copy, the record is left in place until it's changed.
+fn report_unreadable(what: &str, record: &UnreadableRecord) {
+    let notifications = use_context::<Notifications>();
+    let kept = match (&record.copy_key, &record.error) {
+        (Some(key), SchemaError::TooNew { .. }) => {
+            format!(
+                "A copy was kept under '{}' for when the app is updated.",
+                key
+            )
+        }
+        (Some(key), SchemaError::Invalid(_)) => format!("A copy was kept under '{}'.", key),
+        (None, _) => "It couldn't be copied aside, so it's kept until it's changed.".to_string(),
+    };
+    notifications.error(format!(
+        "{} couldn't be read: {}. {}",
+        what, record.error, kept
+    ));
+}
+
+// Loads the API settings from the local storage, upgrading them from an older format, or
+// returns the defaults if none were saved yet. Settings that can't be read are copied
+// aside first, since the app saves its own over them once it runs.
+fn load_api_config() -> Result<ApiEndpointConfig, UnreadableRecord> {
+    let Some(text) = storage::load_text_from_local_storage(LSKEY_API_CONFIG) else {
+        return Ok(ApiEndpointConfig::default());
+    };
+    let config = serde_json::from_str(&text)
+        .map_err(SchemaError::from)
+        .and_then(schema::upgrade_config)
+        .and_then(|upgraded| Ok(serde_json::from_value(upgraded)?));
+    config.map_err(|error| {
+        let copy_key = schema::set_aside_key(LSKEY_API_CONFIG, &error);
+        match storage::save_text_to_local_storage(&copy_key, &text) {
+            Ok(()) => UnreadableRecord {
+                error,
+                copy_key: Some(copy_key),
+            },
+            Err(e) => {
+                console_log!("Failed to set aside the API settings: {}", e);
+                UnreadableRecord {
+                    error,
+                    copy_key: None,
+                }
+            }
+        }
+    })
+}
+
+// Moves the messages queued while a response was pending into the chatlog and
+// requests the reply to them.
+fn dispatch_queued_messages() {
+    let combine = use_context::<CombineQueuedMessages>()
+        .signal()
+        .get_untracked();
+    let active_chatlog = use_context::<Signal<Chatlog>>();
+    let mut log = active_chatlog.get_clone_untracked();
+    if log.dequeue_messages(combine) {
+        log.trigger_response_generation();
+    }
+}
+
+/// A component that renders the application, starting with the chatlog metadata and the
+/// JSON of the current chatlog that were loaded from the storage backend. The JSON is
+/// empty when there's no current chatlog yet.
+#[component(inline_props)]
+fn MainComponent(
+    storage: AppStorage,
+    metadata: Result<ChatLogMetadata, UnreadableRecord>,
+    current_chatlog: String,
+) -> View {
+    // keep a handle to this scope so that asynchronous callbacks can reach the contexts
+    provide_context(AppScope::current());
+
+    // the backend the chatlogs and their metadata are kept in
+    provide_context(storage.clone());
+
+    // setup the non-blocking notifications and the modal confirmation dialog
+    provide_context(Notifications::new());
+    provide_context(ConfirmationDialog::new());
+
     // create a signal for the chatlog metadata and put it in the context
-    let metadata = storage::load_from_local_storage::<ChatLogMetadata>(LSKEY_CHATLOG_METADATA)
-        .unwrap_or_else(ChatLogMetadata::new);
+    let (metadata, mut keep_stored_metadata) = match metadata {
+        Ok(metadata) => (metadata, false),
+        Err(record) => {
+            report_unreadable("The list of saved chatlogs", &record);
+            (ChatLogMetadata::default(), record.copy_key.is_none())
+        }
+    };
     let chatlog_metadata = create_signal(metadata);
     provide_context(chatlog_metadata);
 
-    // add effect to save metadata on change
-    let chatlog_metadata_clone = chatlog_metadata.clone();
+    // add effect to save metadata on change. metadata that couldn't be read or copied
+    // aside isn't saved over until the list changes.
+    let chatlog_metadata_clone = chatlog_metadata;
+    let notifications = use_context::<Notifications>();
     create_effect(move || {
         let metadata = chatlog_metadata_clone.get_clone();
+        if keep_stored_metadata {
+            keep_stored_metadata = false;
+            return;
+        }
+        let storage = storage.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = chatlogs::save_metadata(&storage, &metadata).await {
+                console_log!("Failed to save chatlog metadata: {:?}", e);
+                if e == StorageError::QuotaExceeded {
+                    notifications.error(format!(
+                        "The list of chatlogs couldn't be saved: {}",
+                        describe_storage_error(&e)
+                    ));
+                }
+            }
+        });
+    });
+
+    // create a signal for the monthly usage summary, saving it to storage on change
+    let usage_summary = create_signal(
+        storage::load_from_local_storage::<UsageSummary>(LSKEY_USAGE_SUMMARY).unwrap_or_default(),
+    );
+    provide_context(usage_summary);
+    create_effect(move || {
+        let summary = usage_summary.get_clone();
         if let Err(e) =
-            storage::save_to_local_storage::<ChatLogMetadata>(LSKEY_CHATLOG_METADATA, &metadata)
+            storage::save_to_local_storage::<UsageSummary>(LSKEY_USAGE_SUMMARY, &summary)
         {
-            console_log!("Failed to save chatlog metadata: {:?}", e);
+            console_log!("Failed to save the usage summary: {:?}", e);
         }
     });
 
     // create a signal for the chatlog and put it in the context
-    let chatlog_json_maybe = storage::load_from_local_storage::<String>(LSKEY_CURRENTLOG);
-    let active_chatlog = match chatlog_json_maybe {
-        Some(json) => match Chatlog::from_json(&json, generate_response) {
-            Ok((log, _, _)) => create_signal(log),
-            Err(_) => create_signal(Chatlog::new(generate_response)),
-        },
-        None => create_signal(Chatlog::new(generate_response)),
+    let active_chatlog = match Chatlog::from_json(&current_chatlog, generate_response) {
+        Ok((log, _, _)) => create_signal(log),
+        Err(e) => {
+            if !current_chatlog.is_empty() {
+                let storage = use_context::<AppStorage>();
+                let app_scope = use_context::<AppScope>();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let record =
+                        storage::set_aside(&storage, LSKEY_CURRENTLOG, &current_chatlog, e).await;
+                    app_scope.run_in(|| report_unreadable("The current chatlog", &record));
+                });
+            }
+            create_signal(Chatlog::new(generate_response))
+        }
     };
     provide_context(active_chatlog);
 
     // setup the light and dark mode switching signal. we pull the initial value
     // from local storage and setup an effect to send it to loca storage on change.
-    let dark_mode_init =
-        storage::load_from_local_storage::<bool>(LSKEY_DARK_MODE).unwrap_or_else(|| true);
+    let dark_mode_init = storage::load_from_local_storage::<bool>(LSKEY_DARK_MODE).unwrap_or(true);
     let dark_mode = DarkMode::new(dark_mode_init);
     provide_context(dark_mode);
-    let dark_mode_clone = dark_mode.signal().clone();
+    let dark_mode_clone = dark_mode.signal();
     create_effect(move || {
         let is_dark_mode = dark_mode_clone.get();
         if let Err(e) = storage::save_to_local_storage::<bool>(LSKEY_DARK_MODE, &is_dark_mode) {
@@ -148,13 +727,22 @@ fn MainComponent() -> View {
 
     // Create a signal for the API configuration to use in the context
     // as well as an effect to save it to storage on change.
-    let api_config = create_signal({
-        storage::load_from_local_storage::<ApiEndpointConfig>(LSKEY_API_CONFIG)
-            .unwrap_or_else(|| ApiEndpointConfig::default())
-    });
+    // settings that couldn't be read or copied aside aren't saved over until they change
+    let (config, mut keep_stored_config) = match load_api_config() {
+        Ok(config) => (config, false),
+        Err(record) => {
+            report_unreadable("The API settings", &record);
+            (ApiEndpointConfig::default(), record.copy_key.is_none())
+        }
+    };
+    let api_config = create_signal(config);
     provide_context(api_config);
     create_effect(move || {
         let new_config = api_config.get_clone();
+        if keep_stored_config {
+            keep_stored_config = false;
+            return;
+        }
         if let Err(e) =
             storage::save_to_local_storage::<ApiEndpointConfig>(LSKEY_API_CONFIG, &new_config)
         {
@@ -164,8 +752,8 @@ fn MainComponent() -> View {
 
     // Create a signal for the System Message to use in the context
     // as well as an effect to save it to storage on change.
-    let initial_system_msg = storage::load_from_local_storage::<String>(LSKEY_SYSMSG)
-        .unwrap_or_else(|| String::default());
+    let initial_system_msg =
+        storage::load_from_local_storage::<String>(LSKEY_SYSMSG).unwrap_or_default();
     let system_msg = SystemMessage::new(initial_system_msg);
     provide_context(system_msg);
     create_effect(move || {
@@ -180,6 +768,144 @@ fn MainComponent() -> View {
     let response_pending = IsResponsePending::new(false);
     provide_context(response_pending);
 
+    // Create a signal for the error of the last failed response so it can be shown inline
+    provide_context(ResponseError::new(None));
+    provide_context(IgnoreSpendingCap::new(false));
+
+    // setup the switch for how messages queued during a pending response are sent
+    let combine_queued = CombineQueuedMessages::new(
+        storage::load_from_local_storage::<bool>(LSKEY_COMBINE_QUEUED).unwrap_or(true),
+    );
+    provide_context(combine_queued);
+    create_effect(move || {
+        let combine = combine_queued.signal().get();
+        if let Err(e) = storage::save_to_local_storage::<bool>(LSKEY_COMBINE_QUEUED, &combine) {
+            console_log!(
+                "save_to_local_storage error for combining queued messages: {:?}",
+                e
+            );
+        }
+    });
+
+    // setup how attached images get downscaled and recompressed
+    let image_compression = ImageCompression::new(
+        storage::load_from_local_storage::<ImageCompressionSettings>(LSKEY_IMAGE_COMPRESSION)
+            .unwrap_or_default(),
+    );
+    provide_context(image_compression);
+    create_effect(move || {
+        let settings = image_compression.signal().get_clone();
+        if let Err(e) = storage::save_to_local_storage::<ImageCompressionSettings>(
+            LSKEY_IMAGE_COMPRESSION,
+            &settings,
+        ) {
+            console_log!(
+                "save_to_local_storage error for the image compression settings: {:?}",
+                e
+            );
+        }
+    });
+
+    // setup the player for reading messages aloud
+    provide_context(SpeechPlayer::new());
+
+    // the message to scroll to when a search result is opened
+    provide_context(JumpToMessage::new(None));
+
+    // the request inspector is opt-in; only whether it's enabled is kept in storage
+    let request_inspector = RequestInspector::new(
+        storage::load_from_local_storage::<bool>(LSKEY_REQUEST_INSPECTOR).unwrap_or(false),
+    );
+    provide_context(request_inspector);
+    create_effect(move || {
+        let enabled = request_inspector.enabled().get();
+        if let Err(e) = storage::save_to_local_storage::<bool>(LSKEY_REQUEST_INSPECTOR, &enabled) {
+            console_log!(
+                "save_to_local_storage error for the request inspector: {:?}",
+                e
+            );
+        }
+    });
+
+    // the saved chatlog the active one came from, and whether changes are saved into it
+    let linked_chatlog = LinkedChatlog::new(
+        storage::load_from_local_storage::<Option<String>>(LSKEY_LINKED_CHATLOG).flatten(),
+    );
+    provide_context(linked_chatlog);
+    create_effect(move || {
+        let linked = linked_chatlog.signal().get_clone();
+        if let Err(e) = storage::save_to_local_storage(LSKEY_LINKED_CHATLOG, &linked) {
+            console_log!(
+                "save_to_local_storage error for the linked chatlog: {:?}",
+                e
+            );
+        }
+    });
+    let autosave_to_slot = AutosaveToSlot::new(
+        storage::load_from_local_storage::<bool>(LSKEY_AUTOSAVE_TO_SLOT).unwrap_or(false),
+    );
+    provide_context(autosave_to_slot);
+    create_effect(move || {
+        let enabled = autosave_to_slot.signal().get();
+        if let Err(e) = storage::save_to_local_storage::<bool>(LSKEY_AUTOSAVE_TO_SLOT, &enabled) {
+            console_log!(
+                "save_to_local_storage error for auto-saving to the slot: {:?}",
+                e
+            );
+        }
+    });
+
+    // save the active chatlog a moment after it last changed, so that every new message,
+    // edit, deletion and variant switch survives a reload. every change restarts the
+    // timer, which keeps streamed responses from being saved chunk by chunk.
+    let autosave = Autosave::new();
+    let mut is_first_run = true;
+    create_effect(move || {
+        let log = active_chatlog.get_clone();
+        log.messages.track();
+        log.keep_reasoning.track();
+        log.usage.track();
+        log.queued_messages.track();
+        log.documents.track();
+        api_config.track();
+        system_msg.signal().track();
+        // the first run only sees the chatlog that was just loaded from storage
+        if is_first_run {
+            is_first_run = false;
+            return;
+        }
+        autosave.schedule();
+    });
+
+    // the timer doesn't get to run once the page is closed or put away, so a waiting save
+    // is done as soon as the page is hidden. browsers on phones may never fire the
+    // pagehide event, but they do report the page turning hidden.
+    let app_scope = use_context::<AppScope>();
+    let on_hide = Closure::<dyn Fn()>::new(move || app_scope.run_in(|| autosave.flush()));
+    for event in ["pagehide", "visibilitychange"] {
+        if let Err(e) =
+            window().add_event_listener_with_callback(event, on_hide.as_ref().unchecked_ref())
+        {
+            console_log!("Failed to listen for the page being hidden: {:?}", e);
+        }
+    }
+    on_cleanup(move || {
+        for event in ["pagehide", "visibilitychange"] {
+            let _ = window()
+                .remove_event_listener_with_callback(event, on_hide.as_ref().unchecked_ref());
+        }
+    });
+
+    // the notifications and dialogs sit outside the interfaces so they need their
+    // own dark mode class.
+    let get_overlay_classes = move || {
+        if dark_mode.is_dark_mode() {
+            "dark"
+        } else {
+            "light"
+        }
+    };
+
     view! {
         (if is_editing_config.get() {
             view! { ConfigInterface() }
@@ -188,10 +914,77 @@ fn MainComponent() -> View {
         } else {
             view! { ChatInterface() }
         })
+
+        div(class=get_overlay_classes()) {
+            NotificationsComponent()
+            ConfirmationDialogComponent()
+        }
     }
 }
 
 fn main() {
     console_error_panic_hook::set_once();
-    sycamore::render(|| view! { MainComponent() });
+
+    // the chatlogs usually live in IndexedDB, so the metadata and the current chatlog
+    // are read asynchronously before the first render
+    wasm_bindgen_futures::spawn_local(async {
+        let storage = storage::open_storage().await;
+        let metadata = chatlogs::load_metadata(&storage).await;
+        let current_chatlog = chatlogs::load_current_chatlog(&storage).await;
+        sycamore::render(move || {
+            view! {
+                MainComponent(storage=storage, metadata=metadata, current_chatlog=current_chatlog)
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata_with(ids: &[&str]) -> ChatLogMetadata {
+        ChatLogMetadata {
+            saved_logs: ids
+                .iter()
+                .map(|id| ChatLogMetadataEntry {
+                    id: id.to_string(),
+                    storage_key: format!("chatlog_{}", id),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_linked_chatlog_is_saved_with_autosave_on() {
+        let metadata = metadata_with(&["a", "b"]);
+        let slot = resolve_autosave_slot(true, Some("b".to_string()), &metadata);
+        assert_eq!(slot, Some(metadata.saved_logs[1].clone()));
+    }
+
+    #[test]
+    fn only_the_current_chatlog_is_saved_with_autosave_off() {
+        let metadata = metadata_with(&["a", "b"]);
+        assert_eq!(
+            resolve_autosave_slot(false, Some("b".to_string()), &metadata),
+            None
+        );
+    }
+
+    #[test]
+    fn there_is_no_slot_without_a_linked_chatlog() {
+        let metadata = metadata_with(&["a"]);
+        assert_eq!(resolve_autosave_slot(true, None, &metadata), None);
+    }
+
+    #[test]
+    fn a_deleted_chatlog_is_not_saved_again() {
+        let metadata = metadata_with(&["a"]);
+        assert_eq!(
+            resolve_autosave_slot(true, Some("deleted".to_string()), &metadata),
+            None
+        );
+    }
 }
@@ -0,0 +1,497 @@
+use serde_json::{Value, json};
+use sycamore::prelude::*;
+use web_sys::js_sys::{Math, Promise};
+use web_sys::wasm_bindgen::JsValue;
+
+use crate::api_endpoint::{RawResponse, estimate_tokens};
+use crate::models::config::{MockErrorKind, MockProviderConfig, MockReplyMode};
+
+// the delay between the events of a streamed reply
+const STREAM_CHUNK_DELAY_MS: u32 = 15;
+
+const LOREM_IPSUM_WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+];
+
+/// The URL recorded for requests answered by the mock provider.
+pub const MOCK_PROVIDER_URL: &str = "mock://chat/completions";
+
+/// Answers a chat completion request with the built-in mock provider instead of sending it
+/// over the network. The reply is the raw response an OpenAI compatible API would send back
+/// and goes through the same parsing as a real one, so the rest of the application can't
+/// tell the difference.
+pub(crate) async fn mock_chat_completion(
+    mock: MockProviderConfig,
+    request_body: &Value,
+) -> Result<RawResponse, String> {
+    sleep(mock.get_latency_ms()).await?;
+
+    let inject_error =
+        mock.error_kind != MockErrorKind::None && Math::random() * 100.0 < mock.get_error_rate();
+    if inject_error {
+        return injected_error(mock.error_kind);
+    }
+
+    // a streamed reply arrives as server-sent events, but like the replies of real endpoints
+    // it's only parsed and shown once the stream has ended.
+    if mock.simulate_streaming {
+        let mut body = String::new();
+        for chunk in build_stream_chunks(&mock, request_body) {
+            sleep(STREAM_CHUNK_DELAY_MS).await?;
+            body.push_str(&format!("data: {}\n\n", chunk));
+        }
+        body.push_str("data: [DONE]\n\n");
+        Ok(raw_response(200, "text/event-stream", body))
+    } else {
+        Ok(raw_response(
+            200,
+            "application/json",
+            build_response(&mock, request_body).to_string(),
+        ))
+    }
+}
+
+fn raw_response(status: u16, content_type: &str, body: String) -> RawResponse {
+    RawResponse {
+        status,
+        headers: vec![("content-type".to_string(), content_type.to_string())],
+        body,
+    }
+}
+
+// waits for the given number of milliseconds without blocking the page
+async fn sleep(ms: u32) -> Result<(), String> {
+    let window = web_sys::window().ok_or("mock provider has no window to wait in")?;
+    let promise = Promise::new(&mut |resolve, _| {
+        if let Err(e) =
+            window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms as i32)
+        {
+            console_log!("Failed to schedule the mock provider delay: {:?}", e);
+            let _ = resolve.call0(&JsValue::NULL);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+    Ok(())
+}
+
+// builds the raw failure for the error kind, in the same shapes real providers respond with
+fn injected_error(kind: MockErrorKind) -> Result<RawResponse, String> {
+    let error_body = |code: &str, message: &str| {
+        json!({ "error": { "code": code, "message": message } }).to_string()
+    };
+    let reply_body = |content: Value, finish_reason: &str| {
+        json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": finish_reason,
+            }]
+        })
+        .to_string()
+    };
+    let response = match kind {
+        MockErrorKind::None | MockErrorKind::Network => {
+            return Err("mock provider simulated a network failure".to_string());
+        }
+        MockErrorKind::Unauthorized => raw_response(
+            401,
+            "application/json",
+            error_body("invalid_api_key", "Mock provider: invalid API key."),
+        ),
+        MockErrorKind::RateLimited => raw_response(
+            429,
+            "application/json",
+            error_body("rate_limit_exceeded", "Mock provider: too many requests."),
+        ),
+        MockErrorKind::ServerError => raw_response(
+            500,
+            "application/json",
+            error_body("server_error", "Mock provider: internal server error."),
+        ),
+        MockErrorKind::ContextLengthExceeded => raw_response(
+            400,
+            "application/json",
+            error_body(
+                "context_length_exceeded",
+                "Mock provider: this model's maximum context length was exceeded.",
+            ),
+        ),
+        MockErrorKind::ContentFiltered => raw_response(
+            200,
+            "application/json",
+            reply_body(Value::Null, "content_filter"),
+        ),
+        MockErrorKind::MalformedResponse => raw_response(
+            200,
+            "text/html",
+            "<html><body>502 Bad Gateway</body></html>".to_string(),
+        ),
+        MockErrorKind::EmptyContent => {
+            raw_response(200, "application/json", reply_body(json!(""), "stop"))
+        }
+    };
+    Ok(response)
+}
+
+// returns the text of a message in the request, which may be a string or an array of parts
+fn message_text(message: &Value) -> String {
+    match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+// comes up with the reply text and optional reasoning for the request
+fn build_reply(mock: &MockProviderConfig, request_body: &Value) -> (String, Option<String>) {
+    let empty = Vec::new();
+    let messages = request_body["messages"].as_array().unwrap_or(&empty);
+    let last_user_text = messages
+        .iter()
+        .rev()
+        .find(|m| m["role"] == "user")
+        .map(message_text)
+        .unwrap_or_default();
+
+    let (content, scripted_reasoning) = match mock.reply_mode {
+        MockReplyMode::Echo => (last_user_text.clone(), None),
+        MockReplyMode::LoremIpsum => (lorem_ipsum(40 + (Math::random() * 60.0) as usize), None),
+        MockReplyMode::Scripted => {
+            if mock.scripted_replies.is_empty() {
+                (
+                    "No scripted replies have been loaded for the mock provider.".to_string(),
+                    None,
+                )
+            } else {
+                // the replies are used in order, based on how far along the conversation is
+                let turn = messages.iter().filter(|m| m["role"] == "assistant").count();
+                let reply = &mock.scripted_replies[turn % mock.scripted_replies.len()];
+                (reply.content.clone(), reply.reasoning.clone())
+            }
+        }
+    };
+
+    let reasoning = scripted_reasoning.or_else(|| {
+        mock.include_thinking.then(|| {
+            format!(
+                "The user wrote {} characters, so I should reply with something of similar substance.",
+                last_user_text.chars().count()
+            )
+        })
+    });
+    (content, reasoning)
+}
+
+// generates the given number of words of placeholder text
+fn lorem_ipsum(word_count: usize) -> String {
+    let mut text = String::new();
+    for i in 0..word_count {
+        let word = LOREM_IPSUM_WORDS
+            [(Math::random() * LOREM_IPSUM_WORDS.len() as f64) as usize % LOREM_IPSUM_WORDS.len()];
+        if i == 0 {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                text.push_str(&first.to_uppercase().collect::<String>());
+                text.push_str(chars.as_str());
+            }
+        } else {
+            text.push(' ');
+            text.push_str(word);
+        }
+    }
+    text.push('.');
+    text
+}
+
+// the usage block for the request, with the tokens estimated from the text lengths
+fn build_usage(request_body: &Value, reply: &str) -> Value {
+    let empty = Vec::new();
+    let prompt_tokens: u32 = request_body["messages"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .map(|m| estimate_tokens(&message_text(m)))
+        .sum();
+    let completion_tokens = estimate_tokens(reply);
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+    })
+}
+
+// a tool call for the model to make, in the OpenAI format
+fn mock_tool_calls() -> Value {
+    json!([{
+        "id": "call_mock_1",
+        "type": "function",
+        "function": {
+            "name": "get_current_weather",
+            "arguments": "{\"location\": \"Boston, MA\"}",
+        },
+    }])
+}
+
+// builds the non-streamed response body for the request
+fn build_response(mock: &MockProviderConfig, request_body: &Value) -> Value {
+    let model = request_body["model"].clone();
+    if mock.reply_with_tool_calls {
+        return json!({
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": null, "tool_calls": mock_tool_calls() },
+                "finish_reason": "tool_calls",
+            }],
+            "usage": build_usage(request_body, ""),
+        });
+    }
+
+    let (content, reasoning) = build_reply(mock, request_body);
+    let mut message = json!({ "role": "assistant", "content": content });
+    if let Some(reasoning) = reasoning {
+        message["reasoning_content"] = json!(reasoning);
+    }
+    json!({
+        "object": "chat.completion",
+        "model": model,
+        "choices": [{ "index": 0, "message": message, "finish_reason": "stop" }],
+        "usage": build_usage(request_body, &content),
+    })
+}
+
+// builds the chunks of a streamed response body for the request, one per word
+fn build_stream_chunks(mock: &MockProviderConfig, request_body: &Value) -> Vec<Value> {
+    let model = request_body["model"].clone();
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({
+            "object": "chat.completion.chunk",
+            "model": model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    };
+
+    let mut chunks = vec![chunk(json!({ "role": "assistant" }), Value::Null)];
+    if mock.reply_with_tool_calls {
+        chunks.push(chunk(
+            json!({ "tool_calls": mock_tool_calls() }),
+            json!("tool_calls"),
+        ));
+        return chunks;
+    }
+
+    let (content, reasoning) = build_reply(mock, request_body);
+    if let Some(reasoning) = reasoning {
+        for word in reasoning.split_inclusive(' ') {
+            chunks.push(chunk(json!({ "reasoning": word }), Value::Null));
+        }
+    }
+    for word in content.split_inclusive(' ') {
+        chunks.push(chunk(json!({ "content": word }), Value::Null));
+    }
+
+    let mut last = chunk(json!({}), json!("stop"));
+    last["usage"] = build_usage(request_body, &content);
+    chunks.push(last);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_endpoint::{ApiError, parse_chat_completion_response};
+    use crate::models::config::MockReply;
+
+    fn request(messages: Value) -> Value {
+        json!({ "model": "mock-model", "messages": messages })
+    }
+
+    // parses a mocked response the way the replies of real endpoints are
+    fn parse(response: &RawResponse) -> Result<String, ApiError> {
+        parse_chat_completion_response(response.status, &response.body).map(|r| r.text)
+    }
+
+    fn scripted_config() -> MockProviderConfig {
+        MockProviderConfig {
+            reply_mode: MockReplyMode::Scripted,
+            scripted_replies: vec![
+                MockReply {
+                    content: "First".to_string(),
+                    reasoning: Some("Planned".to_string()),
+                },
+                MockReply {
+                    content: "Second".to_string(),
+                    reasoning: None,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn echo_replies_repeat_the_last_user_message() {
+        let body = request(json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Earlier" },
+            { "role": "assistant", "content": "Reply" },
+            { "role": "user", "content": [
+                { "type": "text", "text": "Look at" },
+                { "type": "image_url", "image_url": { "url": "data:," } },
+                { "type": "text", "text": "this" },
+            ] },
+        ]));
+        let mock = MockProviderConfig::default();
+        let response = raw_response(
+            200,
+            "application/json",
+            build_response(&mock, &body).to_string(),
+        );
+        assert_eq!(parse(&response).unwrap(), "Look at\nthis");
+
+        let usage = &build_response(&mock, &body)["usage"];
+        assert_eq!(usage["completion_tokens"], estimate_tokens("Look at\nthis"));
+    }
+
+    #[test]
+    fn scripted_replies_follow_the_conversation() {
+        let mock = scripted_config();
+        let first_turn = request(json!([{ "role": "user", "content": "Hi" }]));
+        assert_eq!(
+            build_reply(&mock, &first_turn),
+            ("First".to_string(), Some("Planned".to_string()))
+        );
+
+        let second_turn = request(json!([
+            { "role": "user", "content": "Hi" },
+            { "role": "assistant", "content": "First" },
+            { "role": "user", "content": "And?" },
+        ]));
+        assert_eq!(
+            build_reply(&mock, &second_turn),
+            ("Second".to_string(), None)
+        );
+
+        // the script starts over once it runs out
+        let third_turn = request(json!([
+            { "role": "assistant", "content": "First" },
+            { "role": "assistant", "content": "Second" },
+        ]));
+        assert_eq!(build_reply(&mock, &third_turn).0, "First");
+
+        let empty = MockProviderConfig {
+            scripted_replies: Vec::new(),
+            ..mock
+        };
+        assert!(
+            build_reply(&empty, &first_turn)
+                .0
+                .contains("No scripted replies")
+        );
+    }
+
+    #[test]
+    fn streamed_replies_parse_the_same_as_whole_ones() {
+        let mock = scripted_config();
+        let body = request(json!([{ "role": "user", "content": "Hi there" }]));
+        let mut stream = String::new();
+        for chunk in build_stream_chunks(&mock, &body) {
+            stream.push_str(&format!("data: {}\n\n", chunk));
+        }
+        stream.push_str("data: [DONE]\n\n");
+
+        let streamed = parse(&raw_response(200, "text/event-stream", stream)).unwrap();
+        let whole = raw_response(
+            200,
+            "application/json",
+            build_response(&mock, &body).to_string(),
+        );
+        assert_eq!(streamed, parse(&whole).unwrap());
+        assert_eq!(streamed, "<think>Planned</think>\nFirst");
+    }
+
+    #[test]
+    fn tool_calls_are_reported_as_empty_content() {
+        let mock = MockProviderConfig {
+            reply_with_tool_calls: true,
+            ..Default::default()
+        };
+        let body = request(json!([{ "role": "user", "content": "Weather?" }]));
+        let response = raw_response(
+            200,
+            "application/json",
+            build_response(&mock, &body).to_string(),
+        );
+        assert!(matches!(parse(&response), Err(ApiError::EmptyContent(_))));
+    }
+
+    #[test]
+    fn injected_errors_are_read_as_the_failures_they_stand_in_for() {
+        let parsed = |kind| parse(&injected_error(kind).unwrap());
+        assert!(injected_error(MockErrorKind::Network).is_err());
+        assert!(matches!(
+            parsed(MockErrorKind::Unauthorized),
+            Err(ApiError::HttpStatus { status: 401, .. })
+        ));
+        assert!(matches!(
+            parsed(MockErrorKind::RateLimited),
+            Err(ApiError::HttpStatus { status: 429, .. })
+        ));
+        assert!(matches!(
+            parsed(MockErrorKind::ServerError),
+            Err(ApiError::HttpStatus { status: 500, .. })
+        ));
+        assert!(matches!(
+            parsed(MockErrorKind::ContextLengthExceeded),
+            Err(ApiError::ContextLengthExceeded(_))
+        ));
+        assert!(matches!(
+            parsed(MockErrorKind::ContentFiltered),
+            Err(ApiError::ContentFiltered(_))
+        ));
+        assert!(matches!(
+            parsed(MockErrorKind::MalformedResponse),
+            Err(ApiError::MalformedResponse(_))
+        ));
+        assert!(matches!(
+            parsed(MockErrorKind::EmptyContent),
+            Err(ApiError::EmptyContent(_))
+        ));
+    }
+}
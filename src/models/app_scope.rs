@@ -0,0 +1,17 @@
+use sycamore::prelude::*;
+
+// Handle to the reactive scope of the main component, where all the application
+// contexts are provided. Callbacks that run outside of an event handler, such as
+// the completion of an API request, use it to get access to the contexts again.
+#[derive(Clone, Copy)]
+pub struct AppScope(NodeHandle);
+
+impl AppScope {
+    pub fn current() -> Self {
+        Self(use_current_scope())
+    }
+
+    pub fn run_in<T>(self, f: impl FnOnce() -> T) -> T {
+        self.0.run_in(f)
+    }
+}
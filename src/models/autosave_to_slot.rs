@@ -0,0 +1,16 @@
+use sycamore::prelude::*;
+
+// When true, every change of the active chatlog is also saved into the saved chatlog it's
+// linked to, without asking to confirm the overwrite.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AutosaveToSlot(Signal<bool>);
+
+impl AutosaveToSlot {
+    pub fn new(initial_value: bool) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<bool> {
+        self.0
+    }
+}
@@ -1,34 +1,38 @@
+use std::collections::VecDeque;
+
 use serde::{Deserialize, Serialize};
 use sycamore::prelude::*;
 
-use super::config::ApiEndpointConfig;
-
-const CHAT_LOG_METADATA_VERSION: u16 = 1;
+use super::{
+    config::ApiEndpointConfig,
+    documents::{AttachedDocument, Citation},
+    usage::Usage,
+};
+use crate::storage::schema::{self, SchemaError};
 
 // Metadata for an individual saved chat logs
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ChatLogMetadataEntry {
     pub id: String,              // guid
     pub title: String,           // user-provided name for the chat log
     pub last_accessed_time: i64, // when it was last accessed
-    pub storage_key: String,     // LocalStorage key where this chat log is stored
+    pub storage_key: String,     // storage key where this chat log is stored
     pub message_count: usize,    // number of messages in this chat log
+    pub usage: Usage,            // cumulative tokens and cost of this chat log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkOrigin>, // set if this chat log was forked from another one
 }
 
-impl Default for ChatLogMetadataEntry {
-    fn default() -> Self {
-        Self {
-            id: Default::default(),
-            title: Default::default(),
-            last_accessed_time: Default::default(),
-            storage_key: Default::default(),
-            message_count: Default::default(),
-        }
-    }
+// Where a forked chat log came from: the chat log it was forked from, if that one was
+// saved, and the last message that was copied from it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ForkOrigin {
+    pub parent_id: Option<String>,
+    pub message_id: u32,
 }
 
 // Represents the metadata for all saved chat logs
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChatLogMetadata {
     pub version: u16,                          // Version of this metadata format
     pub saved_logs: Vec<ChatLogMetadataEntry>, // List of all saved chat logs
@@ -37,7 +41,7 @@ pub struct ChatLogMetadata {
 impl Default for ChatLogMetadata {
     fn default() -> Self {
         Self {
-            version: CHAT_LOG_METADATA_VERSION,
+            version: schema::METADATA_VERSION,
             saved_logs: Vec::new(),
         }
     }
@@ -48,6 +52,59 @@ impl ChatLogMetadata {
     pub fn new() -> Self {
         Self::default()
     }
+
+    // updates the entry of a chatlog that was just saved or loaded.
+    pub fn touch_entry(&mut self, id: &str, message_count: usize, usage: Usage, now: i64) {
+        if let Some(entry) = self.saved_logs.iter_mut().find(|e| e.id == id) {
+            entry.message_count = message_count;
+            entry.usage = usage;
+            entry.last_accessed_time = now;
+        }
+    }
+
+    // removes the entry of a chatlog that was deleted.
+    pub fn remove_entry(&mut self, id: &str) {
+        self.saved_logs.retain(|e| e.id != id);
+    }
+
+    // returns the entries with the forks of a chatlog listed right after it, along with
+    // how deep every entry is nested in forks. chatlogs at the same depth are sorted by
+    // when they were last accessed, newest first, and forks whose parent is gone are
+    // listed like any other chatlog.
+    pub fn grouped_by_fork(&self) -> Vec<(usize, ChatLogMetadataEntry)> {
+        // the parent of a fork, as long as it's still in the list
+        fn listed_parent<'a>(
+            entries: &[ChatLogMetadataEntry],
+            entry: &'a ChatLogMetadataEntry,
+        ) -> Option<&'a str> {
+            entry
+                .forked_from
+                .as_ref()
+                .and_then(|origin| origin.parent_id.as_deref())
+                .filter(|id| *id != entry.id && entries.iter().any(|e| e.id == *id))
+        }
+
+        fn push_with_forks(
+            entries: &[ChatLogMetadataEntry],
+            parent_id: Option<&str>,
+            depth: usize,
+            grouped: &mut Vec<(usize, ChatLogMetadataEntry)>,
+        ) {
+            for entry in entries
+                .iter()
+                .filter(|e| listed_parent(entries, e) == parent_id)
+            {
+                grouped.push((depth, entry.clone()));
+                push_with_forks(entries, Some(&entry.id), depth + 1, grouped);
+            }
+        }
+
+        let mut entries = self.saved_logs.clone();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.last_accessed_time));
+        let mut grouped = Vec::with_capacity(entries.len());
+        push_with_forks(&entries, None, 0, &mut grouped);
+        grouped
+    }
 }
 
 // Convenience struct to handle JSON serialization of chatlog components.
@@ -57,6 +114,75 @@ struct JSONChatlog {
     api_settings: ApiEndpointConfig,
     system_message: String,
     messages: Vec<Message>,
+
+    #[serde(default)]
+    keep_reasoning: bool,
+    #[serde(default)]
+    usage: Usage,
+    #[serde(default)]
+    queued_messages: Vec<QueuedMessage>,
+    #[serde(default)]
+    documents: Vec<AttachedDocument>,
+}
+
+// A user message that was submitted while a response was pending. It's moved into the
+// chatlog's messages once the response lands.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub message: String,
+    #[serde(default)]
+    pub images: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<FileAttachment>,
+    // set when the message is the prompt for an image rather than a chat message
+    #[serde(default)]
+    pub wants_image: bool,
+}
+
+// A text or source file attached to a user message. It's kept apart from the message
+// text so the UI can collapse it, and inlined as a fenced code block when sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileAttachment {
+    pub name: String,
+    pub content: String,
+}
+
+impl FileAttachment {
+    // formats the file as a fenced code block with a filename header. the fence is made
+    // longer than any run of backticks inside the file so that it can't be closed early.
+    pub fn to_fenced_block(&self) -> String {
+        let longest_run = self
+            .content
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest_run.max(2) + 1);
+        let language = self
+            .name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_lowercase())
+            .unwrap_or_default();
+        format!(
+            "**{}**\n{}{}\n{}\n{}",
+            self.name,
+            fence,
+            language,
+            self.content.trim_end(),
+            fence
+        )
+    }
+}
+
+// appends the attached files to the message text as fenced code blocks.
+pub fn inline_file_attachments(message: &str, files: &[FileAttachment]) -> String {
+    files.iter().fold(message.to_string(), |mut content, file| {
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        content.push_str(&file.to_fenced_block());
+        content
+    })
 }
 
 // Represents an individual chat message generation that encapsulates the
@@ -64,10 +190,22 @@ struct JSONChatlog {
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StackedMessage {
     pub message: String,
-    pub image_base64: Option<String>, // optional base64 encoded image associated with message
+    // base64 encoded data URLs of the images associated with the message
+    #[serde(default)]
+    pub images: Vec<String>,
 
-                                      // TODO: generation timing stats
-                                      // TODO: record API settings used
+    // the chunks of the attached documents that were sent along to generate this message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+    // text files attached to a user message, sent inlined after the message text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileAttachment>,
+    // the messages that follow this variant while another variant of the message is
+    // selected; the ones following the selected variant are the rest of the chatlog.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub continuation: Vec<Message>,
+    // TODO: generation timing stats
+    // TODO: record API settings used
 }
 
 /// Represents an individual chat message; combines the message content with metadata
@@ -98,6 +236,90 @@ impl Message {
             *stacked_msg = new_item;
         }
     }
+
+    // returns how many messages follow each variant of the message. the selected one is
+    // followed by the rest of the chatlog, so its count is passed in.
+    pub fn branch_lengths(&self, following_selected: usize) -> Vec<usize> {
+        self.message_stack
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                if index == self.selected_message {
+                    following_selected
+                } else {
+                    variant.continuation.len()
+                }
+            })
+            .collect()
+    }
+}
+
+// reads the JSON of a saved chatlog, upgrading it first if it's in an older format.
+fn read_json_chatlog(json_str: &str) -> Result<JSONChatlog, SchemaError> {
+    Ok(serde_json::from_value(schema::upgrade_chatlog(
+        serde_json::from_str(json_str)?,
+    )?)?)
+}
+
+// Returns true if the saved chatlog in the JSON starts with the messages, as they're shown.
+// A fork can only be traced back to a saved chatlog that does, since the active chatlog
+// may have changed since it was saved.
+pub fn saved_chatlog_shows(saved_json: &str, messages: &[Message]) -> bool {
+    let Ok(saved) = read_json_chatlog(saved_json) else {
+        return false;
+    };
+    let shown = |msg: &Message| {
+        let selected = msg.get_selected_message().unwrap_or_default();
+        (msg.id, msg.ai_generated, selected.message, selected.images)
+    };
+    messages.len() <= saved.messages.len()
+        && messages
+            .iter()
+            .zip(&saved.messages)
+            .all(|(msg, saved)| shown(msg) == shown(saved))
+}
+
+// returns how many messages the branches of the message's variants hold, counting the
+// branches that start within those branches as well.
+fn hidden_message_count(msg: &Message) -> usize {
+    msg.message_stack
+        .iter()
+        .flat_map(|variant| variant.continuation.iter())
+        .map(|m| 1 + hidden_message_count(m))
+        .sum()
+}
+
+// Selects another variant of the message at the index of the chatlog's messages. The
+// messages after it are kept as the continuation of the variant that was selected, and
+// the continuation of the newly selected variant takes their place.
+fn select_variant(msgs: &mut Vec<Message>, index: usize, variant: usize) {
+    let Some(msg) = msgs.get(index) else {
+        return;
+    };
+    if variant == msg.selected_message || variant >= msg.message_stack.len() {
+        return;
+    }
+    let following = msgs.split_off(index + 1);
+    let msg = &mut msgs[index];
+    if let Some(previous) = msg.message_stack.get_mut(msg.selected_message) {
+        previous.continuation = following;
+    }
+    msg.selected_message = variant;
+    let continuation = std::mem::take(&mut msg.message_stack[variant].continuation);
+    msgs.extend(continuation);
+}
+
+// returns the highest id of the messages, including the ones in the other branches.
+fn max_message_id(msgs: &[Message]) -> Option<u32> {
+    msgs.iter()
+        .flat_map(|msg| {
+            let branches = msg
+                .message_stack
+                .iter()
+                .filter_map(|variant| max_message_id(&variant.continuation));
+            std::iter::once(msg.id).chain(branches)
+        })
+        .max()
 }
 
 // extracts think block from message, returning a tuple that represents
@@ -121,6 +343,99 @@ pub fn parse_think_block(message: String) -> Option<(String, String)> {
     }
 }
 
+// how many changes of the messages can be undone; older ones are forgotten
+const MAX_UNDO_STEPS: usize = 50;
+
+// roughly how much memory the earlier states of the messages may take. every state is a
+// full copy of the messages, images included, so older ones are forgotten sooner in
+// chatlogs with many images.
+const MAX_UNDO_BYTES: usize = 32 * 1024 * 1024;
+
+// estimates how much memory the messages take by their text, images and attachments,
+// which make up nearly all of it.
+fn estimated_size(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .flat_map(|msg| &msg.message_stack)
+        .map(|stacked| {
+            stacked.message.len()
+                + stacked.images.iter().map(String::len).sum::<usize>()
+                + stacked.files.iter().map(|f| f.content.len()).sum::<usize>()
+                + stacked
+                    .citations
+                    .iter()
+                    .map(|c| c.text.len())
+                    .sum::<usize>()
+                + estimated_size(&stacked.continuation)
+        })
+        .sum()
+}
+
+// The states the messages had before their latest changes, for undoing them, and the
+// states that were undone, for redoing them. Making a new change forgets what was undone.
+// Every state is kept with its estimated size, so that the oldest ones can be forgotten
+// once they take too much memory; the latest change can always be undone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageHistory {
+    undo: VecDeque<(Vec<Message>, usize)>,
+    redo: Vec<(Vec<Message>, usize)>,
+    bytes: usize, // the estimated size of every state kept
+}
+
+impl MessageHistory {
+    // remembers the state the messages had before a change.
+    pub fn record(&mut self, before: Vec<Message>) {
+        self.bytes -= self.redo.drain(..).map(|(_, size)| size).sum::<usize>();
+        self.push_undo(before);
+    }
+
+    // returns the state from before the latest change, keeping the current one for redoing.
+    pub fn undo(&mut self, current: Vec<Message>) -> Option<Vec<Message>> {
+        let (previous, size) = self.undo.pop_back()?;
+        let current_size = estimated_size(&current);
+        self.bytes = self.bytes - size + current_size;
+        self.redo.push((current, current_size));
+        self.forget_oldest();
+        Some(previous)
+    }
+
+    // returns the state the latest undo went back from, keeping the current one for undoing.
+    pub fn redo(&mut self, current: Vec<Message>) -> Option<Vec<Message>> {
+        let (next, size) = self.redo.pop()?;
+        self.bytes -= size;
+        self.push_undo(current);
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    fn push_undo(&mut self, state: Vec<Message>) {
+        let size = estimated_size(&state);
+        self.bytes += size;
+        self.undo.push_back((state, size));
+        self.forget_oldest();
+    }
+
+    // drops the oldest states that can be undone while there are too many of them or
+    // they take too much memory, always keeping the latest one.
+    fn forget_oldest(&mut self) {
+        while self.undo.len() > MAX_UNDO_STEPS
+            || (self.undo.len() > 1 && self.bytes > MAX_UNDO_BYTES)
+        {
+            let Some((_, size)) = self.undo.pop_front() else {
+                break;
+            };
+            self.bytes -= size;
+        }
+    }
+}
+
 // Encapsulates all the data for a given 'chat log' in the application.
 // Note: Remember to update `clone_from()` when adding more signals.
 #[derive(Debug, Clone)]
@@ -128,7 +443,28 @@ pub struct Chatlog {
     pub next_id: Signal<u32>,
     pub messages: Signal<Vec<Message>>,
     pub response_generator: fn(),
-    pub is_regenerating_msg: Signal<bool>,
+
+    // the id of the AI message a new variant is being generated for, if any. it's
+    // generated from the messages before it.
+    pub regenerating_msg: Signal<Option<u32>>,
+
+    // when true, the next response is an image generated from the last user message
+    pub is_generating_image: Signal<bool>,
+
+    // when true, prior thinking content is sent back to the model instead of stripped
+    pub keep_reasoning: Signal<bool>,
+
+    // cumulative tokens and cost of all the responses generated for this chatlog
+    pub usage: Signal<Usage>,
+
+    // user messages waiting to be sent once the pending response lands
+    pub queued_messages: Signal<Vec<QueuedMessage>>,
+
+    // text documents whose relevant chunks are added to every request
+    pub documents: Signal<Vec<AttachedDocument>>,
+
+    // the earlier states of the messages, for undoing and redoing changes
+    pub history: Signal<MessageHistory>,
 }
 
 impl Chatlog {
@@ -137,31 +473,39 @@ impl Chatlog {
         Self {
             next_id: create_signal(1),
             messages: create_signal(vec![]),
-            response_generator: response_generator,
-            is_regenerating_msg: create_signal(false),
+            response_generator,
+            regenerating_msg: create_signal(None),
+            is_generating_image: create_signal(false),
+            keep_reasoning: create_signal(false),
+            usage: create_signal(Usage::default()),
+            queued_messages: create_signal(vec![]),
+            documents: create_signal(vec![]),
+            history: create_signal(MessageHistory::default()),
         }
     }
 
     // given a JSON string, will attempt to desrialize it into a tupple consisting of a `Chatlog`,
-    // an `ApiEndpointConifg` and a system message in `String` form.
+    // an `ApiEndpointConifg` and a system message in `String` form. chatlogs saved in an
+    // older format are upgraded first.
     pub fn from_json(
         json_str: &str,
         response_generator: fn(),
-    ) -> Result<(Self, ApiEndpointConfig, String), serde_json::Error> {
-        let json_log: JSONChatlog = serde_json::from_str(json_str)?;
+    ) -> Result<(Self, ApiEndpointConfig, String), SchemaError> {
+        let json_log = read_json_chatlog(json_str)?;
 
-        let next_id = json_log
-            .messages
-            .iter()
-            .map(|msg| msg.id)
-            .max()
-            .map_or(1, |max_id| max_id + 1);
+        let next_id = max_message_id(&json_log.messages).map_or(1, |max_id| max_id + 1);
         Ok((
             Self {
                 next_id: create_signal(next_id),
                 messages: create_signal(json_log.messages),
                 response_generator,
-                is_regenerating_msg: create_signal(false),
+                regenerating_msg: create_signal(None),
+                is_generating_image: create_signal(false),
+                keep_reasoning: create_signal(json_log.keep_reasoning),
+                usage: create_signal(json_log.usage),
+                queued_messages: create_signal(json_log.queued_messages),
+                documents: create_signal(json_log.documents),
+                history: create_signal(MessageHistory::default()),
             },
             json_log.api_settings,
             json_log.system_message,
@@ -175,10 +519,14 @@ impl Chatlog {
     ) -> Result<String, serde_json::Error> {
         let messages = self.messages.get_clone();
         let json_log = JSONChatlog {
-            version: 1,
+            version: schema::CHATLOG_VERSION,
             messages,
             system_message,
             api_settings,
+            keep_reasoning: self.keep_reasoning.get(),
+            usage: self.usage.get(),
+            queued_messages: self.queued_messages.get_clone(),
+            documents: self.documents.get_clone(),
         };
         serde_json::to_string(&json_log)
     }
@@ -186,15 +534,24 @@ impl Chatlog {
     pub fn clone_from(&mut self, other: &Self) {
         self.next_id.set(other.next_id.get_clone_untracked());
         self.messages.set(other.messages.get_clone_untracked());
-        self.is_regenerating_msg
-            .set(other.is_regenerating_msg.get_clone_untracked());
+        self.regenerating_msg
+            .set(other.regenerating_msg.get_untracked());
+        self.is_generating_image
+            .set(other.is_generating_image.get_clone_untracked());
+        self.keep_reasoning
+            .set(other.keep_reasoning.get_clone_untracked());
+        self.usage.set(other.usage.get_untracked());
+        self.queued_messages
+            .set(other.queued_messages.get_clone_untracked());
+        self.documents.set(other.documents.get_clone_untracked());
+        self.history.set(other.history.get_clone_untracked());
         self.response_generator = other.response_generator;
     }
 
     // returns the internal signal for the messages, which can be
     // useful to mark closures for tracking.
     pub fn get_messages_signal(&self) -> Signal<Vec<Message>> {
-        return self.messages;
+        self.messages
     }
 
     pub fn track_message(&self, msg_id: u32) -> Signal<Option<Message>> {
@@ -212,62 +569,112 @@ impl Chatlog {
 
     // adds a new `Message` to the chatlog and generates a new id for it.
     // `ai_gen` should be set to `false` if this message was human generated.
-    // `image_base64` is an optional base64 encoded string for an image.
-    pub fn add_message(&mut self, new_msg: String, ai_gen: bool, image_base64: Option<String>) {
-        self.messages.update(|msgs| {
-            let new_id = self.get_next_id();
+    // `images` are base64 encoded data URLs of the images attached to the message.
+    pub fn add_message(&mut self, new_msg: String, ai_gen: bool, images: Vec<String>) {
+        self.add_message_with_files(new_msg, ai_gen, images, Vec::new());
+    }
+
+    // adds a new `Message` like `add_message()` does, along with the attached text files.
+    pub fn add_message_with_files(
+        &mut self,
+        new_msg: String,
+        ai_gen: bool,
+        images: Vec<String>,
+        files: Vec<FileAttachment>,
+    ) {
+        let new_id = self.get_next_id();
+        self.change_messages(|msgs| {
             msgs.push(Message {
                 id: new_id,
                 ai_generated: ai_gen,
                 message_stack: vec![StackedMessage {
                     message: new_msg,
-                    image_base64,
+                    images,
+                    files,
+                    ..Default::default()
                 }],
                 selected_message: 0,
             })
         });
     }
 
-    // pushes a new StackedMessage to the message stack for the specified message ID
+    // pushes a new StackedMessage to the message stack for the specified message ID and
+    // selects it. the new variant starts a new branch; the messages that followed the
+    // previously selected variant are kept in that variant's branch. returns false,
+    // changing nothing, if the message is gone.
     pub fn push_to_message_stack(
         &mut self,
         msg_id: u32,
         new_msg: String,
-        image_base64: Option<String>,
-    ) {
-        self.messages.update(|msgs| {
-            if let Some(msg) = msgs.iter_mut().find(|m| m.id == msg_id) {
-                msg.message_stack.push(StackedMessage {
+        images: Vec<String>,
+    ) -> bool {
+        let mut found = false;
+        self.change_messages(|msgs| {
+            if let Some(index) = msgs.iter().position(|m| m.id == msg_id) {
+                msgs[index].message_stack.push(StackedMessage {
                     message: new_msg,
-                    image_base64,
+                    images,
+                    ..Default::default()
                 });
-                msg.selected_message = msg.message_stack.len() - 1;
+                let new_variant = msgs[index].message_stack.len() - 1;
+                select_variant(msgs, index, new_variant);
+                found = true;
             }
         });
+        found
     }
 
     // gets the message in the chat log for a given id and returns it or
     // `None` if the id isn't found.
     pub fn get_message(&self, id: u32) -> Option<Message> {
-        if let Some(msg) = self.messages.get_clone().iter().find(|msg| msg.id == id) {
-            Some(msg.clone())
-        } else {
-            None
+        self.messages
+            .get_clone()
+            .iter()
+            .find(|msg| msg.id == id)
+            .cloned()
+    }
+
+    // returns the messages a response is generated from: all of them for a new response,
+    // or the ones before the message that's being regenerated. returns `None` if that
+    // message is gone.
+    pub fn response_context(&self) -> Option<Vec<Message>> {
+        let mut messages = self.messages.get_clone_untracked();
+        if let Some(msg_id) = self.regenerating_msg.get_untracked() {
+            let index = messages.iter().position(|m| m.id == msg_id)?;
+            messages.truncate(index);
         }
+        Some(messages)
     }
 
     // removes the `Message` with the matching id *and* all `Message` objects that come after it.
     pub fn purge_messages(&mut self, id: u32) {
-        self.messages.update(|msgs| {
+        self.change_messages(|msgs| {
             if let Some(index) = msgs.iter().position(|msg| msg.id == id) {
                 msgs.truncate(index);
             }
         });
     }
 
+    // returns how many messages in the branches of other variants go along with the
+    // `Message` with the matching id when it's removed, or purged if `with_following` is
+    // set. those branches hang off the removed messages, so they can't be kept.
+    pub fn hidden_messages_removed_with(&self, id: u32, with_following: bool) -> usize {
+        self.messages.with_untracked(|msgs| {
+            let Some(index) = msgs.iter().position(|msg| msg.id == id) else {
+                return 0;
+            };
+            let end = if with_following {
+                msgs.len()
+            } else {
+                index + 1
+            };
+            msgs[index..end].iter().map(hidden_message_count).sum()
+        })
+    }
+
     // removes the `Message` with the matching id.
     pub fn remove_message(&mut self, id: u32) {
-        self.messages.update(|msgs| {
+        self.change_messages(|msgs| {
             if let Some(index) = msgs.iter().position(|msg| msg.id == id) {
                 let _message = msgs.remove(index);
             }
@@ -285,33 +692,123 @@ impl Chatlog {
 
     // updates the message text and image data for the currently selected `StackedMessage` in
     // the `Message` with a matching id.
-    pub fn update_msg(&mut self, id: u32, new_msg: String, image_base64: Option<String>) {
-        self.messages.update(|msgs| {
+    pub fn update_msg(&mut self, id: u32, new_msg: String, images: Vec<String>) {
+        self.change_messages(|msgs| {
             if let Some(msg) = msgs.iter_mut().find(|msg| msg.id == id) {
+                let selected = msg.get_selected_message().unwrap_or_default();
                 msg.set_selected_message(StackedMessage {
                     message: new_msg,
-                    image_base64: image_base64,
+                    images,
+                    ..selected
                 });
             }
         });
     }
 
     // updates the selected index, which represents the `StackedMessage` that should be presented
-    // to the user by default for this `Message`.
+    // to the user by default for this `Message`. the messages after it switch over to the
+    // branch of the newly selected variant.
     pub fn update_selected_index(&mut self, msg_id: u32, delta: i16) {
-        self.messages.update(|msgs| {
-            if let Some(m) = msgs.iter_mut().find(|m| m.id == msg_id) {
-                let len = m.message_stack.len();
+        self.change_messages(|msgs| {
+            if let Some(index) = msgs.iter().position(|m| m.id == msg_id) {
+                let len = msgs[index].message_stack.len();
                 if len == 0 {
                     return;
                 }
-                let current = m.selected_message as i16;
+                let current = msgs[index].selected_message as i16;
                 let new_idx = (current + delta).clamp(0, len as i16 - 1) as usize;
-                m.selected_message = new_idx;
+                select_variant(msgs, index, new_idx);
             }
         })
     }
 
+    // records the document chunks that were sent along to generate the selected variant
+    // of the message with the matching id.
+    pub fn set_citations(&mut self, id: u32, citations: Vec<Citation>) {
+        self.messages.update(|msgs| {
+            if let Some(stacked_msg) = msgs
+                .iter_mut()
+                .find(|msg| msg.id == id)
+                .and_then(|msg| msg.message_stack.get_mut(msg.selected_message))
+            {
+                stacked_msg.citations = citations;
+            }
+        });
+    }
+
+    // attaches a document to the chatlog, replacing an earlier one with the same name.
+    pub fn attach_document(&self, document: AttachedDocument) {
+        self.documents.update(|documents| {
+            match documents.iter_mut().find(|d| d.name == document.name) {
+                Some(existing) => *existing = document,
+                None => documents.push(document),
+            }
+        });
+    }
+
+    // removes the attached document at the given index.
+    pub fn remove_document(&self, index: usize) {
+        self.documents.update(|documents| {
+            if index < documents.len() {
+                documents.remove(index);
+            }
+        });
+    }
+
+    // adds a user message to the queue of messages waiting for the pending response.
+    // `wants_image` marks the message as the prompt of an image to generate.
+    pub fn queue_message(
+        &self,
+        new_msg: String,
+        images: Vec<String>,
+        files: Vec<FileAttachment>,
+        wants_image: bool,
+    ) {
+        self.queued_messages.update(|queued| {
+            queued.push(QueuedMessage {
+                message: new_msg,
+                images,
+                files,
+                wants_image,
+            })
+        });
+    }
+
+    // removes the queued message at the given index so that it's never sent.
+    pub fn remove_queued_message(&self, index: usize) {
+        self.queued_messages.update(|queued| {
+            if index < queued.len() {
+                queued.remove(index);
+            }
+        });
+    }
+
+    // moves queued messages into the chatlog as user messages; either all of them or just
+    // the oldest one. the prompt of an image is always moved on its own, and the messages
+    // after it wait for the image. `is_generating_image` is set to whether the reply to
+    // the moved messages is an image. returns true if any message was moved.
+    pub fn dequeue_messages(&mut self, all: bool) -> bool {
+        let mut queued = self.queued_messages.get_clone_untracked();
+        let Some(wants_image) = queued.first().map(|msg| msg.wants_image) else {
+            return false;
+        };
+        let count = if all && !wants_image {
+            queued
+                .iter()
+                .position(|msg| msg.wants_image)
+                .unwrap_or(queued.len())
+        } else {
+            1
+        };
+        let rest = queued.split_off(count);
+        self.queued_messages.set(rest);
+        for msg in queued {
+            self.add_message_with_files(msg.message, false, msg.images, msg.files);
+        }
+        self.is_generating_image.set(wants_image);
+        true
+    }
+
     // call this to invoke a text generation request to create a `Message` response.
     // the `response_generator` member must be set to the function implementing
     // the response generation.
@@ -319,6 +816,61 @@ impl Chatlog {
         (self.response_generator)();
     }
 
+    // returns a new chatlog with the messages up to and including the one with the id, or
+    // `None` if there's no such message. the branches of the other variants are left out,
+    // and so is the usage, which stays with this chatlog.
+    pub fn fork(&self, msg_id: u32) -> Option<Self> {
+        let mut messages = self.messages.get_clone_untracked();
+        let index = messages.iter().position(|m| m.id == msg_id)?;
+        messages.truncate(index + 1);
+        for variant in messages.iter_mut().flat_map(|m| m.message_stack.iter_mut()) {
+            variant.continuation.clear();
+        }
+
+        let fork = Self::new(self.response_generator);
+        fork.next_id.set(self.next_id.get_untracked());
+        fork.messages.set(messages);
+        fork.keep_reasoning.set(self.keep_reasoning.get_untracked());
+        fork.documents.set(self.documents.get_clone_untracked());
+        Some(fork)
+    }
+
+    // restores the messages to how they were before the latest change. returns false if
+    // there's nothing to undo.
+    pub fn undo(&self) -> bool {
+        let current = self.messages.get_clone_untracked();
+        match self.history.update(|history| history.undo(current)) {
+            Some(previous) => {
+                self.messages.set(previous);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // brings back the change that was undone last. returns false if there's nothing to redo.
+    pub fn redo(&self) -> bool {
+        let current = self.messages.get_clone_untracked();
+        match self.history.update(|history| history.redo(current)) {
+            Some(next) => {
+                self.messages.set(next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // internal helper function that changes the messages and records how they were before,
+    // so that the change can be undone. changes that leave the messages as they were
+    // aren't recorded.
+    fn change_messages(&self, change: impl FnOnce(&mut Vec<Message>)) {
+        let before = self.messages.get_clone_untracked();
+        self.messages.update(change);
+        if self.messages.with_untracked(|msgs| *msgs != before) {
+            self.history.update(|history| history.record(before));
+        }
+    }
+
     // internal helper function to generate the next id for messages.
     fn get_next_id(&mut self) -> u32 {
         let id = self.next_id.get();
@@ -326,3 +878,404 @@ impl Chatlog {
         id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(ids: &[u32]) -> Vec<Message> {
+        ids.iter()
+            .map(|&id| Message {
+                id,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changes_are_undone_and_redone() {
+        let mut history = MessageHistory::default();
+        history.record(messages(&[]));
+        history.record(messages(&[1]));
+        let current = messages(&[1, 2]);
+
+        let previous = history.undo(current.clone()).unwrap();
+        assert_eq!(previous, messages(&[1]));
+        assert!(history.can_redo());
+        assert_eq!(history.redo(previous), Some(current.clone()));
+        assert!(!history.can_redo());
+
+        // a new change forgets what was undone
+        let previous = history.undo(current).unwrap();
+        history.record(previous);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn only_the_latest_changes_are_kept() {
+        let mut history = MessageHistory::default();
+        for id in 0..MAX_UNDO_STEPS as u32 + 10 {
+            history.record(messages(&[id]));
+        }
+        let mut current = Vec::new();
+        let mut steps = 0;
+        while let Some(previous) = history.undo(current.clone()) {
+            current = previous;
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_UNDO_STEPS);
+        assert_eq!(current, messages(&[10]));
+    }
+
+    // a state of a single message whose image takes a quarter of the memory budget
+    fn large_state(id: u32) -> Vec<Message> {
+        vec![Message {
+            id,
+            message_stack: vec![StackedMessage {
+                images: vec!["a".repeat(MAX_UNDO_BYTES / 4)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]
+    }
+
+    #[test]
+    fn large_changes_are_forgotten_sooner() {
+        let mut history = MessageHistory::default();
+        for id in 0..6 {
+            history.record(large_state(id));
+        }
+        let mut current = Vec::new();
+        let mut steps = 0;
+        while let Some(previous) = history.undo(current.clone()) {
+            current = previous;
+            steps += 1;
+        }
+        assert_eq!(steps, 4);
+        assert_eq!(current, large_state(2));
+    }
+
+    #[test]
+    fn the_latest_change_is_kept_however_large() {
+        let mut history = MessageHistory::default();
+        history.record(messages(&[1]));
+        let mut huge = large_state(2);
+        huge[0].message_stack[0]
+            .images
+            .push("a".repeat(MAX_UNDO_BYTES));
+        history.record(huge.clone());
+
+        assert_eq!(history.undo(Vec::new()), Some(huge));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn every_variant_keeps_its_own_branch() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("Name a color.".to_string(), false, Vec::new());
+            log.add_message("Red.".to_string(), true, Vec::new());
+            log.add_message("Why red?".to_string(), false, Vec::new());
+            log.add_message("It's warm.".to_string(), true, Vec::new());
+
+            // regenerating the second message starts a new branch after it
+            assert!(log.push_to_message_stack(2, "Blue.".to_string(), Vec::new()));
+            assert_eq!(log.messages.with(Vec::len), 2);
+            log.add_message("Why blue?".to_string(), false, Vec::new());
+
+            let msg = log.get_message(2).unwrap();
+            assert_eq!(msg.branch_lengths(1), vec![2, 1]);
+
+            // going back to the first variant brings back the messages that followed it
+            log.update_selected_index(2, -1);
+            let texts = log.messages.with(|msgs| {
+                msgs.iter()
+                    .map(|m| m.get_selected_message().unwrap().message)
+                    .collect::<Vec<_>>()
+            });
+            assert_eq!(texts, ["Name a color.", "Red.", "Why red?", "It's warm."]);
+
+            log.update_selected_index(2, 1);
+            assert_eq!(log.messages.with(|msgs| msgs[2].id), 5);
+        });
+    }
+
+    #[test]
+    fn removing_a_message_reports_the_branches_that_go_with_it() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("Name a color.".to_string(), false, Vec::new());
+            log.add_message("Red.".to_string(), true, Vec::new());
+            log.add_message("Why red?".to_string(), false, Vec::new());
+            log.add_message("It's warm.".to_string(), true, Vec::new());
+            log.push_to_message_stack(2, "Blue.".to_string(), Vec::new());
+            log.add_message("Why blue?".to_string(), false, Vec::new());
+
+            // the first variant's branch hangs off the second message
+            assert_eq!(log.hidden_messages_removed_with(1, false), 0);
+            assert_eq!(log.hidden_messages_removed_with(1, true), 2);
+            assert_eq!(log.hidden_messages_removed_with(2, false), 2);
+            assert_eq!(log.hidden_messages_removed_with(5, true), 0);
+
+            // a branch within a branch is counted as well
+            log.update_selected_index(2, -1);
+            log.add_message("Why warm?".to_string(), false, Vec::new());
+            assert!(log.push_to_message_stack(4, "It's calm.".to_string(), Vec::new()));
+            assert_eq!(log.hidden_messages_removed_with(2, false), 1);
+            assert_eq!(log.hidden_messages_removed_with(2, true), 2);
+            log.update_selected_index(2, 1);
+            assert_eq!(log.hidden_messages_removed_with(2, false), 3);
+
+            log.remove_message(2);
+            let ids = log
+                .messages
+                .with(|msgs| msgs.iter().map(|m| m.id).collect::<Vec<_>>());
+            assert_eq!(ids, [1, 5]);
+            assert_eq!(log.hidden_messages_removed_with(1, true), 0);
+        });
+    }
+
+    #[test]
+    fn ids_continue_after_the_other_branches() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("a".to_string(), false, Vec::new());
+            log.add_message("b".to_string(), true, Vec::new());
+            log.add_message("c".to_string(), false, Vec::new());
+            log.push_to_message_stack(2, "b2".to_string(), Vec::new());
+
+            let json = log
+                .to_json(ApiEndpointConfig::default(), String::new())
+                .unwrap();
+            let (mut loaded, _, _) = Chatlog::from_json(&json, || {}).unwrap();
+            loaded.add_message("d".to_string(), false, Vec::new());
+            assert_eq!(loaded.messages.with(|msgs| msgs[2].id), 4);
+        });
+    }
+
+    #[test]
+    fn forks_keep_the_messages_up_to_the_fork() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("a".to_string(), false, Vec::new());
+            log.add_message("b".to_string(), true, Vec::new());
+            log.add_message("c".to_string(), false, Vec::new());
+            log.push_to_message_stack(2, "b2".to_string(), Vec::new());
+            log.update_selected_index(2, -1);
+
+            let mut fork = log.fork(2).unwrap();
+            let msgs = fork.messages.get_clone();
+            assert_eq!(msgs.len(), 2);
+            assert!(
+                msgs[1]
+                    .message_stack
+                    .iter()
+                    .all(|v| v.continuation.is_empty())
+            );
+            fork.add_message("d".to_string(), false, Vec::new());
+            assert_eq!(fork.messages.with(|msgs| msgs[2].id), 4);
+
+            assert!(log.fork(42).is_none());
+            assert_eq!(log.messages.with(Vec::len), 3);
+        });
+    }
+
+    #[test]
+    fn forks_are_listed_after_their_parent() {
+        let entry = |id: &str, time: i64, parent: Option<&str>| ChatLogMetadataEntry {
+            id: id.to_string(),
+            last_accessed_time: time,
+            forked_from: parent.map(|parent| ForkOrigin {
+                parent_id: Some(parent.to_string()),
+                message_id: 1,
+            }),
+            ..Default::default()
+        };
+        let metadata = ChatLogMetadata {
+            saved_logs: vec![
+                entry("fork", 5, Some("parent")),
+                entry("parent", 1, None),
+                entry("other", 3, None),
+                entry("fork of fork", 2, Some("fork")),
+                entry("orphan", 4, Some("deleted")),
+            ],
+            ..Default::default()
+        };
+        let grouped = metadata
+            .grouped_by_fork()
+            .into_iter()
+            .map(|(depth, e)| (depth, e.id))
+            .collect::<Vec<_>>();
+        let expected = [
+            (0, "orphan"),
+            (0, "other"),
+            (0, "parent"),
+            (1, "fork"),
+            (2, "fork of fork"),
+        ]
+        .map(|(depth, id)| (depth, id.to_string()));
+        assert_eq!(grouped, expected);
+    }
+
+    #[test]
+    fn forks_are_only_traced_back_to_a_saved_chatlog_showing_them() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("Name a color.".to_string(), false, Vec::new());
+            log.add_message("Red.".to_string(), true, Vec::new());
+            log.add_message("Why red?".to_string(), false, Vec::new());
+            let saved = log
+                .to_json(ApiEndpointConfig::default(), String::new())
+                .unwrap();
+            let fork_is_shown = |log: &Chatlog, msg_id: u32, saved: &str| {
+                let fork = log.fork(msg_id).unwrap();
+                saved_chatlog_shows(saved, &fork.messages.get_clone())
+            };
+            assert!(fork_is_shown(&log, 2, &saved));
+            assert!(fork_is_shown(&log, 3, &saved));
+
+            // changes since the save, whether edits or new variants, aren't in it
+            log.update_msg(1, "Name a shape.".to_string(), Vec::new());
+            assert!(!fork_is_shown(&log, 2, &saved));
+            log.update_msg(1, "Name a color.".to_string(), Vec::new());
+            log.push_to_message_stack(2, "Blue.".to_string(), Vec::new());
+            assert!(!fork_is_shown(&log, 2, &saved));
+            log.add_message("Why blue?".to_string(), false, Vec::new());
+            let last_id = log.messages.with(|msgs| msgs[2].id);
+            assert!(!fork_is_shown(&log, last_id, &saved));
+            assert!(!fork_is_shown(&log, 1, "not json"));
+        });
+    }
+
+    #[test]
+    fn any_message_can_be_regenerated() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("Name a color.".to_string(), false, Vec::new());
+            log.add_message("Red.".to_string(), true, Vec::new());
+            log.add_message("Why red?".to_string(), false, Vec::new());
+            log.add_message("It's warm.".to_string(), true, Vec::new());
+            assert_eq!(log.response_context().map(|m| m.len()), Some(4));
+
+            // the earlier response is regenerated from the message before it
+            log.regenerating_msg.set(Some(2));
+            let context = log.response_context().unwrap();
+            assert_eq!(context.iter().map(|m| m.id).collect::<Vec<_>>(), [1]);
+
+            // and the messages after it stay with the variant they followed
+            assert!(log.push_to_message_stack(2, "Blue.".to_string(), Vec::new()));
+            assert_eq!(log.messages.with(Vec::len), 2);
+            assert_eq!(log.get_message(2).unwrap().branch_lengths(0), vec![2, 0]);
+
+            log.regenerating_msg.set(Some(42));
+            assert_eq!(log.response_context(), None);
+        });
+    }
+
+    #[test]
+    fn a_response_for_a_deleted_message_is_reported() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("Name a color.".to_string(), false, Vec::new());
+            let messages = log.messages.get_clone();
+            let history = log.history.get_clone();
+
+            assert!(!log.push_to_message_stack(2, "Blue.".to_string(), Vec::new()));
+            assert_eq!(log.messages.get_clone(), messages);
+            assert_eq!(log.history.get_clone(), history);
+        });
+    }
+
+    #[test]
+    fn chatlog_edits_can_be_undone() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("hello".to_string(), false, Vec::new());
+            log.add_message("hi there".to_string(), true, Vec::new());
+            log.purge_messages(1);
+            assert!(log.messages.with(Vec::is_empty));
+
+            assert!(log.undo());
+            assert_eq!(log.messages.with(Vec::len), 2);
+            assert!(log.redo());
+            assert!(log.messages.with(Vec::is_empty));
+
+            // switching to a variant that doesn't exist changes nothing, so it isn't recorded
+            assert!(log.undo());
+            log.update_selected_index(2, 1);
+            assert!(log.history.with(|h| h.can_redo()));
+        });
+    }
+
+    #[test]
+    fn queued_image_prompts_are_sent_on_their_own() {
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.queue_message("hello".to_string(), Vec::new(), Vec::new(), false);
+            log.queue_message("a red fox".to_string(), Vec::new(), Vec::new(), true);
+            log.queue_message("thanks".to_string(), Vec::new(), Vec::new(), false);
+
+            // the chat message goes first, and the image prompt waits for its reply
+            assert!(log.dequeue_messages(true));
+            assert_eq!(log.messages.with(Vec::len), 1);
+            assert!(!log.is_generating_image.get());
+
+            // then the prompt is sent for an image, without the message after it
+            assert!(log.dequeue_messages(true));
+            let prompt = log.get_message(2).unwrap().get_selected_message().unwrap();
+            assert_eq!(prompt.message, "a red fox");
+            assert!(log.is_generating_image.get());
+            assert_eq!(log.queued_messages.with(Vec::len), 1);
+
+            assert!(log.dequeue_messages(true));
+            assert!(!log.is_generating_image.get());
+            assert!(!log.dequeue_messages(true));
+        });
+    }
+
+    fn file(name: &str, content: &str) -> FileAttachment {
+        FileAttachment {
+            name: name.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn files_are_fenced_with_their_language() {
+        assert_eq!(
+            file("Main.RS", "fn main() {}\n\n").to_fenced_block(),
+            "**Main.RS**\n```rs\nfn main() {}\n```"
+        );
+        assert_eq!(
+            file("notes", "plain").to_fenced_block(),
+            "**notes**\n```\nplain\n```"
+        );
+    }
+
+    #[test]
+    fn fences_are_longer_than_any_backticks_inside() {
+        let readme = file("README.md", "Run `cargo test`:\n\n```sh\ncargo test\n```");
+        assert_eq!(
+            readme.to_fenced_block(),
+            "**README.md**\n````md\nRun `cargo test`:\n\n```sh\ncargo test\n```\n````"
+        );
+
+        let block = file("fence.md", "`````").to_fenced_block();
+        assert!(block.starts_with("**fence.md**\n``````md\n"));
+        assert!(block.ends_with("\n``````"));
+    }
+
+    #[test]
+    fn files_are_inlined_after_the_message() {
+        let files = [file("a.txt", "A"), file("b.txt", "B")];
+        assert_eq!(
+            inline_file_attachments("Compare these", &files),
+            "Compare these\n\n**a.txt**\n```txt\nA\n```\n\n**b.txt**\n```txt\nB\n```"
+        );
+        assert_eq!(
+            inline_file_attachments("", &files[..1]),
+            "**a.txt**\n```txt\nA\n```"
+        );
+        assert_eq!(inline_file_attachments("Just text", &[]), "Just text");
+    }
+}
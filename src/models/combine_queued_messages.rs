@@ -0,0 +1,16 @@
+use sycamore::prelude::*;
+
+// When true, all the messages queued while a response was pending are sent together
+// in one request. Otherwise each queued message gets its own reply, one turn at a time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CombineQueuedMessages(Signal<bool>);
+
+impl CombineQueuedMessages {
+    pub fn new(initial_value: bool) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<bool> {
+        self.0
+    }
+}
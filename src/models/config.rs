@@ -1,11 +1,220 @@
 use serde::{Deserialize, Serialize};
 
+use crate::storage::schema;
+
+// The price of a model in USD per million tokens.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+pub struct ModelPrice {
+    pub model_id: String,
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl ModelPrice {
+    // returns the cost in USD of the given number of tokens
+    pub fn cost_of(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt + completion_tokens as f64 * self.completion)
+            / 1_000_000.0
+    }
+}
+
+// Where the completion requests are sent.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ApiProvider {
+    #[default]
+    OpenAiCompatible, // a remote OpenAI compatible API endpoint
+    Mock, // the built-in offline mock provider
+}
+
+// How the mock provider comes up with the text of its replies.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum MockReplyMode {
+    #[default]
+    Echo, // repeats the last user message
+    LoremIpsum, // generates placeholder text
+    Scripted,   // cycles through the scripted replies
+}
+
+// The failure the mock provider can inject instead of replying.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum MockErrorKind {
+    #[default]
+    None,
+    Network,
+    Unauthorized,
+    RateLimited,
+    ServerError,
+    ContentFiltered,
+    ContextLengthExceeded,
+    MalformedResponse,
+    EmptyContent,
+}
+
+// A scripted reply for the mock provider. Scripts are loaded from JSON files holding
+// an array of these objects or an array of plain strings.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, Default)]
+#[serde(from = "MockReplyJson")]
+pub struct MockReply {
+    pub content: String,
+    pub reasoning: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MockReplyJson {
+    Text(String),
+    Full {
+        content: String,
+        #[serde(default)]
+        reasoning: Option<String>,
+    },
+}
+
+impl From<MockReplyJson> for MockReply {
+    fn from(json: MockReplyJson) -> Self {
+        match json {
+            MockReplyJson::Text(content) => Self {
+                content,
+                reasoning: None,
+            },
+            MockReplyJson::Full { content, reasoning } => Self { content, reasoning },
+        }
+    }
+}
+
+// Settings for the built-in mock provider, which replies without any network access.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[serde(default)]
+pub struct MockProviderConfig {
+    pub reply_mode: MockReplyMode,
+    pub scripted_replies: Vec<MockReply>,
+    pub latency_ms: Option<String>,
+    pub simulate_streaming: bool, // the reply is still shown once the whole stream arrived
+    pub include_thinking: bool,
+    pub reply_with_tool_calls: bool,
+    pub error_kind: MockErrorKind,
+    pub error_rate: Option<String>, // percent of requests that fail with `error_kind`
+}
+
+impl MockProviderConfig {
+    // returns the latency_ms string converted to u32 or a default value of 500
+    pub fn get_latency_ms(&self) -> u32 {
+        self.latency_ms
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(500)
+    }
+
+    // returns the error_rate string converted to f64 or a default value of 100.0
+    pub fn get_error_rate(&self) -> f64 {
+        self.error_rate
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(100.0)
+    }
+}
+
+// Settings for the speech-to-text endpoint used by the microphone button. An empty
+// endpoint or API key falls back to the one of the main configuration.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(default)]
+pub struct TranscriptionConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model_id: String,
+    pub language: Option<String>, // ISO-639-1 code; detected by the model when missing
+    pub auto_send: bool,          // send the message as soon as the transcript arrives
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            api_key: String::new(),
+            model_id: "whisper-1".to_string(),
+            language: None,
+            auto_send: false,
+        }
+    }
+}
+
+// Settings for the text-to-speech endpoint used to read messages aloud. An empty
+// endpoint or API key falls back to the one of the main configuration.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(default)]
+pub struct SpeechConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model_id: String,
+    pub voice: String,
+    pub auto_read: bool, // read every new AI response aloud as soon as it arrives
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            api_key: String::new(),
+            model_id: "tts-1".to_string(),
+            voice: "alloy".to_string(),
+            auto_read: false,
+        }
+    }
+}
+
+// Settings for the image generation endpoint used by the `/image` command. An empty
+// endpoint or API key falls back to the one of the main configuration.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(default)]
+pub struct ImageGenerationConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model_id: String,
+    pub size: String, // e.g. 1024x1024
+}
+
+impl Default for ImageGenerationConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            api_key: String::new(),
+            model_id: "dall-e-3".to_string(),
+            size: "1024x1024".to_string(),
+        }
+    }
+}
+
+// Settings for the embeddings endpoint used to search the saved chatlogs. An empty
+// endpoint or API key falls back to the one of the main configuration.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(default)]
+pub struct EmbeddingConfig {
+    pub enabled: bool, // index chatlogs as they are saved
+    pub endpoint: String,
+    pub api_key: String,
+    pub model_id: String,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            api_key: String::new(),
+            model_id: "text-embedding-3-small".to_string(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct ApiEndpointConfig {
+    pub version: u16,     // version of this settings format
     pub name: String,     // user friendly name
     pub endpoint: String, // API endpoint URL
     pub api_key: String,  // API key string
     pub model_id: String, // the model for the API to use
+    #[serde(default)]
+    pub provider: ApiProvider, // where requests are sent; the mock provider ignores the endpoint
 
     pub temperature: Option<String>,
     pub top_p: Option<String>,
@@ -15,15 +224,49 @@ pub struct ApiEndpointConfig {
 
     pub max_tokens: Option<String>,
     pub target_context_size: Option<String>,
+
+    #[serde(default)]
+    pub reasoning_effort: Option<String>, // 'low', 'medium' or 'high' for models that support it
+    #[serde(default)]
+    pub thinking_budget: Option<String>, // max tokens the model may spend on reasoning
+
+    #[serde(default)]
+    pub model_prices: Vec<ModelPrice>, // fetched from the `/models` listing or entered manually
+
+    // spending caps for this endpoint; requests are refused once one is reached
+    #[serde(default)]
+    pub daily_token_limit: Option<String>,
+    #[serde(default)]
+    pub monthly_token_limit: Option<String>,
+    #[serde(default)]
+    pub daily_cost_limit: Option<String>, // in USD
+    #[serde(default)]
+    pub monthly_cost_limit: Option<String>, // in USD
+    #[serde(default)]
+    pub limit_warning_threshold: Option<String>, // percent of a cap that triggers a warning
+
+    #[serde(default)]
+    pub mock: MockProviderConfig, // only used with the mock provider
+
+    #[serde(default)]
+    pub transcription: TranscriptionConfig, // speech-to-text for voice input
+    #[serde(default)]
+    pub speech: SpeechConfig, // text-to-speech for reading messages aloud
+    #[serde(default)]
+    pub image_generation: ImageGenerationConfig, // for the `/image` command
+    #[serde(default)]
+    pub embeddings: EmbeddingConfig, // for searching the saved chatlogs
 }
 
 impl Default for ApiEndpointConfig {
     fn default() -> Self {
         Self {
+            version: schema::CONFIG_VERSION,
             name: "Default".to_string(),
             endpoint: "https://openrouter.ai/api/v1".to_string(),
             api_key: String::new(),
             model_id: "google/gemma-3-27b-it:free".to_string(),
+            provider: ApiProvider::default(),
             temperature: None,
             top_p: None,
             top_k: None,
@@ -31,11 +274,42 @@ impl Default for ApiEndpointConfig {
             repetition_penalty: None,
             max_tokens: None,
             target_context_size: None,
+            reasoning_effort: None,
+            thinking_budget: None,
+            model_prices: Vec::new(),
+            daily_token_limit: None,
+            monthly_token_limit: None,
+            daily_cost_limit: None,
+            monthly_cost_limit: None,
+            limit_warning_threshold: None,
+            mock: MockProviderConfig::default(),
+            transcription: TranscriptionConfig::default(),
+            speech: SpeechConfig::default(),
+            image_generation: ImageGenerationConfig::default(),
+            embeddings: EmbeddingConfig::default(),
         }
     }
 }
 
 impl ApiEndpointConfig {
+    // returns the endpoint of an additional service, falling back to the main endpoint if empty
+    pub fn service_endpoint<'a>(&'a self, endpoint: &'a str) -> &'a str {
+        if endpoint.trim().is_empty() {
+            &self.endpoint
+        } else {
+            endpoint.trim()
+        }
+    }
+
+    // returns the API key of an additional service, falling back to the main API key if empty
+    pub fn service_api_key<'a>(&'a self, api_key: &'a str) -> &'a str {
+        if api_key.trim().is_empty() {
+            &self.api_key
+        } else {
+            api_key.trim()
+        }
+    }
+
     // returns the temperature string converted to f32 or a default value of 1.0
     pub fn get_temperature(&self) -> f32 {
         self.temperature
@@ -91,4 +365,74 @@ impl ApiEndpointConfig {
             .and_then(|s| s.parse().ok())
             .unwrap_or(4096)
     }
+
+    // returns the reasoning_effort string trimmed and lowercased, or `None` if it's empty
+    pub fn get_reasoning_effort(&self) -> Option<String> {
+        self.reasoning_effort
+            .as_ref()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+    }
+
+    // returns the thinking_budget string converted to u32 or a default value of 1024
+    pub fn get_thinking_budget(&self) -> u32 {
+        self.thinking_budget
+            .as_ref()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1024)
+    }
+
+    // returns the daily_token_limit string converted to u64, or `None` if there's no limit
+    pub fn get_daily_token_limit(&self) -> Option<u64> {
+        self.daily_token_limit
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    // returns the monthly_token_limit string converted to u64, or `None` if there's no limit
+    pub fn get_monthly_token_limit(&self) -> Option<u64> {
+        self.monthly_token_limit
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    // returns the daily_cost_limit string converted to f64, or `None` if there's no limit
+    pub fn get_daily_cost_limit(&self) -> Option<f64> {
+        self.daily_cost_limit
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    // returns the monthly_cost_limit string converted to f64, or `None` if there's no limit
+    pub fn get_monthly_cost_limit(&self) -> Option<f64> {
+        self.monthly_cost_limit
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    // returns the limit_warning_threshold string converted to f64 or a default value of 80.0
+    pub fn get_limit_warning_threshold(&self) -> f64 {
+        self.limit_warning_threshold
+            .as_ref()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(80.0)
+    }
+
+    // returns the price for the given model, if one is known
+    pub fn get_model_price(&self, model_id: &str) -> Option<&ModelPrice> {
+        self.model_prices.iter().find(|p| p.model_id == model_id)
+    }
+
+    // sets the price for the given model, replacing any existing one. passing `None`
+    // removes the price for the model.
+    pub fn set_model_price(&mut self, model_id: &str, price: Option<(f64, f64)>) {
+        self.model_prices.retain(|p| p.model_id != model_id);
+        if let Some((prompt, completion)) = price {
+            self.model_prices.push(ModelPrice {
+                model_id: model_id.to_string(),
+                prompt,
+                completion,
+            });
+        }
+    }
 }
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::search_index::split_into_chunks;
+use crate::api_endpoint::estimate_tokens;
+
+// Documents are split into chunks of about this many characters, on line breaks so that
+// code and tables aren't cut in the middle of a line.
+const MAX_CHUNK_CHARS: usize = 1500;
+
+// BM25 tuning parameters; the usual defaults.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+// at most this many chunks are added to a request
+const MAX_CONTEXT_CHUNKS: usize = 6;
+
+// the file extensions of the text-like documents that can be attached
+pub const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "csv", "tsv", "json", "jsonl", "yaml", "yml", "toml", "xml", "html",
+    "css", "log", "rs", "py", "js", "ts", "tsx", "jsx", "c", "h", "cpp", "hpp", "cs", "go", "java",
+    "kt", "rb", "php", "sh", "sql", "lua", "swift",
+];
+
+// returns true if the file looks like a text document that can be attached.
+pub fn is_supported_document(file_name: &str) -> bool {
+    file_name
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// A piece of an attached document. The vector is only present when the document was
+// embedded, and is encoded the same way as the chatlog search index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentChunk {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<String>,
+}
+
+// A text document attached to a chatlog. The most relevant chunks of it are added to
+// every request so that the model can answer questions about it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachedDocument {
+    pub name: String,
+    pub chunks: Vec<DocumentChunk>,
+    #[serde(default)]
+    pub model_id: String, // the embeddings model of the chunk vectors, if any
+}
+
+impl AttachedDocument {
+    // creates the document by splitting the text into chunks, without any vectors.
+    pub fn new(name: &str, text: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            chunks: split_into_chunks(text, "\n", MAX_CHUNK_CHARS)
+                .into_iter()
+                .map(|text| DocumentChunk { text, vector: None })
+                .collect(),
+            model_id: String::new(),
+        }
+    }
+
+    // returns true if every chunk has a vector from the given embeddings model.
+    pub fn is_embedded_with(&self, model_id: &str) -> bool {
+        self.model_id == model_id && self.chunks.iter().all(|c| c.vector.is_some())
+    }
+}
+
+// A chunk of an attached document that was added to a request, so the reply can show
+// which parts of the documents it was based on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Citation {
+    pub document: String,
+    pub chunk: usize, // 1-based index of the chunk within the document
+    pub text: String,
+}
+
+// lowercased alphanumeric terms of the text
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+// Scores every chunk of the documents against the query with BM25 and returns the
+// (document, chunk) indexes of the matching chunks, best match first.
+pub fn rank_chunks_by_keywords(documents: &[AttachedDocument], query: &str) -> Vec<(usize, usize)> {
+    let query_terms = tokenize(query);
+    if query_terms.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_terms: Vec<((usize, usize), Vec<String>)> = documents
+        .iter()
+        .enumerate()
+        .flat_map(|(d, doc)| {
+            doc.chunks
+                .iter()
+                .enumerate()
+                .map(move |(c, chunk)| ((d, c), tokenize(&chunk.text)))
+        })
+        .collect();
+    if chunk_terms.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_count = chunk_terms.len() as f32;
+    let average_len = chunk_terms.iter().map(|(_, t)| t.len()).sum::<usize>() as f32 / chunk_count;
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for term in &query_terms {
+        let count = chunk_terms
+            .iter()
+            .filter(|(_, terms)| terms.contains(term))
+            .count();
+        document_frequency.insert(term, count);
+    }
+
+    let mut scored: Vec<((usize, usize), f32)> = chunk_terms
+        .iter()
+        .map(|(position, terms)| {
+            let len_norm = 1.0 - BM25_B + BM25_B * terms.len() as f32 / average_len.max(1.0);
+            let score = query_terms
+                .iter()
+                .map(|term| {
+                    let frequency = terms.iter().filter(|t| *t == term).count() as f32;
+                    if frequency == 0.0 {
+                        return 0.0;
+                    }
+                    let df = document_frequency[term.as_str()] as f32;
+                    let idf = ((chunk_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    idf * frequency * (BM25_K1 + 1.0) / (frequency + BM25_K1 * len_norm)
+                })
+                .sum::<f32>();
+            (*position, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.into_iter().map(|(position, _)| position).collect()
+}
+
+// Orders the chunks of the documents by the similarity of their vectors to the query
+// vector, best match first. Chunks without a vector are left out.
+pub fn rank_chunks_by_vector(
+    documents: &[AttachedDocument],
+    query_vector: &[f32],
+) -> Vec<(usize, usize)> {
+    let mut scored: Vec<((usize, usize), f32)> = Vec::new();
+    for (d, doc) in documents.iter().enumerate() {
+        for (c, chunk) in doc.chunks.iter().enumerate() {
+            let Some(vector) = chunk
+                .vector
+                .as_deref()
+                .and_then(super::search_index::decode_vector)
+            else {
+                continue;
+            };
+            scored.push((
+                (d, c),
+                super::search_index::cosine_similarity(query_vector, &vector),
+            ));
+        }
+    }
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.into_iter().map(|(position, _)| position).collect()
+}
+
+// Takes the ranked chunks in order for as long as they fit into the token budget, up to
+// `MAX_CONTEXT_CHUNKS` of them.
+pub fn select_citations(
+    documents: &[AttachedDocument],
+    ranked: &[(usize, usize)],
+    token_budget: u32,
+) -> Vec<Citation> {
+    let mut remaining = token_budget;
+    let mut citations = Vec::new();
+    for &(d, c) in ranked {
+        if citations.len() >= MAX_CONTEXT_CHUNKS {
+            break;
+        }
+        let Some(chunk) = documents.get(d).and_then(|doc| doc.chunks.get(c)) else {
+            continue;
+        };
+        let tokens = estimate_tokens(&chunk.text);
+        if tokens > remaining {
+            continue;
+        }
+        remaining -= tokens;
+        citations.push(Citation {
+            document: documents[d].name.clone(),
+            chunk: c + 1,
+            text: chunk.text.clone(),
+        });
+    }
+    citations
+}
+
+// Builds the system message that hands the selected chunks to the model, numbered so
+// that the model can cite them.
+pub fn build_context_message(citations: &[Citation]) -> String {
+    let mut context = String::from(
+        "The following excerpts come from documents the user attached to this conversation. \
+         Use them when they are relevant to the question and cite them by their number, like [1].\n",
+    );
+    for (i, citation) in citations.iter().enumerate() {
+        context.push_str(&format!(
+            "\n[{}] {} (part {}):\n{}\n",
+            i + 1,
+            citation.document,
+            citation.chunk,
+            citation.text
+        ));
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(name: &str, chunks: &[&str]) -> AttachedDocument {
+        AttachedDocument {
+            name: name.to_string(),
+            chunks: chunks
+                .iter()
+                .map(|text| DocumentChunk {
+                    text: text.to_string(),
+                    vector: None,
+                })
+                .collect(),
+            model_id: String::new(),
+        }
+    }
+
+    fn library() -> Vec<AttachedDocument> {
+        vec![
+            document(
+                "garden.md",
+                &[
+                    "Tomatoes need full sun and regular watering.",
+                    "Prune the roses in early spring.",
+                ],
+            ),
+            document(
+                "kitchen.md",
+                &[
+                    "Tomato sauce: simmer the tomatoes, tomatoes and more tomatoes for an hour.",
+                    "Bread needs flour, water, salt and yeast.",
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn documents_are_chunked_on_lines() {
+        let line = "x".repeat(MAX_CHUNK_CHARS / 2);
+        let text = format!("    indented\n{}\n{}\n", line, line);
+        let document = AttachedDocument::new("notes.txt", &text);
+        let chunks: Vec<&str> = document.chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(chunks, [format!("    indented\n{}", line), line]);
+        assert!(!document.is_embedded_with(""));
+    }
+
+    #[test]
+    fn keyword_ranking_prefers_the_chunks_with_more_matches() {
+        let documents = library();
+        assert_eq!(
+            rank_chunks_by_keywords(&documents, "How long do TOMATOES simmer?"),
+            [(1, 0), (0, 0)]
+        );
+        assert_eq!(rank_chunks_by_keywords(&documents, "roses"), [(0, 1)]);
+    }
+
+    #[test]
+    fn keyword_ranking_needs_matching_terms() {
+        let documents = library();
+        assert!(rank_chunks_by_keywords(&documents, "?!").is_empty());
+        assert!(rank_chunks_by_keywords(&documents, "quantum physics").is_empty());
+        assert!(rank_chunks_by_keywords(&[], "tomatoes").is_empty());
+    }
+
+    #[test]
+    fn citations_are_selected_within_the_budget() {
+        let long = "word ".repeat(100);
+        let documents = [
+            document("long.txt", &[&long]),
+            document("short.txt", &["twelve chars", "sixteen chars!!!"]),
+        ];
+        assert_eq!(estimate_tokens(&long), 125);
+        let ranked = [(0, 0), (9, 9), (1, 0), (1, 1)];
+
+        // chunks that don't fit are skipped in favor of smaller ones further down
+        let citations = select_citations(&documents, &ranked, 7);
+        assert_eq!(
+            citations,
+            [
+                Citation {
+                    document: "short.txt".to_string(),
+                    chunk: 1,
+                    text: "twelve chars".to_string(),
+                },
+                Citation {
+                    document: "short.txt".to_string(),
+                    chunk: 2,
+                    text: "sixteen chars!!!".to_string(),
+                },
+            ]
+        );
+        assert_eq!(select_citations(&documents, &ranked, 6).len(), 1);
+        assert!(select_citations(&documents, &ranked, 0).is_empty());
+        assert_eq!(select_citations(&documents, &ranked, 132).len(), 3);
+    }
+
+    #[test]
+    fn only_a_few_citations_are_selected() {
+        let chunks: Vec<String> = (0..MAX_CONTEXT_CHUNKS + 3)
+            .map(|i| format!("chunk {}", i))
+            .collect();
+        let chunks: Vec<&str> = chunks.iter().map(String::as_str).collect();
+        let documents = [document("many.txt", &chunks)];
+        let ranked: Vec<(usize, usize)> = (0..chunks.len()).map(|c| (0, c)).collect();
+        assert_eq!(
+            select_citations(&documents, &ranked, u32::MAX).len(),
+            MAX_CONTEXT_CHUNKS
+        );
+    }
+
+    #[test]
+    fn the_context_message_numbers_the_citations() {
+        let citations = [
+            Citation {
+                document: "garden.md".to_string(),
+                chunk: 2,
+                text: "Prune the roses.".to_string(),
+            },
+            Citation {
+                document: "kitchen.md".to_string(),
+                chunk: 1,
+                text: "Simmer for an hour.".to_string(),
+            },
+        ];
+        let context = build_context_message(&citations);
+        assert!(context.starts_with("The following excerpts come from documents"));
+        assert!(context.ends_with(
+            "\n[1] garden.md (part 2):\nPrune the roses.\n\n[2] kitchen.md (part 1):\nSimmer for an hour.\n"
+        ));
+    }
+}
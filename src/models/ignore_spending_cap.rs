@@ -0,0 +1,23 @@
+use sycamore::prelude::*;
+
+// Set by the user to send the next request even though a spending cap has been
+// reached; it only applies to a single request and is cleared once used.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct IgnoreSpendingCap(Signal<bool>);
+
+impl IgnoreSpendingCap {
+    pub fn new(initial_value: bool) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<bool> {
+        self.0
+    }
+
+    // returns whether the cap should be ignored for this request and clears the flag
+    pub fn take(self) -> bool {
+        let ignore = self.0.get_untracked();
+        self.0.set(false);
+        ignore
+    }
+}
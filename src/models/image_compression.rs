@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use sycamore::prelude::*;
+
+// The formats attached images can be recompressed to.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ImageFormat {
+    #[default]
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+}
+
+// How images are downscaled and recompressed when they're attached to a message, to keep
+// the chatlogs small in the local storage and to save vision tokens.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct ImageCompressionSettings {
+    pub enabled: bool,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub format: ImageFormat,
+    pub quality: f64, // between 0.0 and 1.0
+}
+
+impl Default for ImageCompressionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_width: 1568,
+            max_height: 1568,
+            format: ImageFormat::Jpeg,
+            quality: 0.85,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ImageCompression(Signal<ImageCompressionSettings>);
+
+impl ImageCompression {
+    pub fn new(initial_value: ImageCompressionSettings) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<ImageCompressionSettings> {
+        self.0
+    }
+}
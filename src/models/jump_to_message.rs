@@ -0,0 +1,16 @@
+use sycamore::prelude::*;
+
+// The id of a message the chat interface should scroll to the next time it's shown,
+// instead of scrolling to the end. Set when a search result is opened.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct JumpToMessage(Signal<Option<u32>>);
+
+impl JumpToMessage {
+    pub fn new(initial_value: Option<u32>) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<Option<u32>> {
+        self.0
+    }
+}
@@ -0,0 +1,31 @@
+use sycamore::prelude::*;
+
+// The id of the saved chatlog the active chatlog was last loaded from or saved to, if any.
+// It's the slot that auto-saving keeps up to date.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LinkedChatlog(Signal<Option<String>>);
+
+impl LinkedChatlog {
+    pub fn new(initial_value: Option<String>) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<Option<String>> {
+        self.0
+    }
+
+    // returns true if the active chatlog is linked to the saved chatlog with the id.
+    pub fn is_linked_to(self, id: &str) -> bool {
+        self.0.with(|linked| linked.as_deref() == Some(id))
+    }
+
+    // forgets the link if it's to the saved chatlog with the id, e.g. after deleting it.
+    pub fn unlink_from(self, id: &str) {
+        if self
+            .0
+            .with_untracked(|linked| linked.as_deref() == Some(id))
+        {
+            self.0.set(None);
+        }
+    }
+}
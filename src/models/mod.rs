@@ -1,7 +1,21 @@
+pub mod app_scope;
+pub mod autosave_to_slot;
 pub mod chatlog;
+pub mod combine_queued_messages;
 pub mod config;
 pub mod dark_mode;
+pub mod documents;
+pub mod ignore_spending_cap;
+pub mod image_compression;
 pub mod is_editing_config;
 pub mod is_response_pending;
 pub mod is_saving_chatlog;
+pub mod jump_to_message;
+pub mod linked_chatlog;
+pub mod notifications;
+pub mod request_inspector;
+pub mod response_error;
+pub mod search_index;
+pub mod speech_player;
 pub mod system_message;
+pub mod usage;
@@ -0,0 +1,166 @@
+use std::rc::Rc;
+
+use sycamore::prelude::*;
+use web_sys::wasm_bindgen::{JsCast, closure::Closure};
+
+// how long, in milliseconds, notifications stay on screen before being dismissed.
+const NOTIFICATION_TIMEOUT_MS: i32 = 4000;
+const ERROR_NOTIFICATION_TIMEOUT_MS: i32 = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+// A single non-blocking message shown to the user in the corner of the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub id: u32,
+    pub kind: NotificationKind,
+    pub text: String,
+}
+
+// The list of notifications currently on screen. Each one is dismissed automatically
+// after a timeout or can be closed early by the user.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Notifications {
+    items: Signal<Vec<Notification>>,
+    next_id: Signal<u32>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Self {
+            items: create_signal(Vec::new()),
+            next_id: create_signal(1),
+        }
+    }
+
+    pub fn signal(self) -> Signal<Vec<Notification>> {
+        self.items
+    }
+
+    pub fn info(self, text: impl Into<String>) {
+        self.push(NotificationKind::Info, text.into(), NOTIFICATION_TIMEOUT_MS);
+    }
+
+    pub fn success(self, text: impl Into<String>) {
+        self.push(
+            NotificationKind::Success,
+            text.into(),
+            NOTIFICATION_TIMEOUT_MS,
+        );
+    }
+
+    pub fn warning(self, text: impl Into<String>) {
+        self.push(
+            NotificationKind::Warning,
+            text.into(),
+            ERROR_NOTIFICATION_TIMEOUT_MS,
+        );
+    }
+
+    pub fn error(self, text: impl Into<String>) {
+        self.push(
+            NotificationKind::Error,
+            text.into(),
+            ERROR_NOTIFICATION_TIMEOUT_MS,
+        );
+    }
+
+    // removes the notification with the matching id, if it's still shown.
+    pub fn dismiss(self, id: u32) {
+        self.items.update(|items| items.retain(|n| n.id != id));
+    }
+
+    fn push(self, kind: NotificationKind, text: String, timeout_ms: i32) {
+        let id = self.next_id.get_untracked();
+        self.next_id.set(id + 1);
+        self.items
+            .update(|items| items.push(Notification { id, kind, text }));
+
+        let on_timeout = Closure::once_into_js(move || self.dismiss(id));
+        if let Err(e) = window().set_timeout_with_callback_and_timeout_and_arguments_0(
+            on_timeout.unchecked_ref(),
+            timeout_ms,
+        ) {
+            console_log!("Failed to schedule the notification timeout: {:?}", e);
+        }
+    }
+}
+
+// A request for the user to confirm an action in the modal dialog. When `input` is
+// set, the dialog shows a text field starting with that value and its final text is
+// passed to `on_confirm`.
+#[derive(Clone)]
+pub struct ConfirmationRequest {
+    pub message: String,
+    pub confirm_label: String,
+    pub input: Option<String>,
+    pub on_confirm: Rc<dyn Fn(String)>,
+}
+
+// The modal confirmation dialog; at most one request is shown at a time and a new
+// request replaces any pending one.
+#[derive(Clone, Copy)]
+pub struct ConfirmationDialog(Signal<Option<ConfirmationRequest>>);
+
+impl Default for ConfirmationDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfirmationDialog {
+    pub fn new() -> Self {
+        Self(create_signal(None))
+    }
+
+    pub fn signal(self) -> Signal<Option<ConfirmationRequest>> {
+        self.0
+    }
+
+    // asks the user to confirm with a yes/no style dialog; `on_confirm` is only
+    // called if the user accepts.
+    pub fn confirm(
+        self,
+        message: impl Into<String>,
+        confirm_label: impl Into<String>,
+        on_confirm: impl Fn() + 'static,
+    ) {
+        self.0.set(Some(ConfirmationRequest {
+            message: message.into(),
+            confirm_label: confirm_label.into(),
+            input: None,
+            on_confirm: Rc::new(move |_| on_confirm()),
+        }));
+    }
+
+    // asks the user to enter some text; `on_submit` is only called if the user accepts.
+    pub fn prompt(
+        self,
+        message: impl Into<String>,
+        initial_value: impl Into<String>,
+        on_submit: impl Fn(String) + 'static,
+    ) {
+        self.0.set(Some(ConfirmationRequest {
+            message: message.into(),
+            confirm_label: "OK".to_string(),
+            input: Some(initial_value.into()),
+            on_confirm: Rc::new(on_submit),
+        }));
+    }
+
+    pub fn close(self) {
+        self.0.set(None);
+    }
+}
@@ -0,0 +1,87 @@
+use sycamore::prelude::*;
+
+// the number of exchanges kept by the request inspector
+pub const MAX_RECORDED_EXCHANGES: usize = 20;
+
+// the value the API key is replaced with in recorded headers
+pub const REDACTED_API_KEY: &str = "[REDACTED]";
+
+// A request sent to the API along with the raw response that came back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedExchange {
+    pub id: u32,
+    pub sent_at: String, // local time the request was sent
+    pub url: String,
+    pub request_headers: Vec<(String, String)>, // with the API key redacted
+    pub request_body: String,
+    pub status: Option<u16>, // None if no response was received
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: String,
+    pub error: Option<String>, // the network error if no response was received
+    pub elapsed_ms: u32,
+}
+
+impl RecordedExchange {
+    // builds a curl command line for the request. The API key is read from the
+    // `API_KEY` environment variable so it never ends up on the clipboard.
+    pub fn to_curl(&self) -> String {
+        let quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
+        let mut lines = vec![format!("curl {}", quote(&self.url))];
+        for (name, value) in self.request_headers.iter() {
+            if value.contains(REDACTED_API_KEY) {
+                lines.push(format!(
+                    "  -H \"{}: {}\"",
+                    name,
+                    value.replace(REDACTED_API_KEY, "$API_KEY")
+                ));
+            } else {
+                lines.push(format!("  -H {}", quote(&format!("{}: {}", name, value))));
+            }
+        }
+        lines.push(format!("  -d {}", quote(&self.request_body)));
+        lines.join(" \\\n")
+    }
+}
+
+/// The opt-in developer panel's record of the last few requests sent to the API.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RequestInspector {
+    enabled: Signal<bool>,
+    exchanges: Signal<Vec<RecordedExchange>>,
+    next_id: Signal<u32>,
+}
+
+impl RequestInspector {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: create_signal(enabled),
+            exchanges: create_signal(Vec::new()),
+            next_id: create_signal(0),
+        }
+    }
+
+    pub fn enabled(self) -> Signal<bool> {
+        self.enabled
+    }
+
+    pub fn exchanges(self) -> Signal<Vec<RecordedExchange>> {
+        self.exchanges
+    }
+
+    // keeps the exchange, newest first, if the inspector is enabled
+    pub fn record(self, mut exchange: RecordedExchange) {
+        if !self.enabled.get_untracked() {
+            return;
+        }
+        exchange.id = self.next_id.get_untracked();
+        self.next_id.set(exchange.id.wrapping_add(1));
+        self.exchanges.update(|exchanges| {
+            exchanges.insert(0, exchange);
+            exchanges.truncate(MAX_RECORDED_EXCHANGES);
+        });
+    }
+
+    pub fn clear(self) {
+        self.exchanges.set(Vec::new());
+    }
+}
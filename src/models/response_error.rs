@@ -0,0 +1,36 @@
+use sycamore::prelude::*;
+
+// The action offered to the user to recover from a failed response generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorRecovery {
+    Retry,      // send the same request again
+    OpenConfig, // adjust the API configuration
+    IgnoreCap,  // send the request even though a spending cap was reached
+    None,       // nothing to offer besides changing the chat itself
+}
+
+// Describes the last response generation that failed so that it can be shown
+// beneath the turn it was for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedResponse {
+    pub message: String,
+    pub recovery: ErrorRecovery,
+    pub regenerating: Option<u32>, // the message that was being regenerated, if any
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ResponseError(Signal<Option<FailedResponse>>);
+
+impl ResponseError {
+    pub fn new(initial_value: Option<FailedResponse>) -> Self {
+        Self(create_signal(initial_value))
+    }
+
+    pub fn signal(self) -> Signal<Option<FailedResponse>> {
+        self.0
+    }
+
+    pub fn clear(self) {
+        self.0.set(None);
+    }
+}
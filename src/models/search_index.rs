@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+
+use super::chatlog::{Message, parse_think_block};
+
+// Messages longer than this are split into several chunks, preferably on paragraph breaks.
+const MAX_CHUNK_CHARS: usize = 1200;
+
+// A piece of a saved message together with its embedding. The vector is normalized,
+// quantized to 8 bits and hex encoded to keep the storage footprint small.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    pub message_id: u32,
+    pub text: String,
+    pub vector: String,
+}
+
+// The search index of one saved chatlog. It's stored next to the chatlog under its own
+// key so that saving a chatlog only touches its own entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatlogIndex {
+    pub model_id: String, // the embeddings model the vectors came from
+    pub chunks: Vec<IndexedChunk>,
+}
+
+impl ChatlogIndex {
+    /// The prefix of the storage keys of the indexes.
+    pub const KEY_PREFIX: &str = "search_index_";
+
+    /// Returns the storage key of the index for the saved chatlog.
+    pub fn storage_key(chatlog_id: &str) -> String {
+        format!("{}{}", Self::KEY_PREFIX, chatlog_id)
+    }
+
+    // returns the vector already computed for this chunk of text, if any, so that only
+    // new or edited messages need to be embedded again.
+    pub fn find_vector(&self, message_id: u32, text: &str) -> Option<String> {
+        self.chunks
+            .iter()
+            .find(|c| c.message_id == message_id && c.text == text)
+            .map(|c| c.vector.clone())
+    }
+}
+
+// Splits the selected variant of every message into the chunks of text that get embedded.
+// The think block is left out since it isn't part of the conversation itself.
+pub fn chunk_messages(messages: &[Message]) -> Vec<(u32, String)> {
+    let mut chunks = Vec::new();
+    for msg in messages {
+        let Some(selected) = msg.get_selected_message() else {
+            continue;
+        };
+        let content = match parse_think_block(selected.message.clone()) {
+            Some((main_content, _)) => main_content,
+            None => selected.message,
+        };
+        for chunk in split_into_chunks(&content, "\n\n", MAX_CHUNK_CHARS) {
+            chunks.push((msg.id, chunk));
+        }
+    }
+    chunks
+}
+
+// Groups the pieces of the text between the separators into chunks of at most `max_chars`
+// characters, so that chunks only end where a piece does. A piece that is too long on its
+// own is split on character boundaries, and blank pieces are left out.
+pub fn split_into_chunks(text: &str, separator: &str, max_chars: usize) -> Vec<String> {
+    let separator_chars = separator.chars().count();
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for piece in text
+        .split(separator)
+        .map(str::trim_end)
+        .filter(|p| !p.trim().is_empty())
+    {
+        let piece_chars = piece.chars().count();
+        if current_chars > 0 && current_chars + separator_chars + piece_chars <= max_chars {
+            current.push_str(separator);
+            current.push_str(piece);
+            current_chars += separator_chars + piece_chars;
+            continue;
+        }
+
+        let chars: Vec<char> = piece.chars().collect();
+        for part in chars.chunks(max_chars) {
+            if current_chars > 0 {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current = part.iter().collect();
+            current_chars = part.len();
+        }
+    }
+    if current_chars > 0 {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// normalizes the embedding and quantizes each component to a signed byte, encoded as hex.
+pub fn encode_vector(vector: &[f32]) -> String {
+    let max_abs = vector.iter().fold(0.0f32, |acc, v| acc.max(v.abs()));
+    let scale = if max_abs > 0.0 { 127.0 / max_abs } else { 0.0 };
+    vector
+        .iter()
+        .map(|v| format!("{:02x}", (v * scale).round() as i8 as u8))
+        .collect()
+}
+
+// decodes a vector produced by `encode_vector`, returning `None` on malformed data.
+pub fn decode_vector(encoded: &str) -> Option<Vec<f32>> {
+    if !encoded.is_ascii() || !encoded.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..encoded.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&encoded[i..i + 2], 16)
+                .ok()
+                .map(|b| b as i8 as f32)
+        })
+        .collect()
+}
+
+// cosine similarity of two vectors; zero if they differ in length or either is empty.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::chatlog::StackedMessage;
+
+    fn message(id: u32, variants: &[&str], selected_message: usize) -> Message {
+        Message {
+            id,
+            message_stack: variants
+                .iter()
+                .map(|text| StackedMessage {
+                    message: text.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            selected_message,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn vectors_survive_encoding_up_to_their_scale() {
+        let vector = [0.5, -0.25, 0.0, 1.0, -1.0];
+        let encoded = encode_vector(&vector);
+        assert_eq!(encoded.len(), vector.len() * 2);
+        assert_eq!(encoded, "40e0007f81");
+
+        let decoded = decode_vector(&encoded).unwrap();
+        assert_eq!(decoded, [64.0, -32.0, 0.0, 127.0, -127.0]);
+        assert!((cosine_similarity(&vector, &decoded) - 1.0).abs() < 1e-4);
+
+        // a zero vector has nothing to scale
+        assert_eq!(encode_vector(&[0.0, 0.0]), "0000");
+    }
+
+    #[test]
+    fn malformed_vectors_are_rejected() {
+        assert_eq!(decode_vector("7"), None);
+        assert_eq!(decode_vector("zz"), None);
+        assert_eq!(decode_vector("é0"), None);
+        assert_eq!(decode_vector(""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn cosine_similarity_compares_directions() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 2.0]), 0.0);
+        assert_eq!(cosine_similarity(&[], &[]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 2.0]), 0.0);
+    }
+
+    #[test]
+    fn paragraphs_are_grouped_into_chunks() {
+        assert_eq!(
+            split_into_chunks("One.\n\n\n\nTwo.\n\n  ", "\n\n", MAX_CHUNK_CHARS),
+            ["One.\n\nTwo."]
+        );
+        assert!(split_into_chunks(" \n\n ", "\n\n", MAX_CHUNK_CHARS).is_empty());
+
+        let paragraph = "a".repeat(MAX_CHUNK_CHARS - 10);
+        let text = format!("{}\n\n{}", paragraph, "b".repeat(20));
+        assert_eq!(
+            split_into_chunks(&text, "\n\n", MAX_CHUNK_CHARS),
+            [paragraph, "b".repeat(20)]
+        );
+    }
+
+    #[test]
+    fn long_paragraphs_are_split_on_characters() {
+        let text = "é".repeat(MAX_CHUNK_CHARS * 2 + 5);
+        let chunks = split_into_chunks(&text, "\n\n", MAX_CHUNK_CHARS);
+        let lengths: Vec<usize> = chunks.iter().map(|c| c.chars().count()).collect();
+        assert_eq!(lengths, [MAX_CHUNK_CHARS, MAX_CHUNK_CHARS, 5]);
+    }
+
+    #[test]
+    fn only_the_selected_variants_are_chunked_without_thinking() {
+        let messages = [
+            message(1, &["First question"], 0),
+            message(
+                2,
+                &["Old answer", "<think>Reasoning</think>\nNew answer"],
+                1,
+            ),
+            message(3, &[], 0),
+        ];
+        assert_eq!(
+            chunk_messages(&messages),
+            [
+                (1, "First question".to_string()),
+                (2, "New answer".to_string())
+            ]
+        );
+    }
+}
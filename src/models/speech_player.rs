@@ -0,0 +1,84 @@
+use sycamore::prelude::*;
+use web_sys::js_sys::Function;
+use web_sys::wasm_bindgen::prelude::Closure;
+use web_sys::{Blob, HtmlAudioElement, Url};
+
+// Plays the audio of messages read aloud, one message at a time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SpeechPlayer {
+    audio: Signal<Option<(HtmlAudioElement, String)>>, // the element and its object URL
+    speaking_message: Signal<Option<u32>>,             // id of the message being read
+}
+
+impl Default for SpeechPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpeechPlayer {
+    pub fn new() -> Self {
+        Self {
+            audio: create_signal(None),
+            speaking_message: create_signal(None),
+        }
+    }
+
+    // the id of the message that is being read aloud or waiting on its audio
+    pub fn speaking_message(self) -> Signal<Option<u32>> {
+        self.speaking_message
+    }
+
+    // marks the message as being read while its audio is still being synthesized
+    pub fn prepare(self, msg_id: u32) {
+        self.stop();
+        self.speaking_message.set(Some(msg_id));
+    }
+
+    // plays the audio for the message, unless the reading was stopped in the meantime
+    pub fn play(self, msg_id: u32, audio_data: Blob) {
+        if self.speaking_message.get_untracked() != Some(msg_id) {
+            return;
+        }
+        let url = match Url::create_object_url_with_blob(&audio_data) {
+            Ok(url) => url,
+            Err(e) => {
+                console_log!("Failed to create a URL for the speech audio: {:?}", e);
+                self.stop();
+                return;
+            }
+        };
+        let audio = match HtmlAudioElement::new_with_src(&url) {
+            Ok(audio) => audio,
+            Err(e) => {
+                console_log!("Failed to create the audio element: {:?}", e);
+                let _ = Url::revoke_object_url(&url);
+                self.stop();
+                return;
+            }
+        };
+
+        let handle_ended_js: Function = Closure::wrap(Box::new(move || {
+            if self.speaking_message.get_untracked() == Some(msg_id) {
+                self.stop();
+            }
+        }) as Box<dyn FnMut()>)
+        .into_js_value()
+        .into();
+        audio.set_onended(Some(&handle_ended_js));
+        if let Err(e) = audio.play() {
+            console_log!("Failed to play the speech audio: {:?}", e);
+        }
+        self.audio.set(Some((audio, url)));
+    }
+
+    // stops any reading and releases its audio
+    pub fn stop(self) {
+        if let Some((audio, url)) = self.audio.get_clone_untracked() {
+            let _ = audio.pause();
+            let _ = Url::revoke_object_url(&url);
+        }
+        self.audio.set(None);
+        self.speaking_message.set(None);
+    }
+}
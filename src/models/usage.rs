@@ -0,0 +1,422 @@
+use serde::{Deserialize, Serialize};
+
+use super::config::{ApiEndpointConfig, ModelPrice};
+
+const USAGE_SUMMARY_VERSION: u16 = 1;
+
+// Token counts and the estimated cost of one or more completion requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64, // in USD, estimated from the price table at the time of the request
+}
+
+impl Usage {
+    // builds the usage for a single request from the reported token counts,
+    // pricing it with `price` if the model has one.
+    pub fn from_tokens(
+        prompt_tokens: Option<i64>,
+        completion_tokens: Option<i64>,
+        price: Option<&ModelPrice>,
+    ) -> Self {
+        let prompt_tokens = prompt_tokens.unwrap_or_default().max(0) as u64;
+        let completion_tokens = completion_tokens.unwrap_or_default().max(0) as u64;
+        let cost = price.map_or(0.0, |p| p.cost_of(prompt_tokens, completion_tokens));
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            cost,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_tokens() == 0 && self.cost == 0.0
+    }
+
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost += other.cost;
+    }
+
+    // returns a short human readable description of the usage
+    pub fn describe(&self) -> String {
+        format!(
+            "{} prompt + {} completion tokens, ${:.4}",
+            self.prompt_tokens, self.completion_tokens, self.cost
+        )
+    }
+}
+
+// The usage accumulated for one endpoint over one calendar month.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageSummaryEntry {
+    pub month: String,         // formatted as YYYY-MM
+    pub endpoint: String,      // API endpoint URL
+    pub endpoint_name: String, // user friendly name of the endpoint when last used
+    pub request_count: u32,
+    pub usage: Usage,
+}
+
+// The usage accumulated for one endpoint over a single day, used for the daily caps.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailyUsageEntry {
+    pub day: String,      // formatted as YYYY-MM-DD
+    pub endpoint: String, // API endpoint URL
+    pub usage: Usage,
+}
+
+// Represents the monthly usage for all endpoints that have been used, as well
+// as the usage of the current day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub version: u16,
+    pub entries: Vec<UsageSummaryEntry>,
+    #[serde(default)]
+    pub daily: Vec<DailyUsageEntry>,
+}
+
+impl Default for UsageSummary {
+    fn default() -> Self {
+        Self {
+            version: USAGE_SUMMARY_VERSION,
+            entries: Vec::new(),
+            daily: Vec::new(),
+        }
+    }
+}
+
+// The result of checking the usage of an endpoint against its spending caps.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpendingCapStatus {
+    WithinLimits,
+    Warning(String),  // a cap is past the warning threshold
+    Exceeded(String), // a cap has been reached
+}
+
+impl UsageSummary {
+    // adds the usage of a single request to the entry for the month and endpoint,
+    // creating the entry if needed.
+    // only the entries for `day` are kept for the daily usage.
+    pub fn record(
+        &mut self,
+        day: &str,
+        month: &str,
+        endpoint: &str,
+        endpoint_name: &str,
+        usage: &Usage,
+    ) {
+        let index = match self
+            .entries
+            .iter()
+            .position(|e| e.month == month && e.endpoint == endpoint)
+        {
+            Some(index) => index,
+            None => {
+                self.entries.push(UsageSummaryEntry {
+                    month: month.to_string(),
+                    endpoint: endpoint.to_string(),
+                    ..Default::default()
+                });
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[index];
+        entry.endpoint_name = endpoint_name.to_string();
+        entry.request_count += 1;
+        entry.usage.add(usage);
+
+        self.daily.retain(|d| d.day == day);
+        match self.daily.iter_mut().find(|d| d.endpoint == endpoint) {
+            Some(daily) => daily.usage.add(usage),
+            None => self.daily.push(DailyUsageEntry {
+                day: day.to_string(),
+                endpoint: endpoint.to_string(),
+                usage: *usage,
+            }),
+        }
+    }
+
+    // returns the usage of the endpoint for the given month
+    pub fn usage_for_month(&self, month: &str, endpoint: &str) -> Usage {
+        self.entries
+            .iter()
+            .find(|e| e.month == month && e.endpoint == endpoint)
+            .map(|e| e.usage)
+            .unwrap_or_default()
+    }
+
+    // returns the usage of the endpoint for the given day
+    pub fn usage_for_day(&self, day: &str, endpoint: &str) -> Usage {
+        self.daily
+            .iter()
+            .find(|d| d.day == day && d.endpoint == endpoint)
+            .map(|d| d.usage)
+            .unwrap_or_default()
+    }
+
+    // checks the usage of the configured endpoint against the daily and monthly caps
+    // set in its configuration, reporting the cap closest to being reached.
+    pub fn check_spending_caps(
+        &self,
+        config: &ApiEndpointConfig,
+        day: &str,
+        month: &str,
+    ) -> SpendingCapStatus {
+        let daily = self.usage_for_day(day, &config.endpoint);
+        let monthly = self.usage_for_month(month, &config.endpoint);
+        let caps = [
+            (
+                "daily token",
+                daily.total_tokens() as f64,
+                config.get_daily_token_limit().map(|l| l as f64),
+            ),
+            (
+                "monthly token",
+                monthly.total_tokens() as f64,
+                config.get_monthly_token_limit().map(|l| l as f64),
+            ),
+            ("daily cost", daily.cost, config.get_daily_cost_limit()),
+            (
+                "monthly cost",
+                monthly.cost,
+                config.get_monthly_cost_limit(),
+            ),
+        ];
+
+        let mut worst: Option<(&str, f64)> = None;
+        for (name, used, limit) in caps {
+            let Some(limit) = limit else {
+                continue;
+            };
+            let ratio = if limit > 0.0 { used / limit } else { 1.0 };
+            if worst.is_none_or(|(_, worst_ratio)| ratio > worst_ratio) {
+                worst = Some((name, ratio));
+            }
+        }
+
+        match worst {
+            Some((name, ratio)) if ratio >= 1.0 => SpendingCapStatus::Exceeded(format!(
+                "The {} cap for '{}' has been reached ({:.0}% used).",
+                name,
+                config.name,
+                ratio * 100.0
+            )),
+            Some((name, ratio)) if ratio * 100.0 >= config.get_limit_warning_threshold() => {
+                SpendingCapStatus::Warning(format!(
+                    "{:.0}% of the {} cap for '{}' has been used.",
+                    ratio * 100.0,
+                    name,
+                    config.name
+                ))
+            }
+            _ => SpendingCapStatus::WithinLimits,
+        }
+    }
+}
+
+// returns the current day formatted as YYYY-MM-DD, used to key the daily usage.
+pub fn current_day() -> String {
+    let now = web_sys::js_sys::Date::new_0();
+    format!(
+        "{:04}-{:02}-{:02}",
+        now.get_full_year(),
+        now.get_month() + 1,
+        now.get_date()
+    )
+}
+
+// returns the current month formatted as YYYY-MM, used to key the usage summary.
+pub fn current_month() -> String {
+    let now = web_sys::js_sys::Date::new_0();
+    format!("{:04}-{:02}", now.get_full_year(), now.get_month() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENDPOINT: &str = "https://example.com/v1";
+    const OTHER_ENDPOINT: &str = "http://localhost:8080/v1";
+
+    fn tokens(prompt_tokens: u64, completion_tokens: u64) -> Usage {
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            cost: 0.0,
+        }
+    }
+
+    #[test]
+    fn usage_is_priced_per_million_tokens() {
+        let price = ModelPrice {
+            model_id: "model".to_string(),
+            prompt: 2.0,
+            completion: 10.0,
+        };
+        let usage = Usage::from_tokens(Some(500_000), Some(100_000), Some(&price));
+        assert_eq!(usage.total_tokens(), 600_000);
+        assert!((usage.cost - 2.0).abs() < 1e-9);
+
+        // unknown prices cost nothing and missing or negative counts are zero
+        assert_eq!(Usage::from_tokens(Some(10), Some(20), None), tokens(10, 20));
+        let usage = Usage::from_tokens(None, Some(-5), Some(&price));
+        assert!(usage.is_empty());
+    }
+
+    #[test]
+    fn usage_is_grouped_by_month_and_endpoint() {
+        let mut summary = UsageSummary::default();
+        summary.record("2026-01-30", "2026-01", ENDPOINT, "Remote", &tokens(10, 5));
+        summary.record("2026-01-30", "2026-01", ENDPOINT, "Renamed", &tokens(1, 1));
+        summary.record(
+            "2026-01-30",
+            "2026-01",
+            OTHER_ENDPOINT,
+            "Local",
+            &tokens(7, 0),
+        );
+
+        assert_eq!(summary.entries.len(), 2);
+        assert_eq!(summary.entries[0].request_count, 2);
+        assert_eq!(summary.entries[0].endpoint_name, "Renamed");
+        assert_eq!(summary.usage_for_month("2026-01", ENDPOINT), tokens(11, 6));
+        assert_eq!(
+            summary.usage_for_month("2026-01", OTHER_ENDPOINT),
+            tokens(7, 0)
+        );
+        assert_eq!(summary.usage_for_day("2026-01-30", ENDPOINT), tokens(11, 6));
+    }
+
+    #[test]
+    fn a_new_month_and_day_start_from_zero() {
+        let mut summary = UsageSummary::default();
+        summary.record("2026-01-31", "2026-01", ENDPOINT, "Remote", &tokens(10, 5));
+        summary.record("2026-02-01", "2026-02", ENDPOINT, "Remote", &tokens(3, 2));
+
+        // the previous month is kept, but only the current day is
+        assert_eq!(summary.usage_for_month("2026-01", ENDPOINT), tokens(10, 5));
+        assert_eq!(summary.usage_for_month("2026-02", ENDPOINT), tokens(3, 2));
+        assert_eq!(summary.entries[1].request_count, 1);
+        assert_eq!(summary.daily.len(), 1);
+        assert_eq!(
+            summary.usage_for_day("2026-01-31", ENDPOINT),
+            Usage::default()
+        );
+        assert_eq!(summary.usage_for_day("2026-02-01", ENDPOINT), tokens(3, 2));
+    }
+
+    fn capped_config() -> ApiEndpointConfig {
+        ApiEndpointConfig {
+            endpoint: ENDPOINT.to_string(),
+            daily_token_limit: Some("1000".to_string()),
+            monthly_token_limit: Some(" 10000 ".to_string()),
+            daily_cost_limit: Some("1.0".to_string()),
+            monthly_cost_limit: Some("5".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn summary_with(day_usage: Usage, month_usage: Usage) -> UsageSummary {
+        let mut summary = UsageSummary::default();
+        summary.record("2026-01-30", "2026-01", ENDPOINT, "Remote", &month_usage);
+        summary.daily.clear();
+        summary.record("2026-01-31", "2026-01", ENDPOINT, "Remote", &day_usage);
+        summary
+    }
+
+    fn check(summary: &UsageSummary, config: &ApiEndpointConfig) -> SpendingCapStatus {
+        summary.check_spending_caps(config, "2026-01-31", "2026-01")
+    }
+
+    #[test]
+    fn usage_below_the_caps_is_within_limits() {
+        let summary = summary_with(tokens(100, 100), tokens(1000, 0));
+        assert_eq!(
+            check(&summary, &capped_config()),
+            SpendingCapStatus::WithinLimits
+        );
+    }
+
+    #[test]
+    fn every_token_and_cost_cap_is_enforced() {
+        let config = capped_config();
+        let summary = summary_with(tokens(600, 400), Usage::default());
+        assert!(
+            matches!(check(&summary, &config), SpendingCapStatus::Exceeded(m) if m.contains("daily token"))
+        );
+
+        let summary = summary_with(Usage::default(), tokens(9000, 1000));
+        assert!(
+            matches!(check(&summary, &config), SpendingCapStatus::Exceeded(m) if m.contains("monthly token"))
+        );
+
+        let costly = |cost| Usage {
+            cost,
+            ..Default::default()
+        };
+        let summary = summary_with(costly(1.5), Usage::default());
+        assert!(
+            matches!(check(&summary, &config), SpendingCapStatus::Exceeded(m) if m.contains("daily cost"))
+        );
+
+        let summary = summary_with(Usage::default(), costly(5.0));
+        assert!(
+            matches!(check(&summary, &config), SpendingCapStatus::Exceeded(m) if m.contains("monthly cost"))
+        );
+    }
+
+    #[test]
+    fn caps_past_the_threshold_warn() {
+        let mut config = capped_config();
+        let summary = summary_with(tokens(800, 0), Usage::default());
+        assert!(
+            matches!(check(&summary, &config), SpendingCapStatus::Warning(m) if m.starts_with("80% of the daily token"))
+        );
+
+        config.limit_warning_threshold = Some("90".to_string());
+        assert_eq!(check(&summary, &config), SpendingCapStatus::WithinLimits);
+
+        // an unparsable threshold falls back to the default
+        config.limit_warning_threshold = Some("most".to_string());
+        assert!(matches!(
+            check(&summary, &config),
+            SpendingCapStatus::Warning(_)
+        ));
+    }
+
+    #[test]
+    fn unset_or_unparsable_caps_are_ignored() {
+        let summary = summary_with(tokens(1_000_000, 0), tokens(1_000_000, 0));
+        assert_eq!(
+            check(&summary, &ApiEndpointConfig::default()),
+            SpendingCapStatus::WithinLimits
+        );
+
+        let config = ApiEndpointConfig {
+            endpoint: ENDPOINT.to_string(),
+            daily_token_limit: Some("lots".to_string()),
+            monthly_token_limit: Some(String::new()),
+            monthly_cost_limit: Some("-".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(check(&summary, &config), SpendingCapStatus::WithinLimits);
+
+        // a cap of zero means nothing may be spent at all
+        let config = ApiEndpointConfig {
+            endpoint: ENDPOINT.to_string(),
+            daily_cost_limit: Some("0".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            check(&UsageSummary::default(), &config),
+            SpendingCapStatus::Exceeded(_)
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+use sycamore::prelude::*;
+
+use crate::{
+    api_endpoint,
+    models::{
+        config::ApiEndpointConfig,
+        documents::{self, AttachedDocument, Citation},
+        search_index,
+    },
+};
+
+// Picks the chunks of the attached documents that are the most relevant to the query and
+// fit into the token budget. The chunks are ranked by embeddings when indexing is enabled
+// and every document was embedded with the configured model, and by BM25 keyword scoring
+// otherwise, which is also the fallback if the query can't be embedded.
+pub async fn retrieve_document_context(
+    api_config: &ApiEndpointConfig,
+    documents: &[AttachedDocument],
+    query: &str,
+    token_budget: u32,
+) -> Vec<Citation> {
+    if query.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let model_id = &api_config.embeddings.model_id;
+    let use_embeddings =
+        api_config.embeddings.enabled && documents.iter().all(|d| d.is_embedded_with(model_id));
+    let mut ranked = None;
+    if use_embeddings {
+        match api_endpoint::fetch_embeddings(api_config, &[query.to_string()]).await {
+            Ok(mut vectors) => {
+                let query_vector = vectors.pop().unwrap_or_default();
+                ranked = Some(documents::rank_chunks_by_vector(documents, &query_vector));
+            }
+            Err(e) => console_log!("Falling back to keyword retrieval: {}", e),
+        }
+    }
+    let ranked = ranked.unwrap_or_else(|| documents::rank_chunks_by_keywords(documents, query));
+    documents::select_citations(documents, &ranked, token_budget)
+}
+
+// Computes the vectors of every chunk of the document with the configured embeddings
+// model, so it can be retrieved by meaning instead of by keywords.
+pub async fn embed_document(
+    api_config: &ApiEndpointConfig,
+    mut document: AttachedDocument,
+) -> Result<AttachedDocument, String> {
+    let texts: Vec<String> = document.chunks.iter().map(|c| c.text.clone()).collect();
+    let mut vectors = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(api_endpoint::EMBEDDING_BATCH_SIZE) {
+        let batch_vectors = api_endpoint::fetch_embeddings(api_config, batch)
+            .await
+            .map_err(|e| e.to_string())?;
+        vectors.extend(batch_vectors);
+    }
+    for (chunk, vector) in document.chunks.iter_mut().zip(vectors) {
+        chunk.vector = Some(search_index::encode_vector(&vector));
+    }
+    document.model_id = api_config.embeddings.model_id.clone();
+    Ok(document)
+}
@@ -0,0 +1,190 @@
+use serde::Deserialize;
+use sycamore::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{
+    api_endpoint::{self, ApiError},
+    models::{
+        chatlog::{ChatLogMetadataEntry, Message},
+        config::ApiEndpointConfig,
+        notifications::Notifications,
+        search_index::{self, ChatlogIndex, IndexedChunk},
+    },
+    storage::{self, AppStorage, Storage},
+};
+
+// how many results a search returns at most
+const MAX_SEARCH_RESULTS: usize = 10;
+
+/// A message of a saved chatlog that matched a search query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub chatlog_id: String,
+    pub chatlog_title: String,
+    pub storage_key: String,
+    pub message_id: u32,
+    pub text: String,
+    pub score: f32,
+}
+
+// only the messages of a saved chatlog are needed to index it
+#[derive(Deserialize)]
+struct SavedMessages {
+    messages: Vec<Message>,
+}
+
+// Brings the search index of a saved chatlog up to date with its messages. Chunks that
+// were indexed before with the same embeddings model are reused, so only new or edited
+// messages are sent to the endpoint. Returns the number of chunks that were embedded.
+pub async fn update_chatlog_index(
+    api_config: &ApiEndpointConfig,
+    storage: &dyn Storage,
+    chatlog_id: &str,
+    messages: &[Message],
+) -> Result<usize, String> {
+    let key = ChatlogIndex::storage_key(chatlog_id);
+    let model_id = api_config.embeddings.model_id.clone();
+    let old_index = storage::load_record::<ChatlogIndex>(storage, &key)
+        .await
+        .filter(|index| index.model_id == model_id)
+        .unwrap_or_default();
+
+    let chunks = search_index::chunk_messages(messages);
+    let missing: Vec<String> = chunks
+        .iter()
+        .filter(|(id, text)| old_index.find_vector(*id, text).is_none())
+        .map(|(_, text)| text.clone())
+        .collect();
+
+    let mut new_vectors = Vec::with_capacity(missing.len());
+    for batch in missing.chunks(api_endpoint::EMBEDDING_BATCH_SIZE) {
+        let vectors = api_endpoint::fetch_embeddings(api_config, batch)
+            .await
+            .map_err(|e| e.to_string())?;
+        for vector in vectors {
+            new_vectors.push(search_index::encode_vector(&vector));
+        }
+    }
+
+    let mut new_vectors = new_vectors.into_iter();
+    let index = ChatlogIndex {
+        model_id,
+        chunks: chunks
+            .into_iter()
+            .filter_map(|(message_id, text)| {
+                let vector = old_index
+                    .find_vector(message_id, &text)
+                    .or_else(|| new_vectors.next())?;
+                Some(IndexedChunk {
+                    message_id,
+                    text,
+                    vector,
+                })
+            })
+            .collect(),
+    };
+
+    storage::save_record(storage, &key, &index)
+        .await
+        .map_err(|e| format!("unable to store the search index: {}", e))?;
+    Ok(missing.len())
+}
+
+// Updates the search index of a chatlog that was just saved, in the background. Nothing
+// happens unless indexing is enabled in the embeddings settings.
+pub fn index_saved_chatlog(
+    api_config: ApiEndpointConfig,
+    storage: AppStorage,
+    notifications: Notifications,
+    chatlog_id: String,
+    messages: Vec<Message>,
+) {
+    if !api_config.embeddings.enabled {
+        return;
+    }
+    spawn_local(async move {
+        if let Err(e) = update_chatlog_index(&api_config, &storage, &chatlog_id, &messages).await {
+            notifications.warning(format!("Unable to update the search index: {}", e));
+        }
+    });
+}
+
+// Indexes every saved chatlog, e.g. for the chatlogs saved before indexing was enabled
+// or after the embeddings model changed. Returns the number of chunks that were embedded.
+pub async fn index_all_chatlogs(
+    api_config: &ApiEndpointConfig,
+    storage: &dyn Storage,
+    entries: &[ChatLogMetadataEntry],
+) -> Result<usize, String> {
+    let mut embedded = 0;
+    for entry in entries {
+        let Some(json_str) = storage.load(&entry.storage_key).await else {
+            continue;
+        };
+        let Ok(saved) = serde_json::from_str::<SavedMessages>(&json_str) else {
+            console_log!(
+                "Skipping the unreadable chatlog '{}' for indexing.",
+                entry.title
+            );
+            continue;
+        };
+        embedded += update_chatlog_index(api_config, storage, &entry.id, &saved.messages).await?;
+    }
+    Ok(embedded)
+}
+
+// Removes the search index of a deleted chatlog.
+pub async fn delete_chatlog_index(storage: &dyn Storage, chatlog_id: &str) {
+    if let Err(e) = storage.delete(&ChatlogIndex::storage_key(chatlog_id)).await {
+        console_log!("Unable to delete the search index: {}", e);
+    }
+}
+
+// Embeds the query and returns the messages of the saved chatlogs that are the most
+// similar to it, best match first. Indexes built with another embeddings model are
+// skipped since their vectors can't be compared.
+pub async fn search_chatlogs(
+    api_config: &ApiEndpointConfig,
+    storage: &dyn Storage,
+    query: &str,
+    entries: &[ChatLogMetadataEntry],
+) -> Result<Vec<SearchResult>, ApiError> {
+    let query_vector = api_endpoint::fetch_embeddings(api_config, &[query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+
+    let mut results = Vec::new();
+    for entry in entries {
+        let Some(index) =
+            storage::load_record::<ChatlogIndex>(storage, &ChatlogIndex::storage_key(&entry.id))
+                .await
+        else {
+            continue;
+        };
+        if index.model_id != api_config.embeddings.model_id {
+            continue;
+        }
+
+        for chunk in index.chunks {
+            let Some(vector) = search_index::decode_vector(&chunk.vector) else {
+                continue;
+            };
+            results.push(SearchResult {
+                chatlog_id: entry.id.clone(),
+                chatlog_title: entry.title.clone(),
+                storage_key: entry.storage_key.clone(),
+                message_id: chunk.message_id,
+                text: chunk.text,
+                score: search_index::cosine_similarity(&query_vector, &vector),
+            });
+        }
+    }
+
+    // only the best chunk of each message is kept
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut seen = std::collections::HashSet::new();
+    results.retain(|r| seen.insert((r.chatlog_id.clone(), r.message_id)));
+    results.truncate(MAX_SEARCH_RESULTS);
+    Ok(results)
+}
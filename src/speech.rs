@@ -0,0 +1,144 @@
+use markdown::mdast::Node;
+use sycamore::prelude::*;
+
+use crate::{
+    api_endpoint,
+    models::{
+        chatlog::parse_think_block, config::ApiEndpointConfig, notifications::Notifications,
+        speech_player::SpeechPlayer,
+    },
+};
+
+// Reads the main content of a message aloud, leaving out the think block and the
+// markdown formatting. Any message that is already being read is stopped first.
+pub fn read_message_aloud(msg_id: u32, message: String) {
+    let text = speakable_text(message);
+    if text.is_empty() {
+        return;
+    }
+
+    let config = use_context::<Signal<ApiEndpointConfig>>().get_clone_untracked();
+    let player = use_context::<SpeechPlayer>();
+    let notifications = use_context::<Notifications>();
+    player.prepare(msg_id);
+    api_endpoint::send_speech_request(config, text, move |result| match result {
+        Ok(audio_data) => player.play(msg_id, audio_data),
+        Err(e) => {
+            if player.speaking_message().get_untracked() == Some(msg_id) {
+                player.stop();
+            }
+            notifications.error(format!("Unable to read the message aloud: {}", e));
+        }
+    });
+}
+
+// returns the plain text of the message's main content, without its think block
+fn speakable_text(message: String) -> String {
+    let content = match parse_think_block(message.clone()) {
+        Some((main_content, _)) => main_content,
+        None => message,
+    };
+    markdown_to_plain_text(&content)
+}
+
+// Converts markdown to plain text suitable for speech synthesis. Formatting is dropped,
+// links and images are reduced to their text and code blocks are left out entirely.
+pub fn markdown_to_plain_text(markdown: &str) -> String {
+    let Ok(root) = markdown::to_mdast(markdown, &markdown::ParseOptions::gfm()) else {
+        return markdown.trim().to_string();
+    };
+    let mut text = String::new();
+    collect_plain_text(&root, &mut text);
+
+    // collapse the blank lines left behind by the block level elements
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collect_plain_text(node: &Node, text: &mut String) {
+    match node {
+        Node::Text(t) => text.push_str(&t.value),
+        Node::InlineCode(c) => text.push_str(&c.value),
+        Node::InlineMath(m) => text.push_str(&m.value),
+        Node::Image(i) => text.push_str(&i.alt),
+        Node::Break(_) => text.push('\n'),
+        Node::Code(_)
+        | Node::Math(_)
+        | Node::Html(_)
+        | Node::ThematicBreak(_)
+        | Node::Definition(_)
+        | Node::Yaml(_)
+        | Node::Toml(_) => {}
+        Node::TableCell(_) => {
+            if let Some(children) = node.children() {
+                children.iter().for_each(|c| collect_plain_text(c, text));
+            }
+            text.push_str(", ");
+        }
+        _ => {
+            if let Some(children) = node.children() {
+                children.iter().for_each(|c| collect_plain_text(c, text));
+            }
+            // block level elements end their own line so the sentences don't run together
+            if matches!(
+                node,
+                Node::Paragraph(_) | Node::Heading(_) | Node::ListItem(_) | Node::TableRow(_)
+            ) {
+                text.push('\n');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_is_dropped() {
+        assert_eq!(
+            markdown_to_plain_text("# Title\n\nSome **bold** and *emphasized* `code`."),
+            "Title\nSome bold and emphasized code."
+        );
+    }
+
+    #[test]
+    fn code_blocks_are_left_out() {
+        let markdown = "Run this:\n\n```rust\nfn main() {}\n```\n\nThen check the output.";
+        assert_eq!(
+            markdown_to_plain_text(markdown),
+            "Run this:\nThen check the output."
+        );
+    }
+
+    #[test]
+    fn links_and_images_keep_their_text() {
+        assert_eq!(
+            markdown_to_plain_text("See [the docs](https://example.com) or ![a chart](chart.png)."),
+            "See the docs or a chart."
+        );
+    }
+
+    #[test]
+    fn list_items_get_their_own_lines() {
+        assert_eq!(
+            markdown_to_plain_text("Steps:\n\n- First\n- Second\n  1. Nested"),
+            "Steps:\nFirst\nSecond\nNested"
+        );
+    }
+
+    #[test]
+    fn think_blocks_are_not_read() {
+        assert_eq!(
+            speakable_text("<think>Plan the **answer**.</think>\nThe *answer*.".to_string()),
+            "The answer."
+        );
+        assert_eq!(
+            speakable_text("<think>Only thinking</think>".to_string()),
+            ""
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{
+    models::{
+        chatlog::{
+            ChatLogMetadata, ChatLogMetadataEntry, ForkOrigin, Message, saved_chatlog_shows,
+        },
+        usage::Usage,
+    },
+    search,
+};
+
+use super::{
+    Storage, StorageError, generate_chatlog_id, save_record,
+    schema::{self, SchemaError, UnreadableRecord},
+    set_aside,
+};
+
+// Loads the metadata of the saved chatlogs, upgrading it from an older format, or returns
+// empty metadata if nothing was saved yet. Metadata that can't be read is copied aside
+// first, since the app saves its own over it once it runs.
+pub async fn load_metadata(storage: &dyn Storage) -> Result<ChatLogMetadata, UnreadableRecord> {
+    let Some(json) = storage.load(crate::LSKEY_CHATLOG_METADATA).await else {
+        return Ok(ChatLogMetadata::default());
+    };
+    match read_metadata(&json) {
+        Ok(metadata) => Ok(metadata),
+        Err(e) => Err(set_aside(storage, crate::LSKEY_CHATLOG_METADATA, &json, e).await),
+    }
+}
+
+// deserializes the metadata, upgrading it from an older format.
+fn read_metadata(json: &str) -> Result<ChatLogMetadata, SchemaError> {
+    let value = schema::upgrade_metadata(serde_json::from_str(json)?)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+// Saves the metadata of the saved chatlogs.
+pub async fn save_metadata(
+    storage: &dyn Storage,
+    metadata: &ChatLogMetadata,
+) -> Result<(), StorageError> {
+    save_record(storage, crate::LSKEY_CHATLOG_METADATA, metadata).await
+}
+
+// Loads the JSON of the chatlog that was open last, or an empty string if there's none.
+pub async fn load_current_chatlog(storage: &dyn Storage) -> String {
+    storage
+        .load(crate::LSKEY_CURRENTLOG)
+        .await
+        .unwrap_or_default()
+}
+
+// Saves the JSON of the open chatlog so that it's opened again the next time.
+pub async fn save_current_chatlog(
+    storage: &dyn Storage,
+    json_str: &str,
+) -> Result<(), StorageError> {
+    storage.save(crate::LSKEY_CURRENTLOG, json_str).await
+}
+
+// Loads the JSON of the saved chatlog kept under the key, if it's still there.
+pub async fn load_chatlog(storage: &dyn Storage, storage_key: &str) -> Option<String> {
+    storage.load(storage_key).await
+}
+
+// Saves the JSON of a chatlog over the saved chatlog of the entry. The entry is up to the
+// caller to touch in the metadata.
+pub async fn save_chatlog(
+    storage: &dyn Storage,
+    entry: &ChatLogMetadataEntry,
+    json_str: &str,
+) -> Result<(), StorageError> {
+    storage.save(&entry.storage_key, json_str).await
+}
+
+// Deletes the saved chatlog of the entry along with its search index. The entry is up to
+// the caller to remove from the metadata.
+pub async fn delete_chatlog(
+    storage: &dyn Storage,
+    entry: &ChatLogMetadataEntry,
+) -> Result<(), StorageError> {
+    storage.delete(&entry.storage_key).await?;
+    search::delete_chatlog_index(storage, &entry.id).await;
+    Ok(())
+}
+
+// Returns the id of the saved chatlog that a fork of the messages is made from: the one
+// the active chatlog is linked to, as long as what's saved of it still shows the messages.
+pub async fn fork_parent(
+    storage: &dyn Storage,
+    linked: Option<&ChatLogMetadataEntry>,
+    messages: &[Message],
+) -> Option<String> {
+    let linked = linked?;
+    let json = storage.load(&linked.storage_key).await?;
+    saved_chatlog_shows(&json, messages).then(|| linked.id.clone())
+}
+
+// Saves the JSON of a chatlog under a newly generated key and returns the entry for it,
+// which is up to the caller to add to the metadata.
+pub async fn save_new_chatlog(
+    storage: &dyn Storage,
+    json_str: &str,
+    title: &str,
+    message_count: usize,
+    usage: Usage,
+    forked_from: Option<ForkOrigin>,
+    now: i64,
+) -> Result<ChatLogMetadataEntry, StorageError> {
+    let id = generate_chatlog_id();
+    let storage_key = format!("chatlog_{}", id);
+    storage.save(&storage_key, json_str).await?;
+
+    Ok(ChatLogMetadataEntry {
+        id,
+        title: title.to_string(),
+        last_accessed_time: now,
+        storage_key,
+        message_count,
+        usage,
+        forked_from,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sycamore::reactive::create_root;
+
+    use super::*;
+    use crate::{
+        models::{chatlog::Chatlog, config::ApiEndpointConfig, search_index::ChatlogIndex},
+        storage::{MemoryStorage, block_on},
+    };
+
+    #[test]
+    fn the_current_chatlog_is_kept_for_next_time() {
+        let storage = MemoryStorage::default();
+        block_on(async {
+            assert_eq!(load_current_chatlog(&storage).await, "");
+            save_current_chatlog(&storage, "{\"messages\":[]}")
+                .await
+                .unwrap();
+            assert_eq!(load_current_chatlog(&storage).await, "{\"messages\":[]}");
+        });
+    }
+
+    #[test]
+    fn saved_chatlogs_are_saved_over_and_deleted_with_their_index() {
+        let storage = MemoryStorage::default();
+        block_on(async {
+            let entry = save_new_chatlog(&storage, "{}", "Notes", 0, Usage::default(), None, 1)
+                .await
+                .unwrap();
+            save_chatlog(&storage, &entry, "{\"messages\":[]}")
+                .await
+                .unwrap();
+            assert_eq!(
+                load_chatlog(&storage, &entry.storage_key).await.as_deref(),
+                Some("{\"messages\":[]}")
+            );
+
+            let index_key = ChatlogIndex::storage_key(&entry.id);
+            storage.save(&index_key, "{}").await.unwrap();
+            delete_chatlog(&storage, &entry).await.unwrap();
+            assert_eq!(load_chatlog(&storage, &entry.storage_key).await, None);
+            assert_eq!(storage.load(&index_key).await, None);
+        });
+    }
+
+    #[test]
+    fn forks_are_made_from_the_linked_chatlog_while_it_shows_them() {
+        let storage = MemoryStorage::default();
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            log.add_message("Name a color.".to_string(), false, Vec::new());
+            log.add_message("Red.".to_string(), true, Vec::new());
+            let json = log
+                .to_json(ApiEndpointConfig::default(), String::new())
+                .unwrap();
+            let messages = log.fork(2).unwrap().messages.get_clone();
+            block_on(async {
+                let parent =
+                    save_new_chatlog(&storage, &json, "Colors", 2, Usage::default(), None, 1)
+                        .await
+                        .unwrap();
+                assert_eq!(fork_parent(&storage, None, &messages).await, None);
+                assert_eq!(
+                    fork_parent(&storage, Some(&parent), &messages).await,
+                    Some(parent.id.clone())
+                );
+
+                // once the messages were changed without saving, the fork is its own
+                let mut changed = messages.clone();
+                changed[1].message_stack[0].message = "Blue.".to_string();
+                assert_eq!(fork_parent(&storage, Some(&parent), &changed).await, None);
+
+                let forked_from = ForkOrigin {
+                    parent_id: Some(parent.id.clone()),
+                    message_id: 2,
+                };
+                let fork = save_new_chatlog(
+                    &storage,
+                    &json,
+                    "Colors (fork)",
+                    2,
+                    Usage::default(),
+                    Some(forked_from.clone()),
+                    2,
+                )
+                .await
+                .unwrap();
+                assert_eq!(fork.forked_from, Some(forked_from));
+
+                // a deleted parent can't be forked from either
+                delete_chatlog(&storage, &parent).await.unwrap();
+                assert_eq!(fork_parent(&storage, Some(&parent), &messages).await, None);
+            });
+        });
+    }
+
+    #[test]
+    fn metadata_defaults_when_nothing_was_saved() {
+        let storage = MemoryStorage::default();
+        assert_eq!(
+            block_on(load_metadata(&storage)),
+            Ok(ChatLogMetadata::default())
+        );
+    }
+
+    #[test]
+    fn new_chatlogs_are_saved_and_listed() {
+        let storage = MemoryStorage::default();
+        block_on(async {
+            let entry = save_new_chatlog(
+                &storage,
+                "{\"messages\":[]}",
+                "Notes",
+                3,
+                Usage::default(),
+                None,
+                42,
+            )
+            .await
+            .unwrap();
+            assert_eq!(entry.storage_key, format!("chatlog_{}", entry.id));
+            assert_eq!(entry.title, "Notes");
+            assert_eq!(entry.message_count, 3);
+            assert_eq!(entry.last_accessed_time, 42);
+            assert_eq!(
+                storage.load(&entry.storage_key).await.as_deref(),
+                Some("{\"messages\":[]}")
+            );
+
+            let mut metadata = load_metadata(&storage).await.unwrap();
+            metadata.saved_logs.push(entry.clone());
+            save_metadata(&storage, &metadata).await.unwrap();
+            assert_eq!(
+                load_metadata(&storage).await.unwrap().saved_logs,
+                vec![entry]
+            );
+        });
+    }
+
+    #[test]
+    fn entries_are_touched_and_removed() {
+        let storage = MemoryStorage::default();
+        let mut metadata = ChatLogMetadata::default();
+        block_on(async {
+            for title in ["first", "second"] {
+                let entry = save_new_chatlog(&storage, "{}", title, 0, Usage::default(), None, 1)
+                    .await
+                    .unwrap();
+                metadata.saved_logs.push(entry);
+            }
+        });
+        let first_id = metadata.saved_logs[0].id.clone();
+
+        let usage = Usage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            cost: 0.5,
+        };
+        metadata.touch_entry(&first_id, 7, usage, 99);
+        let first = &metadata.saved_logs[0];
+        assert_eq!(
+            (first.message_count, first.usage, first.last_accessed_time),
+            (7, usage, 99)
+        );
+        assert_eq!(metadata.saved_logs[1].last_accessed_time, 1);
+
+        metadata.remove_entry(&first_id);
+        assert_eq!(metadata.saved_logs.len(), 1);
+        assert_eq!(metadata.saved_logs[0].title, "second");
+    }
+
+    #[test]
+    fn newer_metadata_is_set_aside() {
+        let storage = MemoryStorage::default();
+        let newer = format!(
+            "{{\"version\":{},\"saved_logs\":[]}}",
+            schema::METADATA_VERSION + 1
+        );
+        block_on(async {
+            storage
+                .save(crate::LSKEY_CHATLOG_METADATA, &newer)
+                .await
+                .unwrap();
+            let record = load_metadata(&storage).await.unwrap_err();
+            assert!(matches!(record.error, SchemaError::TooNew { .. }));
+            let key = format!(
+                "{}.v{}",
+                crate::LSKEY_CHATLOG_METADATA,
+                schema::METADATA_VERSION + 1
+            );
+            assert_eq!(record.copy_key.as_deref(), Some(key.as_str()));
+            assert_eq!(storage.load(&key).await, Some(newer.clone()));
+        });
+    }
+
+    #[test]
+    fn unreadable_metadata_survives_being_saved_over() {
+        let storage = MemoryStorage::default();
+        let damaged = "{\"saved_logs\":[{\"id\":";
+        block_on(async {
+            storage
+                .save(crate::LSKEY_CHATLOG_METADATA, damaged)
+                .await
+                .unwrap();
+            let record = load_metadata(&storage).await.unwrap_err();
+            assert!(matches!(record.error, SchemaError::Invalid(_)));
+
+            // the app goes on with empty metadata and saves it over the damaged one
+            save_metadata(&storage, &ChatLogMetadata::default())
+                .await
+                .unwrap();
+            let copy_key = record.copy_key.unwrap();
+            assert_eq!(storage.load(&copy_key).await.as_deref(), Some(damaged));
+        });
+    }
+}
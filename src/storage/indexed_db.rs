@@ -0,0 +1,453 @@
+use std::collections::{HashMap, HashSet};
+
+use sycamore::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{Array, Promise, Uint8Array};
+use web_sys::wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use web_sys::{
+    Blob, BlobPropertyBag, IdbDatabase, IdbKeyRange, IdbRequest, IdbTransaction, IdbTransactionMode,
+};
+
+use super::{Storage, StorageError, StorageFuture, schema};
+
+const DATABASE_NAME: &str = "switchboard";
+const DATABASE_VERSION: u32 = 1;
+
+// the object store for the serialized chatlogs and metadata, keyed like the local storage
+const RECORDS_STORE: &str = "records";
+
+// the object store for the images of the chatlogs, stored as blobs under
+// `{record key}/{hash of the image}`
+const ATTACHMENTS_STORE: &str = "attachments";
+
+// images stored in the attachments store are replaced in the JSON of their record by this
+// prefix followed by the key of the attachment
+const ATTACHMENT_REF_PREFIX: &str = "idb-attachment:";
+
+// A write to one of the object stores. The changes passed to `apply_changes` are made
+// in a single transaction, so a chatlog and its attachments are updated together.
+enum Change {
+    Put {
+        store: &'static str,
+        key: String,
+        value: JsValue,
+    },
+    Delete {
+        store: &'static str,
+        key: String,
+    },
+    // deletes every key that starts with the prefix
+    DeletePrefix {
+        store: &'static str,
+        prefix: String,
+    },
+}
+
+// resolves with the result of the request once it succeeds, or with its error.
+async fn wait_for_request(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let success_request = request.clone();
+        let on_success = Closure::once_into_js(move || {
+            let result = success_request.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::NULL, &result);
+        });
+        let error_request = request.clone();
+        let on_error = Closure::once_into_js(move || {
+            let error = match error_request.error() {
+                Ok(Some(error)) => JsValue::from(error),
+                _ => JsValue::from_str("IndexedDB request failed"),
+            };
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
+
+// resolves once the transaction is committed, or with its error if it was aborted.
+async fn wait_for_transaction(transaction: &IdbTransaction) -> Result<(), JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let on_complete = Closure::once_into_js(move || {
+            let _ = resolve.call0(&JsValue::NULL);
+        });
+        let error_transaction = transaction.clone();
+        let on_abort = Closure::once_into_js(move || {
+            let error = match error_transaction.error() {
+                Some(error) => JsValue::from(error),
+                None => JsValue::from_str("IndexedDB transaction was aborted"),
+            };
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+        transaction.set_oncomplete(Some(on_complete.unchecked_ref()));
+        // errors of the requests abort the transaction, which reports the error
+        transaction.set_onabort(Some(on_abort.unchecked_ref()));
+    });
+    JsFuture::from(promise).await.map(|_| ())
+}
+
+// Opens the database of the app, creating the object stores on the first run. Fails
+// when the browser doesn't support IndexedDB or blocks it, such as in some private modes.
+async fn open_database() -> Result<IdbDatabase, JsValue> {
+    let factory = window()
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from_str("IndexedDB isn't available in this browser"))?;
+    let request = factory.open_with_u32(DATABASE_NAME, DATABASE_VERSION)?;
+
+    let upgrade_request = request.clone();
+    let on_upgrade_needed = Closure::once_into_js(move || {
+        let Ok(result) = upgrade_request.result() else {
+            return;
+        };
+        let database: IdbDatabase = result.unchecked_into();
+        for store in [RECORDS_STORE, ATTACHMENTS_STORE] {
+            if !database.object_store_names().contains(store)
+                && let Err(e) = database.create_object_store(store)
+            {
+                console_log!("Unable to create the '{}' object store: {:?}", store, e);
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade_needed.unchecked_ref()));
+
+    let database = wait_for_request(&request).await?;
+    Ok(database.unchecked_into())
+}
+
+// Reads the values of the keys from the store, in the same order. Keys that don't exist
+// come back as `undefined`.
+async fn get_values(
+    database: &IdbDatabase,
+    store: &str,
+    keys: &[String],
+) -> Result<Vec<JsValue>, JsValue> {
+    let transaction = database.transaction_with_str(store)?;
+    let object_store = transaction.object_store(store)?;
+    // every request is made before awaiting any of them, so the transaction stays active
+    let requests = keys
+        .iter()
+        .map(|key| object_store.get(&JsValue::from_str(key)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut values = Vec::with_capacity(requests.len());
+    for request in requests {
+        values.push(wait_for_request(&request).await?);
+    }
+    Ok(values)
+}
+
+// Reads a single value from the store, or `None` if the key doesn't exist.
+async fn get_value(
+    database: &IdbDatabase,
+    store: &str,
+    key: &str,
+) -> Result<Option<JsValue>, JsValue> {
+    let value = get_values(database, store, &[key.to_string()])
+        .await?
+        .pop()
+        .unwrap_or(JsValue::UNDEFINED);
+    Ok((!value.is_undefined()).then_some(value))
+}
+
+// Makes all of the changes in one read-write transaction and waits for it to commit.
+async fn apply_changes(database: &IdbDatabase, changes: Vec<Change>) -> Result<(), JsValue> {
+    let stores = Array::of2(
+        &JsValue::from_str(RECORDS_STORE),
+        &JsValue::from_str(ATTACHMENTS_STORE),
+    );
+    let transaction =
+        database.transaction_with_str_sequence_and_mode(&stores, IdbTransactionMode::Readwrite)?;
+    for change in changes {
+        match change {
+            Change::Put { store, key, value } => {
+                transaction
+                    .object_store(store)?
+                    .put_with_key(&value, &JsValue::from_str(&key))?;
+            }
+            Change::Delete { store, key } => {
+                transaction
+                    .object_store(store)?
+                    .delete(&JsValue::from_str(&key))?;
+            }
+            Change::DeletePrefix { store, prefix } => {
+                transaction
+                    .object_store(store)?
+                    .delete(&prefix_range(&prefix)?)?;
+            }
+        }
+    }
+    wait_for_transaction(&transaction).await
+}
+
+// the range of the keys that start with the prefix.
+fn prefix_range(prefix: &str) -> Result<JsValue, JsValue> {
+    IdbKeyRange::bound(
+        &JsValue::from_str(prefix),
+        &JsValue::from_str(&format!("{}\u{ffff}", prefix)),
+    )
+    .map(JsValue::from)
+}
+
+// Lists the keys of the store that start with the prefix.
+async fn get_keys_with_prefix(
+    database: &IdbDatabase,
+    store: &str,
+    prefix: &str,
+) -> Result<Vec<String>, JsValue> {
+    let transaction = database.transaction_with_str(store)?;
+    let request = transaction
+        .object_store(store)?
+        .get_all_keys_with_key(&prefix_range(prefix)?)?;
+    let keys: Array = wait_for_request(&request).await?.unchecked_into();
+    Ok(keys.iter().filter_map(|key| key.as_string()).collect())
+}
+
+// Lists every key of the store.
+async fn get_keys(database: &IdbDatabase, store: &str) -> Result<Vec<String>, JsValue> {
+    let transaction = database.transaction_with_str(store)?;
+    let request = transaction.object_store(store)?.get_all_keys()?;
+    let keys: Array = wait_for_request(&request).await?.unchecked_into();
+    Ok(keys.iter().filter_map(|key| key.as_string()).collect())
+}
+
+// the key an image of the record is stored under. it's derived from the image itself,
+// so an image keeps its key from one save to the next and only new images are written.
+fn attachment_key(record_key: &str, data_url: &str) -> String {
+    // 64-bit FNV-1a, which stays the same across builds unlike the hasher of the std
+    let hash = data_url.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{}/{:016x}-{}", record_key, hash, data_url.len())
+}
+
+// replaces the base64 data URLs in the image lists of the chatlog with references to
+// attachments. returns the images that aren't stored yet, decoded as blobs, and the keys
+// of every image the record refers to. images that don't decode are left inline.
+fn extract_images(
+    value: &mut serde_json::Value,
+    key: &str,
+    stored: &HashSet<String>,
+) -> (HashMap<String, Blob>, HashSet<String>) {
+    let mut new_images = HashMap::new();
+    let mut referenced = HashSet::new();
+    let images = schema::image_lists(value).into_iter().flatten();
+    for image in images {
+        let Some(data_url) = image.as_str() else {
+            continue;
+        };
+        let attachment_key = attachment_key(key, data_url);
+        if !stored.contains(&attachment_key) && !new_images.contains_key(&attachment_key) {
+            let Some(blob) = data_url_to_blob(data_url) else {
+                continue;
+            };
+            new_images.insert(attachment_key.clone(), blob);
+        }
+        *image = format!("{}{}", ATTACHMENT_REF_PREFIX, attachment_key).into();
+        referenced.insert(attachment_key);
+    }
+    (new_images, referenced)
+}
+
+// collects the attachment references in the image lists of the chatlog.
+fn find_attachment_refs(value: &mut serde_json::Value) -> Vec<String> {
+    let mut refs = schema::image_lists(value)
+        .into_iter()
+        .flatten()
+        .filter_map(|image| image.as_str()?.strip_prefix(ATTACHMENT_REF_PREFIX))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    refs.sort();
+    refs.dedup();
+    refs
+}
+
+// puts the images back in place of their references, as data URLs.
+fn restore_images(value: &mut serde_json::Value, images: &HashMap<String, String>) {
+    for image in schema::image_lists(value).into_iter().flatten() {
+        if let Some(data_url) = image
+            .as_str()
+            .and_then(|s| s.strip_prefix(ATTACHMENT_REF_PREFIX))
+            .and_then(|k| images.get(k))
+        {
+            *image = data_url.clone().into();
+        }
+    }
+}
+
+// decodes a base64 data URL into a blob of the same type.
+fn data_url_to_blob(data_url: &str) -> Option<Blob> {
+    let (mime_type, data) = data_url.strip_prefix("data:")?.split_once(";base64,")?;
+    let binary = window().atob(data).ok()?;
+    let bytes: Vec<u8> = binary.chars().map(|c| c as u8).collect();
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    Blob::new_with_u8_array_sequence_and_options(
+        &Array::of1(&Uint8Array::from(bytes.as_slice())),
+        &options,
+    )
+    .ok()
+}
+
+// encodes the blob as a base64 data URL.
+async fn blob_to_data_url(blob: &Blob) -> Result<String, JsValue> {
+    let buffer = JsFuture::from(blob.array_buffer()).await?;
+    let binary: String = Uint8Array::new(&buffer)
+        .to_vec()
+        .into_iter()
+        .map(char::from)
+        .collect();
+    Ok(format!(
+        "data:{};base64,{}",
+        blob.type_(),
+        window().btoa(&binary)?
+    ))
+}
+
+/// The browser's IndexedDB, which has far more room than the local storage. The images
+/// in the JSON are stored as blobs of their own rather than as base64 text.
+pub struct IndexedDbStorage {
+    database: IdbDatabase,
+}
+
+impl IndexedDbStorage {
+    // opens the database, which fails when the browser doesn't support IndexedDB or
+    // blocks it, such as in some private modes.
+    pub async fn open() -> Result<Self, JsValue> {
+        Ok(Self {
+            database: open_database().await?,
+        })
+    }
+
+    // loads the images referenced by the JSON and puts them back in place.
+    async fn restore_attachments(&self, key: &str, value: &mut serde_json::Value) {
+        let refs = find_attachment_refs(value);
+        if refs.is_empty() {
+            return;
+        }
+
+        let blobs = match get_values(&self.database, ATTACHMENTS_STORE, &refs).await {
+            Ok(blobs) => blobs,
+            Err(e) => {
+                console_log!("Unable to read the images of '{}': {:?}", key, e);
+                return;
+            }
+        };
+        let mut images = HashMap::new();
+        for (attachment_key, blob) in refs.into_iter().zip(blobs) {
+            let Ok(blob) = blob.dyn_into::<Blob>() else {
+                console_log!("The image '{}' is missing.", attachment_key);
+                continue;
+            };
+            match blob_to_data_url(&blob).await {
+                Ok(data_url) => {
+                    images.insert(attachment_key, data_url);
+                }
+                Err(e) => console_log!("Unable to read the image '{}': {:?}", attachment_key, e),
+            }
+        }
+        restore_images(value, &images);
+    }
+}
+
+impl Storage for IndexedDbStorage {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move {
+            let json = match get_value(&self.database, RECORDS_STORE, key).await {
+                Ok(value) => value?.as_string()?,
+                Err(e) => {
+                    console_log!("Unable to read '{}' from IndexedDB: {:?}", key, e);
+                    return None;
+                }
+            };
+            let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&json) else {
+                return Some(json);
+            };
+            self.restore_attachments(key, &mut value).await;
+            Some(value.to_string())
+        })
+    }
+
+    // the attachments of the previous version of the record are replaced in the same
+    // transaction as the record itself.
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        json: &'a str,
+    ) -> StorageFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            // text that isn't JSON, such as a damaged record being set aside, has no images
+            // and is stored as it is
+            let Ok(mut value) = serde_json::from_str::<serde_json::Value>(json) else {
+                let changes = vec![Change::Put {
+                    store: RECORDS_STORE,
+                    key: key.to_string(),
+                    value: JsValue::from_str(json),
+                }];
+                return apply_changes(&self.database, changes)
+                    .await
+                    .map_err(StorageError::from_js);
+            };
+            let prefix = format!("{}/", key);
+            let stored = get_keys_with_prefix(&self.database, ATTACHMENTS_STORE, &prefix)
+                .await
+                .map_err(StorageError::from_js)?
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let (new_images, referenced) = extract_images(&mut value, key, &stored);
+
+            // images that were removed from the record are deleted, and only the ones that
+            // weren't stored before are written
+            let mut changes = stored
+                .difference(&referenced)
+                .map(|attachment_key| Change::Delete {
+                    store: ATTACHMENTS_STORE,
+                    key: attachment_key.clone(),
+                })
+                .collect::<Vec<_>>();
+            for (attachment_key, blob) in new_images {
+                changes.push(Change::Put {
+                    store: ATTACHMENTS_STORE,
+                    key: attachment_key,
+                    value: blob.into(),
+                });
+            }
+            changes.push(Change::Put {
+                store: RECORDS_STORE,
+                key: key.to_string(),
+                value: JsValue::from_str(&value.to_string()),
+            });
+            apply_changes(&self.database, changes)
+                .await
+                .map_err(StorageError::from_js)
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            let changes = vec![
+                Change::Delete {
+                    store: RECORDS_STORE,
+                    key: key.to_string(),
+                },
+                Change::DeletePrefix {
+                    store: ATTACHMENTS_STORE,
+                    prefix: format!("{}/", key),
+                },
+            ];
+            apply_changes(&self.database, changes)
+                .await
+                .map_err(StorageError::from_js)
+        })
+    }
+
+    fn keys(&self) -> StorageFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            get_keys(&self.database, RECORDS_STORE)
+                .await
+                .unwrap_or_else(|e| {
+                    console_log!("Unable to list the keys in IndexedDB: {:?}", e);
+                    Vec::new()
+                })
+        })
+    }
+}
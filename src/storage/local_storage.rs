@@ -0,0 +1,67 @@
+use sycamore::prelude::*;
+
+use super::{Storage, StorageError, StorageFuture};
+
+/// The browser's local storage. It only holds a few megabytes per origin, so it's only
+/// used for the chatlogs when IndexedDB isn't available.
+pub struct LocalStorage;
+
+impl LocalStorage {
+    // returns true if the browser allows the page to use the local storage.
+    pub fn is_available() -> bool {
+        window()
+            .local_storage()
+            .is_ok_and(|storage| storage.is_some())
+    }
+
+    fn storage() -> Result<web_sys::Storage, StorageError> {
+        window()
+            .local_storage()
+            .map_err(StorageError::from_js)?
+            .ok_or_else(|| StorageError::Failed("the local storage is unavailable".to_string()))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move {
+            let value = Self::storage().ok()?.get_item(key).ok()??;
+            // chatlogs used to be stored as a JSON string that holds the chatlog's JSON
+            match serde_json::from_str::<String>(&value) {
+                Ok(json) => Some(json),
+                Err(_) => Some(value),
+            }
+        })
+    }
+
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        json: &'a str,
+    ) -> StorageFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            Self::storage()?
+                .set_item(key, json)
+                .map_err(StorageError::from_js)
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            Self::storage()?
+                .remove_item(key)
+                .map_err(StorageError::from_js)
+        })
+    }
+
+    fn keys(&self) -> StorageFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            let Ok(storage) = Self::storage() else {
+                return Vec::new();
+            };
+            (0..storage.length().unwrap_or(0))
+                .filter_map(|i| storage.key(i).ok().flatten())
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use super::{Storage, StorageError, StorageFuture};
+
+/// Keeps everything in memory, for when the browser allows no storage at all and for
+/// tests. Nothing outlives the page.
+#[derive(Default)]
+pub struct MemoryStorage {
+    values: RefCell<BTreeMap<String, String>>,
+}
+
+impl Storage for MemoryStorage {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(async move { self.values.borrow().get(key).cloned() })
+    }
+
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        json: &'a str,
+    ) -> StorageFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            self.values
+                .borrow_mut()
+                .insert(key.to_string(), json.to_string());
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            self.values.borrow_mut().remove(key);
+            Ok(())
+        })
+    }
+
+    fn keys(&self) -> StorageFuture<'_, Vec<String>> {
+        Box::pin(async move { self.values.borrow().keys().cloned().collect() })
+    }
+}
@@ -0,0 +1,160 @@
+use serde_json::Value;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::StorageEstimate;
+use web_sys::wasm_bindgen::JsCast;
+
+use super::schema;
+use crate::image_processing::data_url_size;
+
+/// How much room the JSON of a stored chatlog takes, with its images told apart from
+/// the rest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChatlogFootprint {
+    pub text_bytes: usize,
+    pub image_bytes: Vec<usize>, // the decoded size of every image
+    pub index_bytes: usize,      // the search index kept for a saved chatlog
+}
+
+impl ChatlogFootprint {
+    pub fn images_total(&self) -> usize {
+        self.image_bytes.iter().sum()
+    }
+
+    pub fn total(&self) -> usize {
+        self.text_bytes + self.images_total() + self.index_bytes
+    }
+}
+
+/// The room the browser reports as used by the page and the most it will let the page use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaEstimate {
+    pub usage: f64,
+    pub quota: f64,
+}
+
+// returns true for the base64 data URLs that images are kept as.
+fn is_data_url(s: &str) -> bool {
+    s.starts_with("data:") && s.contains(";base64,")
+}
+
+// Measures the JSON of a chatlog. Images are counted by their decoded size, since that's
+// how they're kept in IndexedDB.
+pub fn measure_chatlog(json: &str) -> ChatlogFootprint {
+    let mut footprint = ChatlogFootprint::default();
+    let mut data_url_chars = 0;
+    if let Ok(mut value) = serde_json::from_str::<Value>(json) {
+        let images = schema::image_lists(&mut value).into_iter().flatten();
+        for image in images.filter_map(|i| i.as_str()).filter(|i| is_data_url(i)) {
+            footprint.image_bytes.push(data_url_size(image));
+            data_url_chars += image.len();
+        }
+    }
+    footprint.text_bytes = json.len().saturating_sub(data_url_chars);
+    footprint
+}
+
+// Removes every image from the JSON of a chatlog and returns the new JSON along with
+// the number of images that were removed. Only the lists of images are emptied; the
+// text of the messages is kept as it is, even where it holds a data URL.
+pub fn strip_images(json: &str) -> Result<(String, usize), String> {
+    let mut value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut removed = 0;
+    for images in schema::image_lists(&mut value) {
+        let before = images.len();
+        images.retain(|i| !i.as_str().is_some_and(is_data_url));
+        removed += before - images.len();
+    }
+    Ok((value.to_string(), removed))
+}
+
+// Asks the browser how much storage the page uses and how much it may use. Not every
+// browser can tell.
+pub async fn estimate_quota() -> Option<QuotaEstimate> {
+    let promise = web_sys::window()?.navigator().storage().estimate().ok()?;
+    let estimate: StorageEstimate = JsFuture::from(promise).await.ok()?.unchecked_into();
+    Some(QuotaEstimate {
+        usage: estimate.get_usage()?,
+        quota: estimate.get_quota()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sycamore::reactive::create_root;
+
+    use super::*;
+    use crate::models::{chatlog::Chatlog, config::ApiEndpointConfig};
+
+    // "hello" in base64 takes up 8 characters and decodes to 5 bytes
+    const IMAGE: &str = "data:image/png;base64,aGVsbG8=";
+
+    // a chatlog with two images in the first message, one in a message kept in the branch
+    // of another variant, and a data URL written into the text of a message
+    fn sample_chatlog() -> String {
+        let mut json = String::new();
+        let _ = create_root(|| {
+            let mut log = Chatlog::new(|| {});
+            let images = vec![IMAGE.to_string(), IMAGE.to_string()];
+            log.add_message("look".to_string(), false, images);
+            log.add_message("Nice.".to_string(), true, Vec::new());
+            log.add_message("and this?".to_string(), false, vec![IMAGE.to_string()]);
+            log.push_to_message_stack(2, format!("Try {}", IMAGE), Vec::new());
+            json = log
+                .to_json(ApiEndpointConfig::default(), String::new())
+                .unwrap();
+        });
+        json
+    }
+
+    #[test]
+    fn images_are_measured_apart_from_the_text() {
+        let json = sample_chatlog();
+        let footprint = measure_chatlog(&json);
+        assert_eq!(footprint.image_bytes, vec![5, 5, 5]);
+        assert_eq!(footprint.text_bytes, json.len() - 3 * IMAGE.len());
+        assert_eq!(footprint.total(), footprint.text_bytes + 15);
+    }
+
+    #[test]
+    fn the_search_index_counts_towards_the_total() {
+        let footprint = ChatlogFootprint {
+            index_bytes: 100,
+            ..measure_chatlog(&sample_chatlog())
+        };
+        assert_eq!(footprint.total(), footprint.text_bytes + 15 + 100);
+    }
+
+    #[test]
+    fn stripping_removes_every_image() {
+        let (stripped, removed) = strip_images(&sample_chatlog()).unwrap();
+        assert_eq!(removed, 3);
+        assert!(measure_chatlog(&stripped).image_bytes.is_empty());
+
+        // the stripped chatlog still loads, with the text of its messages untouched
+        let _ = create_root(|| {
+            let (mut log, _, _) = Chatlog::from_json(&stripped, || {}).unwrap();
+            let message = log.get_message(2).unwrap().get_selected_message().unwrap();
+            assert_eq!(message.message, format!("Try {}", IMAGE));
+            assert!(
+                log.get_message(1).unwrap().message_stack[0]
+                    .images
+                    .is_empty()
+            );
+
+            // the branch of the other variant lost its image as well
+            log.update_selected_index(2, -1);
+            let message = log.get_message(3).unwrap().get_selected_message().unwrap();
+            assert_eq!(message.message, "and this?");
+            assert!(message.images.is_empty());
+        });
+    }
+
+    #[test]
+    fn text_only_chatlogs_are_left_alone() {
+        let json = r#"{"messages":[{"id":0,"message_stack":[{"message":"data: not an image"}]}]}"#;
+        assert_eq!(measure_chatlog(json).text_bytes, json.len());
+        let (stripped, removed) = strip_images(json).unwrap();
+        assert_eq!(removed, 0);
+        assert_eq!(measure_chatlog(&stripped).image_bytes, Vec::<usize>::new());
+    }
+}
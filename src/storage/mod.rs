@@ -0,0 +1,301 @@
+use std::{fmt, future::Future, pin::Pin, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+use sycamore::prelude::*;
+use uuid::Uuid;
+use web_sys::DomException;
+use web_sys::wasm_bindgen::{JsCast, JsValue};
+
+use crate::models::search_index::ChatlogIndex;
+use schema::{SchemaError, UnreadableRecord};
+
+pub mod chatlogs;
+mod indexed_db;
+mod local_storage;
+mod memory;
+pub mod meter;
+pub mod schema;
+
+pub use indexed_db::IndexedDbStorage;
+pub use local_storage::LocalStorage;
+pub use memory::MemoryStorage;
+
+/// The future returned by the storage backends. Everything runs on the browser's main
+/// thread, so it doesn't need to be `Send`.
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// Why something couldn't be written to or removed from storage.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageError {
+    /// The browser won't let the page store any more data.
+    QuotaExceeded,
+    Failed(String),
+}
+
+impl StorageError {
+    // classifies an error thrown by a browser storage API. browsers name the quota error
+    // differently, and older ones only set its legacy code.
+    fn from_js(error: JsValue) -> Self {
+        if let Some(exception) = error.dyn_ref::<DomException>()
+            && (matches!(
+                exception.name().as_str(),
+                "QuotaExceededError" | "NS_ERROR_DOM_QUOTA_REACHED"
+            ) || exception.code() == DomException::QUOTA_EXCEEDED_ERR)
+        {
+            return Self::QuotaExceeded;
+        }
+        Self::Failed(error.as_string().unwrap_or_else(|| format!("{:?}", error)))
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::QuotaExceeded => write!(f, "the browser's storage is full"),
+            Self::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// A place where the chatlogs and their metadata are kept, as serialized JSON under a key.
+pub trait Storage {
+    /// Returns the JSON stored under the key, if there is any.
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>>;
+
+    /// Stores the JSON under the key, replacing whatever was there before.
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        json: &'a str,
+    ) -> StorageFuture<'a, Result<(), StorageError>>;
+
+    /// Removes the key along with anything that was stored for it.
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Result<(), StorageError>>;
+
+    /// Returns every key that's stored.
+    fn keys(&self) -> StorageFuture<'_, Vec<String>>;
+}
+
+/// The storage backend the app keeps the chatlogs in, provided as a context.
+#[derive(Clone)]
+pub struct AppStorage(Rc<dyn Storage>);
+
+impl AppStorage {
+    pub fn new(backend: impl Storage + 'static) -> Self {
+        Self(Rc::new(backend))
+    }
+}
+
+impl Storage for AppStorage {
+    fn load<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        self.0.load(key)
+    }
+
+    fn save<'a>(
+        &'a self,
+        key: &'a str,
+        json: &'a str,
+    ) -> StorageFuture<'a, Result<(), StorageError>> {
+        self.0.save(key, json)
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Result<(), StorageError>> {
+        self.0.delete(key)
+    }
+
+    fn keys(&self) -> StorageFuture<'_, Vec<String>> {
+        self.0.keys()
+    }
+}
+
+// Picks the storage backend for the app: IndexedDB if the browser allows it, after moving
+// anything still in the local storage into it, otherwise the local storage. As a last
+// resort everything is kept in memory and doesn't outlive the page.
+pub async fn open_storage() -> AppStorage {
+    match IndexedDbStorage::open().await {
+        Ok(database) => {
+            if LocalStorage::is_available()
+                && let Err(e) = migrate(&LocalStorage, &database).await
+            {
+                console_log!("Unable to move the chatlogs into IndexedDB: {}", e);
+            }
+            return AppStorage::new(database);
+        }
+        Err(e) => console_log!(
+            "IndexedDB is unavailable, falling back to the local storage: {:?}",
+            e
+        ),
+    }
+
+    if LocalStorage::is_available() {
+        return AppStorage::new(LocalStorage);
+    }
+    console_log!("No storage is available; chatlogs will only be kept until the page closes.");
+    AppStorage::new(MemoryStorage::default())
+}
+
+// returns true for the keys of the chatlogs, saved or current, their metadata and their
+// search indexes, as opposed to the settings that always stay in the local storage.
+fn is_chatlog_key(key: &str) -> bool {
+    key == crate::LSKEY_CURRENTLOG
+        || key.starts_with("chatlog_")
+        || key.starts_with(ChatlogIndex::KEY_PREFIX)
+}
+
+// Moves the chatlogs and their metadata from one backend into another and returns how
+// many keys were moved. Keys are only deleted from the old backend once they were written
+// to the new one, so an interrupted migration picks up where it left off on the next start.
+pub async fn migrate(from: &dyn Storage, to: &dyn Storage) -> Result<usize, StorageError> {
+    let mut moved = 0;
+    for key in from.keys().await {
+        if !is_chatlog_key(&key) {
+            continue;
+        }
+        let Some(json) = from.load(&key).await else {
+            continue;
+        };
+        to.save(&key, &json).await?;
+        from.delete(&key).await?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+// Loads the record stored under the key, if there is one that can be read.
+pub async fn load_record<T: for<'a> Deserialize<'a>>(
+    storage: &dyn Storage,
+    key: &str,
+) -> Option<T> {
+    let json = storage.load(key).await?;
+    serde_json::from_str(&json).ok()
+}
+
+// Serializes the record and stores it under the key.
+pub async fn save_record<T: Serialize>(
+    storage: &dyn Storage,
+    key: &str,
+    value: &T,
+) -> Result<(), StorageError> {
+    let json = serde_json::to_string(value).map_err(|e| StorageError::Failed(e.to_string()))?;
+    storage.save(key, &json).await
+}
+
+// copies a record that couldn't be read aside, under the key for its error, so that it
+// isn't lost once the app saves its own data under the original key.
+pub async fn set_aside(
+    storage: &dyn Storage,
+    key: &str,
+    json: &str,
+    error: SchemaError,
+) -> UnreadableRecord {
+    let copy_key = schema::set_aside_key(key, &error);
+    match storage.save(&copy_key, json).await {
+        Ok(()) => UnreadableRecord {
+            error,
+            copy_key: Some(copy_key),
+        },
+        Err(e) => {
+            console_log!("Failed to set aside '{}': {}", key, e);
+            UnreadableRecord {
+                error,
+                copy_key: None,
+            }
+        }
+    }
+}
+
+// helper function to save text to local storage as it is
+pub fn save_text_to_local_storage(key: &str, text: &str) -> Result<(), StorageError> {
+    let window = window();
+    if let Ok(opt_storage) = window.local_storage()
+        && let Some(local_storage) = opt_storage
+    {
+        return local_storage
+            .set_item(key, text)
+            .map_err(StorageError::from_js);
+    }
+
+    Err(StorageError::Failed(
+        "Failed to attempt to get local_storage for the window.".to_string(),
+    ))
+}
+
+// helper function to save to local storage
+pub fn save_to_local_storage<T: Serialize>(key: &str, value: &T) -> Result<(), StorageError> {
+    let serialized =
+        serde_json::to_string(value).map_err(|e| StorageError::Failed(e.to_string()))?;
+    save_text_to_local_storage(key, &serialized)
+}
+
+// helper function to load the text stored in local storage as it is
+pub fn load_text_from_local_storage(key: &str) -> Option<String> {
+    window().local_storage().ok()??.get_item(key).ok()?
+}
+
+// helper function to load from local storage
+pub fn load_from_local_storage<T: for<'a> Deserialize<'a>>(key: &str) -> Option<T> {
+    serde_json::from_str(&load_text_from_local_storage(key)?).ok()
+}
+
+/// Generates a new GUID for a saved chatlog
+pub fn generate_chatlog_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+// runs a storage future to completion. the in-memory backend never has to wait, so a
+// single poll is all it takes.
+#[cfg(test)]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+
+    let mut future = std::pin::pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("the in-memory storage should never have to wait"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_moves_only_the_chatlogs() {
+        let from = MemoryStorage::default();
+        let to = MemoryStorage::default();
+        block_on(async {
+            from.save("chatlog_1", "{\"a\":1}").await.unwrap();
+            from.save("chatlog_metadata", "{}").await.unwrap();
+            from.save(crate::LSKEY_CURRENTLOG, "{}").await.unwrap();
+            from.save("search_index_1", "{}").await.unwrap();
+            from.save("dark_mode", "true").await.unwrap();
+
+            assert_eq!(migrate(&from, &to).await, Ok(4));
+            assert_eq!(from.keys().await, vec!["dark_mode".to_string()]);
+            assert_eq!(to.load("chatlog_1").await.as_deref(), Some("{\"a\":1}"));
+            assert_eq!(to.keys().await.len(), 4);
+
+            // nothing is left to move the second time
+            assert_eq!(migrate(&from, &to).await, Ok(0));
+        });
+    }
+
+    #[test]
+    fn records_are_saved_as_json() {
+        let storage = MemoryStorage::default();
+        block_on(async {
+            save_record(&storage, "numbers", &vec![1, 2, 3])
+                .await
+                .unwrap();
+            assert_eq!(storage.load("numbers").await.as_deref(), Some("[1,2,3]"));
+            assert_eq!(
+                load_record::<Vec<i32>>(&storage, "numbers").await,
+                Some(vec![1, 2, 3])
+            );
+            assert_eq!(load_record::<Vec<i32>>(&storage, "missing").await, None);
+        });
+    }
+}
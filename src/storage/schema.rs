@@ -0,0 +1,389 @@
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::models::usage::Usage;
+
+/// Why a stored chatlog, chatlog list or configuration couldn't be read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    /// It was written by a newer version of the app than this one.
+    TooNew {
+        found: u16,
+        supported: u16,
+    },
+    Invalid(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooNew { found, supported } => write!(
+                f,
+                "it was saved by a newer version of the app (format version {}, while this \
+                 version reads up to {})",
+                found, supported
+            ),
+            Self::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SchemaError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Invalid(e.to_string())
+    }
+}
+
+// upgrades a record in place from one version of its format to the next
+type Step = fn(&mut Value);
+
+// The history of a stored format. The record is at version `first` when it has no version
+// field, and `steps[n]` upgrades it from version `first + n` to the one after that, so the
+// current version is always the one after the last step.
+struct Format {
+    first: u16,
+    steps: &'static [Step],
+}
+
+impl Format {
+    const fn current(&self) -> u16 {
+        self.first + self.steps.len() as u16
+    }
+
+    // runs the steps the record is missing, one version at a time, and stamps every
+    // version on the record as it goes.
+    fn upgrade(&self, mut value: Value) -> Result<Value, SchemaError> {
+        let Some(record) = value.as_object_mut() else {
+            return Err(SchemaError::Invalid("expected a JSON object".to_string()));
+        };
+        let found = match record.get("version") {
+            None => self.first,
+            Some(version) => version
+                .as_u64()
+                .and_then(|v| u16::try_from(v).ok())
+                .ok_or_else(|| SchemaError::Invalid(format!("invalid version: {}", version)))?,
+        };
+        if found > self.current() {
+            return Err(SchemaError::TooNew {
+                found,
+                supported: self.current(),
+            });
+        }
+
+        value["version"] = Value::from(found.max(self.first));
+        for version in found.max(self.first)..self.current() {
+            self.steps[(version - self.first) as usize](&mut value);
+            value["version"] = Value::from(version + 1);
+        }
+        Ok(value)
+    }
+}
+
+// The format of a chatlog, as saved, exported and kept as the current chatlog.
+//   1: the original format; a message had at most one image, under `image_base64`
+//   2: messages and queued messages hold a list of images under `images`
+//   3: every variant of a message has its own branch of the messages that follow it, kept
+//      under `continuation` while another variant is selected. the flat chatlogs before
+//      shared the later messages between all variants, which now make up the branch of
+//      the selected one, so they're read as they are
+const CHATLOG: Format = Format {
+    first: 1,
+    steps: &[chatlog_v1_to_v2, |_| {}],
+};
+
+// The format of the list of saved chatlogs.
+//   1: the original format
+//   2: every entry has the tokens and cost of its chatlog under `usage`
+//   3: the entries of forked chatlogs tell where they were forked from under `forked_from`;
+//      no entry was forked before, so they're read as they are
+const METADATA: Format = Format {
+    first: 1,
+    steps: &[metadata_v1_to_v2, |_| {}],
+};
+
+// The format of the API settings, stored on their own and inside every chatlog.
+//   0: the original format, without a version field
+//   1: the version field was added; settings added since are filled in by their defaults
+const CONFIG: Format = Format {
+    first: 0,
+    steps: &[|_| {}],
+};
+
+/// The version chatlogs are saved with.
+pub const CHATLOG_VERSION: u16 = CHATLOG.current();
+
+/// The version the list of saved chatlogs is saved with.
+pub const METADATA_VERSION: u16 = METADATA.current();
+
+/// The version the API settings are saved with.
+pub const CONFIG_VERSION: u16 = CONFIG.current();
+
+/// Upgrades the JSON of a chatlog, including its API settings, to the current format.
+pub fn upgrade_chatlog(value: Value) -> Result<Value, SchemaError> {
+    let mut value = CHATLOG.upgrade(value)?;
+    if let Some(settings) = value.get_mut("api_settings") {
+        *settings = upgrade_config(settings.take())?;
+    }
+    Ok(value)
+}
+
+/// Upgrades the JSON of the list of saved chatlogs to the current format.
+pub fn upgrade_metadata(value: Value) -> Result<Value, SchemaError> {
+    METADATA.upgrade(value)
+}
+
+/// Upgrades the JSON of the API settings to the current format.
+pub fn upgrade_config(value: Value) -> Result<Value, SchemaError> {
+    CONFIG.upgrade(value)
+}
+
+/// A stored record that couldn't be read, along with the key a copy of it was kept under,
+/// if it could be copied aside.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnreadableRecord {
+    pub error: SchemaError,
+    pub copy_key: Option<String>,
+}
+
+/// The key a record that can't be read is copied to, so that it isn't lost when the app
+/// saves its own data under the original key. Records of a newer version are kept by
+/// their version, for when the app is updated.
+pub fn set_aside_key(key: &str, error: &SchemaError) -> String {
+    match error {
+        SchemaError::TooNew { found, .. } => format!("{}.v{}", key, found),
+        SchemaError::Invalid(_) => format!("{}.unreadable", key),
+    }
+}
+
+/// Returns the lists of images in the JSON of a chatlog: those of every variant of every
+/// message, including the branches kept with the variants, and those of the queued
+/// messages. Data URLs anywhere else, such as in the text of a message, aren't images.
+pub fn image_lists(chatlog: &mut Value) -> Vec<&mut Vec<Value>> {
+    fn from_messages<'a>(messages: &'a mut Value, lists: &mut Vec<&'a mut Vec<Value>>) {
+        let variants = messages
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m.get_mut("message_stack").and_then(Value::as_array_mut))
+            .flatten()
+            .filter_map(Value::as_object_mut);
+        for variant in variants {
+            for (field, value) in variant.iter_mut() {
+                match (field.as_str(), value) {
+                    ("images", Value::Array(images)) => lists.push(images),
+                    ("continuation", continuation) => from_messages(continuation, lists),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut lists = Vec::new();
+    let Some(chatlog) = chatlog.as_object_mut() else {
+        return lists;
+    };
+    for (field, value) in chatlog.iter_mut() {
+        match field.as_str() {
+            "messages" => from_messages(value, &mut lists),
+            "queued_messages" => lists.extend(
+                value
+                    .as_array_mut()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|q| q.get_mut("images").and_then(Value::as_array_mut)),
+            ),
+            _ => {}
+        }
+    }
+    lists
+}
+
+// replaces the single optional `image_base64` of a message with a list of `images`.
+fn move_image_into_list(message: &mut Map<String, Value>) {
+    if let Some(image) = message.remove("image_base64") {
+        let images = match image {
+            Value::String(image) => vec![Value::String(image)],
+            _ => Vec::new(),
+        };
+        message.entry("images").or_insert(Value::Array(images));
+    }
+}
+
+fn chatlog_v1_to_v2(chatlog: &mut Value) {
+    if let Some(messages) = chatlog["messages"].as_array_mut() {
+        messages
+            .iter_mut()
+            .filter_map(|m| m["message_stack"].as_array_mut())
+            .flatten()
+            .filter_map(Value::as_object_mut)
+            .for_each(move_image_into_list);
+    }
+    if let Some(queued) = chatlog
+        .get_mut("queued_messages")
+        .and_then(Value::as_array_mut)
+    {
+        queued
+            .iter_mut()
+            .filter_map(Value::as_object_mut)
+            .for_each(move_image_into_list);
+    }
+}
+
+fn metadata_v1_to_v2(metadata: &mut Value) {
+    if let Some(entries) = metadata["saved_logs"].as_array_mut() {
+        for entry in entries.iter_mut().filter_map(Value::as_object_mut) {
+            entry
+                .entry("usage")
+                .or_insert_with(|| serde_json::json!(Usage::default()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        chatlog::{ChatLogMetadata, Chatlog},
+        config::ApiEndpointConfig,
+    };
+
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn chatlog_v1_is_upgraded() {
+        let value = upgrade_chatlog(fixture(include_str!("fixtures/chatlog_v1.json"))).unwrap();
+        assert_eq!(value["version"], CHATLOG_VERSION);
+        assert_eq!(value["api_settings"]["version"], CONFIG_VERSION);
+
+        let stack = &value["messages"][0]["message_stack"];
+        assert_eq!(
+            stack[0]["images"],
+            serde_json::json!(["data:image/png;base64,AAAA"])
+        );
+        assert_eq!(stack[0].get("image_base64"), None);
+        assert_eq!(
+            value["messages"][1]["message_stack"][0]["images"],
+            serde_json::json!([])
+        );
+        assert_eq!(
+            value["queued_messages"][0]["images"],
+            serde_json::json!(["data:image/png;base64,BBBB"])
+        );
+
+        let _ = sycamore::reactive::create_root(|| {
+            let json = serde_json::to_string(&value).unwrap();
+            let (log, config, system_message) = Chatlog::from_json(&json, || {}).unwrap();
+            assert_eq!(log.messages.get_clone().len(), 2);
+            assert_eq!(config.model_id, "gpt-4o");
+            assert_eq!(system_message, "Be brief.");
+        });
+    }
+
+    #[test]
+    fn flat_chatlog_v2_keeps_its_messages_on_the_selected_branch() {
+        let value = upgrade_chatlog(fixture(include_str!("fixtures/chatlog_v2.json"))).unwrap();
+        assert_eq!(value["version"], CHATLOG_VERSION);
+
+        let _ = sycamore::reactive::create_root(|| {
+            let json = serde_json::to_string(&value).unwrap();
+            let (mut log, _, _) = Chatlog::from_json(&json, || {}).unwrap();
+            assert_eq!(log.messages.with(Vec::len), 4);
+
+            // the other variant has no branch of its own yet
+            log.update_selected_index(2, -1);
+            assert_eq!(log.messages.with(Vec::len), 2);
+            log.update_selected_index(2, 1);
+            assert_eq!(log.messages.with(Vec::len), 4);
+        });
+    }
+
+    #[test]
+    fn only_the_image_lists_hold_images() {
+        let _ = sycamore::reactive::create_root(|| {
+            let image = |n: u8| format!("data:image/png;base64,{}", n);
+            let mut log = Chatlog::new(|| {});
+            log.add_message(format!("see {}", image(0)), false, vec![image(1), image(2)]);
+            log.add_message("Red.".to_string(), true, vec![image(3)]);
+            log.add_message("Why?".to_string(), false, vec![image(8)]);
+            // the last message moves into the branch of the first answer
+            log.push_to_message_stack(2, "Blue.".to_string(), vec![image(4)]);
+            log.queue_message(image(5), vec![image(6)], Vec::new(), false);
+            let json = log.to_json(ApiEndpointConfig::default(), image(7)).unwrap();
+
+            let mut value: Value = serde_json::from_str(&json).unwrap();
+            let mut images = image_lists(&mut value)
+                .into_iter()
+                .flatten()
+                .map(|i| i.as_str().unwrap().to_string())
+                .collect::<Vec<_>>();
+            images.sort();
+            assert_eq!(images, [1, 2, 3, 4, 6, 8].map(image));
+        });
+    }
+
+    #[test]
+    fn metadata_v1_is_upgraded() {
+        let value = upgrade_metadata(fixture(include_str!("fixtures/metadata_v1.json"))).unwrap();
+        let metadata: ChatLogMetadata = serde_json::from_value(value).unwrap();
+        assert_eq!(metadata.version, METADATA_VERSION);
+        assert_eq!(metadata.saved_logs.len(), 2);
+        assert_eq!(metadata.saved_logs[0].title, "Recipes");
+        assert_eq!(metadata.saved_logs[0].usage, Default::default());
+    }
+
+    #[test]
+    fn unversioned_config_is_upgraded() {
+        let value = upgrade_config(fixture(include_str!("fixtures/config_v0.json"))).unwrap();
+        let config: ApiEndpointConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.endpoint, "http://localhost:8080/v1");
+        assert_eq!(config.temperature.as_deref(), Some("0.7"));
+        assert_eq!(config.embeddings, Default::default());
+    }
+
+    #[test]
+    fn current_formats_are_left_alone() {
+        let config = serde_json::to_value(ApiEndpointConfig::default()).unwrap();
+        assert_eq!(upgrade_config(config.clone()), Ok(config));
+
+        let metadata = serde_json::to_value(ChatLogMetadata::default()).unwrap();
+        assert_eq!(upgrade_metadata(metadata.clone()), Ok(metadata));
+    }
+
+    #[test]
+    fn newer_formats_are_refused() {
+        let newer = serde_json::json!({ "version": CHATLOG_VERSION + 1, "messages": [] });
+        assert_eq!(
+            upgrade_chatlog(newer),
+            Err(SchemaError::TooNew {
+                found: CHATLOG_VERSION + 1,
+                supported: CHATLOG_VERSION
+            })
+        );
+
+        // a chatlog is refused when the settings inside it are too new as well
+        let newer_settings = serde_json::json!({
+            "version": CHATLOG_VERSION,
+            "api_settings": { "version": CONFIG_VERSION + 1 },
+        });
+        assert!(matches!(
+            upgrade_chatlog(newer_settings),
+            Err(SchemaError::TooNew { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_versions_are_refused() {
+        assert!(matches!(
+            upgrade_metadata(serde_json::json!({ "version": "two" })),
+            Err(SchemaError::Invalid(_))
+        ));
+        assert!(matches!(
+            upgrade_config(serde_json::json!([])),
+            Err(SchemaError::Invalid(_))
+        ));
+    }
+}